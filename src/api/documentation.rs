@@ -2,15 +2,73 @@
 #[openapi(
     paths(
         crate::api::login,
+        crate::api::captcha_info,
+        crate::api::register,
         crate::api::logout,
+        crate::api::forgot_password,
+        crate::api::reset_password,
+        crate::api::totp_setup,
+        crate::api::totp_enable,
+        crate::api::totp_disable,
+        crate::api::change_password,
+        crate::api::change_email,
+        crate::api::resend_verification,
+        crate::api::verify_email,
+        crate::api::change_username,
+        crate::api::list_tokens,
+        crate::api::create_token,
+        crate::api::revoke_token,
+        crate::api::list_ssh_keys,
+        crate::api::add_ssh_key,
+        crate::api::delete_ssh_key,
+        crate::api::list_identities,
+        crate::api::unlink_identity,
+        crate::api::list_invites,
+        crate::api::create_invite,
+        crate::api::revoke_invite,
+        crate::api::list_sessions,
+        crate::api::revoke_session,
+        crate::api::revoke_all_sessions,
         crate::api::create_repo,
         crate::api::delete_repo,
+        crate::api::update_repo,
+        crate::api::fork_repo,
+        crate::api::list_forks,
+        crate::api::rename_repo,
+        crate::api::transfer_repo,
         crate::api::list_repos,
         crate::api::branches,
         crate::api::delete_branch,
         crate::api::content,
         crate::api::commits,
-        crate::api::download
+        crate::api::download,
+        crate::api::list_collaborators,
+        crate::api::add_collaborator,
+        crate::api::remove_collaborator,
+        crate::api::list_deploy_keys,
+        crate::api::add_deploy_key,
+        crate::api::delete_deploy_key,
+        crate::api::list_deploy_tokens,
+        crate::api::create_deploy_token,
+        crate::api::revoke_deploy_token,
+        crate::api::admin_stats,
+        crate::api::admin_users,
+        crate::api::admin_suspend_user,
+        crate::api::admin_delete_user,
+        crate::api::admin_repos,
+        crate::api::admin_delete_repo,
+        crate::api::admin_transfer_repo,
+        crate::api::create_org,
+        crate::api::org_members,
+        crate::api::set_org_member,
+        crate::api::remove_org_member,
+        crate::api::org_teams,
+        crate::api::create_team,
+        crate::api::delete_team,
+        crate::api::add_team_member,
+        crate::api::remove_team_member,
+        crate::api::add_team_repo,
+        crate::api::remove_team_repo
     ),
     components(
         schemas(
@@ -18,7 +76,37 @@
             crate::models::LoginRequest,
             crate::models::LoginResponse,
             crate::models::RegisterRequest,
+            crate::models::CaptchaResponse,
+            crate::models::CaptchaInfo,
+            crate::models::ForgotPasswordRequest,
+            crate::models::ResetPasswordRequest,
+            crate::models::TotpSetupResponse,
+            crate::models::TotpEnableRequest,
+            crate::models::RecoveryCodesResponse,
+            crate::models::TotpDisableRequest,
+            crate::models::ChangePasswordRequest,
+            crate::models::ChangeEmailRequest,
+            crate::models::VerifyEmailRequest,
+            crate::models::ChangeUsernameRequest,
+            crate::models::CreateTokenRequest,
+            crate::models::TokenInfo,
+            crate::models::CreatedTokenResponse,
+            crate::models::IdentityInfo,
+            crate::models::AddSshKeyRequest,
+            crate::models::SshKeyInfo,
+            crate::models::CreateInviteRequest,
+            crate::models::InviteInfo,
+            crate::models::CreatedInviteResponse,
+            crate::models::RevokeTokenQuery,
+            crate::models::SessionInfo,
+            crate::models::RevokeSessionQuery,
+            crate::models::RevokedResponse,
             crate::models::CreateRepoRequest,
+            crate::models::UpdateRepoRequest,
+            crate::models::ForkRepoRequest,
+            crate::models::ForksQuery,
+            crate::models::RenameRepoRequest,
+            crate::models::TransferRepoRequest,
             crate::models::DeleteQuery,
             crate::models::OkResponse,
             crate::models::ReposQuery,
@@ -28,10 +116,44 @@
             crate::models::ContentQuery,
             crate::models::ContentResponse,
             crate::models::CommitsQuery,
+            crate::models::CollaboratorsQuery,
+            crate::models::AddCollaboratorRequest,
+            crate::models::RemoveCollaboratorQuery,
+            crate::models::CollaboratorInfo,
+            crate::models::DeployQuery,
+            crate::models::AddDeployKeyRequest,
+            crate::models::DeployKeyInfo,
+            crate::models::CreateDeployTokenRequest,
+            crate::models::DeployTokenInfo,
+            crate::models::CreatedDeployTokenResponse,
+            crate::models::AdminSearchQuery,
+            crate::models::AdminUserQuery,
+            crate::models::SuspendUserRequest,
+            crate::models::AdminUserInfo,
+            crate::models::AdminRepoInfo,
+            crate::models::InstanceStats,
+            crate::models::CreateOrgRequest,
+            crate::models::OrgQuery,
+            crate::models::SetOrgMemberRequest,
+            crate::models::RemoveOrgMemberQuery,
+            crate::models::OrgMemberInfo,
+            crate::models::CreateTeamRequest,
+            crate::models::TeamQuery,
+            crate::models::TeamMemberQuery,
+            crate::models::TeamRepoQuery,
+            crate::models::TeamInfo,
             // db models
             crate::db::Repository,
             crate::db::Token,
+            crate::db::TokenKind,
+            crate::db::Scope,
             crate::db::User,
+            crate::db::Role,
+            crate::db::Collaborator,
+            crate::db::Organization,
+            crate::db::OrgRole,
+            crate::db::OrgMember,
+            crate::db::Team,
             // repo models
             crate::models::EntryKind,
             crate::models::TreeEntry,
@@ -44,7 +166,11 @@
     tags(
         (name = "auth", description = "Authentication endpoints"),
         (name = "repos", description = "Repository management"),
-        (name = "git", description = "Git data browsing")
+        (name = "git", description = "Git data browsing"),
+        (name = "collaborators", description = "Repository collaborators"),
+        (name = "deploy", description = "Per-repository deploy keys and tokens"),
+        (name = "admin", description = "Site administration"),
+        (name = "orgs", description = "Organizations and teams")
     ),
     modifiers(
         &SecurityAddon
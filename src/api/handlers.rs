@@ -2,10 +2,15 @@ use actix_web::{get, post, delete, web, HttpRequest, HttpResponse, Responder};
 
 use crate::db::Database;
 use crate::errors::AuthError;
+use crate::auth::ClientInfo;
+use crate::auth_backend::AuthBackend;
+use crate::captcha::Captcha;
+use crate::mail::Mailer;
+use crate::oidc::Oidc;
 use crate::api::service;
 use crate::models::*;
 use bson::oid::ObjectId;
-use crate::db::Repository;
+use crate::db::{Repository, Scope, Token, TokenKind};
 
 // ----------------- helpers -----------------
 
@@ -31,13 +36,76 @@ fn bearer_token(req: &HttpRequest) -> Result<String, AuthError> {
     }
 }
 
-async fn optional_requester(db: &Database, req: &HttpRequest) -> Option<ObjectId> {
+/// The requester, or `None` when anonymous or when their token lacks `scope`.
+async fn optional_requester(db: &Database, req: &HttpRequest, scope: Scope) -> Option<ObjectId> {
+    let token = service::token_info(db, bearer_token(req).ok()?).await.ok()?;
+    token.allows(scope).then_some(token.user)
+}
+
+/// Maps the error strings returned by the service layer onto HTTP statuses.
+fn service_error(msg: String) -> HttpResponse {
+    match msg.as_str() {
+        "forbidden" | "email not verified" => HttpResponse::Forbidden().json(error_message(&msg)),
+        "already exists" => HttpResponse::Conflict().json(error_message(&msg)),
+        "too many failed attempts" => HttpResponse::TooManyRequests().json(error_message(&msg)),
+        m if m.ends_with("not found") => HttpResponse::NotFound().json(error_message(&msg)),
+        "invalid name"
+        | "name must not be empty"
+        | "at least one scope is required"
+        | "invalid expiry"
+        | "invalid max uses"
+        | "organization must keep at least one owner"
+        | "user is not a member of the organization"
+        | "wrong password"
+        | "invalid password"
+        | "invalid email"
+        | "invalid key"
+        | "cannot modify your own account"
+        | "invalid website"
+        | "cannot transfer to this owner"
+        | "invalid topic"
+        | "too many topics"
+        | "forks of private repositories must stay private"
+        | "email already verified" => HttpResponse::BadRequest().json(error_message(&msg)),
+        _ => to_http_500(msg),
+    }
+}
+
+async fn requester_token(db: &Database, req: &HttpRequest) -> Result<Token, HttpResponse> {
     match bearer_token(req) {
-        Ok(token) => service::get_user_id_from_token(db, token)
-            .await
-            .ok(),
-        Err(_) => None,
+        Ok(t) => match service::token_info(db, t).await {
+            Ok(token) => Ok(token),
+            Err(AuthError::InvalidCredentials) => {
+                Err(HttpResponse::Unauthorized().json(error_message("unauthorized")))
+            }
+            Err(e) => Err(to_http_500(e)),
+        },
+        Err(AuthError::MissingAuthHeader | AuthError::InvalidAuthHeader) => {
+            Err(HttpResponse::Unauthorized().json(error_message("unauthorized")))
+        }
+        Err(e) => Err(to_http_500(e)),
+    }
+}
+
+async fn require_requester(db: &Database, req: &HttpRequest, scope: Scope) -> Result<ObjectId, HttpResponse> {
+    let token = requester_token(db, req).await?;
+    if !token.allows(scope) {
+        return Err(HttpResponse::Forbidden().json(error_message("insufficient scope")));
+    }
+    Ok(token.user)
+}
+
+/// Account management (2FA, tokens, sessions) is limited to login sessions.
+async fn require_session_token(db: &Database, req: &HttpRequest) -> Result<Token, HttpResponse> {
+    let token = requester_token(db, req).await?;
+    if token.kind != TokenKind::Session {
+        return Err(HttpResponse::Forbidden().json(error_message("session token required")));
     }
+    Ok(token)
+}
+
+async fn require_session(db: &Database, req: &HttpRequest) -> Result<ObjectId, HttpResponse> {
+    require_session_token(db, req).await.map(|t| t.user)
 }
 
 // ----------------- auth -----------------
@@ -48,17 +116,81 @@ async fn optional_requester(db: &Database, req: &HttpRequest) -> Option<ObjectId
     request_body = LoginRequest,
     responses(
         (status = 200, description = "Logged in successfully", body = LoginResponse),
-        (status = 401, description = "Invalid credentials", body = ErrorResponse)
+        (status = 401, description = "Invalid credentials, or `otp required` for 2FA accounts", body = ErrorResponse),
+        (status = 403, description = "The account is suspended", body = ErrorResponse),
+        (status = 429, description = "Too many failed attempts; see the Retry-After header", body = ErrorResponse)
     ),
     tag = "auth"
 )]
 #[post("/api/v1/login")]
-pub async fn login(db: web::Data<Database>, payload: web::Json<LoginRequest>) -> impl Responder {
-    match service::auth_login(&db, payload.login.clone(), payload.password.clone()).await {
+pub async fn login(
+    db: web::Data<Database>,
+    backend: web::Data<dyn AuthBackend>,
+    req: HttpRequest,
+    payload: web::Json<LoginRequest>,
+) -> impl Responder {
+    let payload = payload.into_inner();
+    let client = ClientInfo::from_request(&req);
+    match service::auth_login(&db, backend.get_ref(), payload.login, payload.password, payload.otp, client).await {
         Ok(token) => HttpResponse::Ok().json(LoginResponse { token }),
         Err(AuthError::InvalidCredentials) => {
             HttpResponse::Unauthorized().json(error_message("invalid credentials"))
         }
+        Err(AuthError::OtpRequired) => {
+            HttpResponse::Unauthorized().json(error_message("otp required"))
+        }
+        Err(AuthError::Suspended) => HttpResponse::Forbidden().json(error_message("account suspended")),
+        Err(AuthError::TooManyAttempts(secs)) => HttpResponse::TooManyRequests()
+            .insert_header((actix_web::http::header::RETRY_AFTER, secs.to_string()))
+            .json(error_message("too many failed attempts")),
+        Err(e) => to_http_500(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/captcha",
+    responses(
+        (status = 200, description = "Captcha to solve before registering, with a fresh challenge for the offline providers", body = CaptchaInfo)
+    ),
+    tag = "auth"
+)]
+#[get("/api/v1/captcha")]
+pub async fn captcha_info(captcha: web::Data<dyn Captcha>) -> impl Responder {
+    HttpResponse::Ok().json(captcha.info())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/register",
+    request_body = RegisterRequest,
+    responses(
+        (status = 201, description = "Account created; a verification link is emailed", body = OkResponse),
+        (status = 400, description = "Invalid or taken username, email or password, invalid invite code, or failed captcha", body = ErrorResponse),
+        (status = 403, description = "Registration is disabled", body = ErrorResponse)
+    ),
+    tag = "auth"
+)]
+#[post("/api/v1/register")]
+pub async fn register(
+    db: web::Data<Database>,
+    mailer: web::Data<dyn Mailer>,
+    captcha: web::Data<dyn Captcha>,
+    req: HttpRequest,
+    payload: web::Json<RegisterRequest>,
+) -> impl Responder {
+    let payload = payload.into_inner();
+    let remote_ip = req.connection_info().realip_remote_addr().map(|s| s.to_string());
+    if !captcha.verify(&payload.captcha, remote_ip.as_deref()).await {
+        return HttpResponse::BadRequest().json(error_message("captcha verification failed"));
+    }
+    match service::auth_register(&db, mailer.get_ref(), payload.username, payload.email, payload.password, payload.invite).await {
+        Ok(()) => HttpResponse::Created().json(OkResponse { ok: true }),
+        Err(AuthError::RegistrationDisabled) => HttpResponse::Forbidden().json(error_message("registration disabled")),
+        Err(AuthError::InvalidInvite) => HttpResponse::BadRequest().json(error_message("invalid invite")),
+        Err(AuthError::InvalidCredentials) => {
+            HttpResponse::BadRequest().json(error_message("invalid or taken username, email or password"))
+        }
         Err(e) => to_http_500(e),
     }
 }
@@ -92,275 +224,995 @@ pub async fn logout(db: web::Data<Database>, req: HttpRequest) -> impl Responder
     }
 }
 
-// ----------------- repos -----------------
-
 #[utoipa::path(
     post,
-    path = "/api/v1/create",
-    security(("bearerAuth" = [])),
-    request_body = CreateRepoRequest,
+    path = "/api/v1/password/forgot",
+    request_body = ForgotPasswordRequest,
     responses(
-        (status = 201, description = "Repository created", body = Repository),
-        (status = 400, description = "Invalid request"),
-        (status = 401, description = "Unauthorized"),
-        (status = 409, description = "Already exists")
+        (status = 202, description = "A reset link is emailed if the account exists", body = OkResponse)
     ),
-    tag = "repos"
+    tag = "auth"
 )]
-#[post("/api/v1/create")]
-pub async fn create_repo(
-    db: web::Data<Database>,
-    req: HttpRequest,
-    payload: web::Json<CreateRepoRequest>,
-) -> impl Responder {
-    let user_id = match bearer_token(&req) {
-        Ok(t) => match service::get_user_id_from_token(&db, t).await {
-            Ok(uid) => uid,
-            Err(AuthError::InvalidCredentials) => {
-                return HttpResponse::Unauthorized().json(error_message("unauthorized"))
-            }
-            Err(e) => return to_http_500(e),
-        },
-        Err(AuthError::MissingAuthHeader | AuthError::InvalidAuthHeader) => {
-            return HttpResponse::Unauthorized().json(error_message("unauthorized"))
-        }
-        Err(e) => return to_http_500(e),
-    };
-
-    match service::repo_create(&db, user_id, payload.into_inner()).await {
-        Ok(repo) => HttpResponse::Created().json(repo),
-        Err(msg) if msg == "name must not be empty" => HttpResponse::BadRequest().json(error_message(&msg)),
-        Err(msg) if msg == "already exists" => HttpResponse::Conflict().json(error_message(&msg)),
+#[post("/api/v1/password/forgot")]
+pub async fn forgot_password(db: web::Data<Database>, mailer: web::Data<dyn Mailer>, payload: web::Json<ForgotPasswordRequest>) -> impl Responder {
+    match service::auth_request_password_reset(&db, mailer.get_ref(), &payload.login).await {
+        Ok(()) => HttpResponse::Accepted().json(OkResponse { ok: true }),
         Err(e) => to_http_500(e),
     }
 }
 
 #[utoipa::path(
-    delete,
-    path = "/api/v1/delete",
-    security(("bearerAuth" = [])),
-    params(DeleteQuery),
+    post,
+    path = "/api/v1/password/reset",
+    request_body = ResetPasswordRequest,
     responses(
-        (status = 200, description = "Repository deleted", body = OkResponse),
-        (status = 401, description = "Unauthorized"),
-        (status = 403, description = "Forbidden"),
-        (status = 404, description = "Repository not found")
+        (status = 200, description = "Password changed; all sessions are signed out and access tokens revoked", body = OkResponse),
+        (status = 400, description = "Invalid or expired token, or invalid password", body = ErrorResponse)
     ),
-    tag = "repos"
+    tag = "auth"
 )]
-#[delete("/api/v1/delete")]
-pub async fn delete_repo(
-    db: web::Data<Database>,
-    req: HttpRequest,
-    query: web::Query<DeleteQuery>,
-) -> impl Responder {
-    let requester = match bearer_token(&req) {
-        Ok(t) => match service::get_user_id_from_token(&db, t).await {
-            Ok(uid) => uid,
-            Err(AuthError::InvalidCredentials) => {
-                return HttpResponse::Unauthorized().json(error_message("unauthorized"))
-            }
-            Err(e) => return to_http_500(e),
-        },
-        Err(AuthError::MissingAuthHeader | AuthError::InvalidAuthHeader) => {
-            return HttpResponse::Unauthorized().json(error_message("unauthorized"))
-        }
-        Err(e) => return to_http_500(e),
-    };
-
-    match service::repo_delete(&db, requester, &query.id).await {
+#[post("/api/v1/password/reset")]
+pub async fn reset_password(db: web::Data<Database>, payload: web::Json<ResetPasswordRequest>) -> impl Responder {
+    match service::auth_reset_password(&db, &payload.token, &payload.new_password).await {
         Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
-        Err(msg) if msg == "forbidden" => HttpResponse::Forbidden().json(error_message(&msg)),
-        Err(msg) if msg == "repository not found" => HttpResponse::NotFound().json(error_message(&msg)),
+        Err(AuthError::InvalidCredentials) => HttpResponse::BadRequest().json(error_message("invalid or expired token")),
+        Err(AuthError::InvalidPassword) => HttpResponse::BadRequest().json(error_message("invalid password")),
         Err(e) => to_http_500(e),
     }
 }
 
+// ----------------- two-factor -----------------
+
 #[utoipa::path(
-    get,
-    path = "/api/v1/repos",
-    params(ReposQuery),
+    post,
+    path = "/api/v1/user/2fa/setup",
+    security(("bearerAuth" = [])),
     responses(
-        (status = 200, description = "List repositories", body = [Repository])
+        (status = 200, description = "Pending TOTP secret generated", body = TotpSetupResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 409, description = "2FA already enabled", body = ErrorResponse)
     ),
-    tag = "repos"
+    tag = "auth"
 )]
-#[get("/api/v1/repos")]
-pub async fn list_repos(db: web::Data<Database>, req: HttpRequest, query: web::Query<ReposQuery>) -> impl Responder {
-    let requester = optional_requester(&db, &req).await;
-    match service::repo_list(&db, requester, query.into_inner()).await {
-        Ok(repos) => HttpResponse::Ok().json(repos),
+#[post("/api/v1/user/2fa/setup")]
+pub async fn totp_setup(db: web::Data<Database>, req: HttpRequest) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::totp_setup(&db, requester).await {
+        Ok(resp) => HttpResponse::Ok().json(resp),
+        Err(AuthError::OtpAlreadyEnabled) => HttpResponse::Conflict().json(error_message("2fa already enabled")),
+        Err(AuthError::InvalidCredentials) => HttpResponse::Unauthorized().json(error_message("unauthorized")),
         Err(e) => to_http_500(e),
     }
 }
 
-// ----------------- git browsing -----------------
-
 #[utoipa::path(
-    get,
-    path = "/api/v1/branches",
-    params(BranchesQuery),
+    post,
+    path = "/api/v1/user/2fa/enable",
+    security(("bearerAuth" = [])),
+    request_body = TotpEnableRequest,
     responses(
-        (status = 200, description = "List branches", body = BranchesResponse),
+        (status = 200, description = "2FA enabled", body = RecoveryCodesResponse),
+        (status = 400, description = "Invalid code or no pending setup", body = ErrorResponse),
         (status = 401, description = "Unauthorized"),
-        (status = 403, description = "Forbidden"),
-        (status = 404, description = "Repository not found")
+        (status = 409, description = "2FA already enabled", body = ErrorResponse)
     ),
-    tag = "git"
+    tag = "auth"
 )]
-#[get("/api/v1/branches")]
-pub async fn branches(db: web::Data<Database>, req: HttpRequest, query: web::Query<BranchesQuery>) -> impl Responder {
-    let requester = optional_requester(&db, &req).await;
-    match service::git_branches(&db, requester.clone(), &query.id).await {
-        Ok(list) => HttpResponse::Ok().json(BranchesResponse { branches: list }),
-        Err(msg) if msg == "forbidden" => {
-            if requester.is_none() {
-                HttpResponse::Unauthorized().json(error_message("unauthorized"))
-            } else {
-                HttpResponse::Forbidden().json(error_message("forbidden"))
-            }
-        }
-        Err(msg) if msg == "repository not found" => HttpResponse::NotFound().json(error_message(&msg)),
+#[post("/api/v1/user/2fa/enable")]
+pub async fn totp_enable(db: web::Data<Database>, req: HttpRequest, payload: web::Json<TotpEnableRequest>) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::totp_enable(&db, requester, &payload.code).await {
+        Ok(resp) => HttpResponse::Ok().json(resp),
+        Err(AuthError::OtpAlreadyEnabled) => HttpResponse::Conflict().json(error_message("2fa already enabled")),
+        Err(AuthError::InvalidCredentials) => HttpResponse::BadRequest().json(error_message("invalid code")),
         Err(e) => to_http_500(e),
     }
 }
 
 #[utoipa::path(
-    delete,
-    path = "/api/v1/branch",
-    params(DeleteBranchQuery),
+    post,
+    path = "/api/v1/user/2fa/disable",
+    security(("bearerAuth" = [])),
+    request_body = TotpDisableRequest,
     responses(
-        (status = 200, description = "Branch deleted"),
+        (status = 200, description = "2FA disabled", body = OkResponse),
+        (status = 400, description = "Wrong password", body = ErrorResponse),
         (status = 401, description = "Unauthorized"),
-        (status = 403, description = "Forbidden"),
-        (status = 404, description = "Repository not found"),
-        (status = 400, description = "Bad request")
+        (status = 429, description = "Too many failed attempts; see the Retry-After header", body = ErrorResponse)
     ),
-    tag = "git"
+    tag = "auth"
 )]
-#[delete("/api/v1/branch")]
-pub async fn delete_branch(
+#[post("/api/v1/user/2fa/disable")]
+pub async fn totp_disable(
     db: web::Data<Database>,
+    backend: web::Data<dyn AuthBackend>,
     req: HttpRequest,
-    query: web::Query<DeleteBranchQuery>,
+    payload: web::Json<TotpDisableRequest>,
 ) -> impl Responder {
-    let requester = optional_requester(&db, &req).await;
-
-    match service::git_remove_branch(&db, requester.clone(), &query.id, &query.branch).await {
-        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
-            "message": format!("Branch '{}' deleted", query.branch)
-        })),
-        Err(msg) if msg == "forbidden" => {
-            if requester.is_none() {
-                HttpResponse::Unauthorized().json(error_message("unauthorized"))
-            } else {
-                HttpResponse::Forbidden().json(error_message("forbidden"))
-            }
-        }
-        Err(msg) if msg == "repository not found" => {
-            HttpResponse::NotFound().json(error_message(&msg))
-        }
-        Err(msg) if msg.contains("cannot delete branch") => {
-            HttpResponse::BadRequest().json(error_message(&msg))
-        }
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    let client = ClientInfo::from_request(&req);
+    match service::totp_disable(&db, backend.get_ref(), requester, &payload.password, client.ip.as_deref()).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(AuthError::InvalidCredentials) => HttpResponse::BadRequest().json(error_message("wrong password")),
+        Err(AuthError::TooManyAttempts(secs)) => HttpResponse::TooManyRequests()
+            .insert_header((actix_web::http::header::RETRY_AFTER, secs.to_string()))
+            .json(error_message("too many failed attempts")),
         Err(e) => to_http_500(e),
     }
 }
 
+// ----------------- account -----------------
+
 #[utoipa::path(
-    get,
-    path = "/api/v1/content",
-    params(ContentQuery),
+    post,
+    path = "/api/v1/user/password",
+    security(("bearerAuth" = [])),
+    request_body = ChangePasswordRequest,
     responses(
-        (status = 200, description = "Get tree or blob content", body = ContentResponse),
+        (status = 200, description = "Password changed; other sessions are signed out and access tokens revoked", body = OkResponse),
+        (status = 400, description = "Wrong current password or invalid new password", body = ErrorResponse),
         (status = 401, description = "Unauthorized"),
-        (status = 403, description = "Forbidden"),
-        (status = 404, description = "Repository not found")
+        (status = 403, description = "Session token required"),
+        (status = 429, description = "Too many failed attempts", body = ErrorResponse)
     ),
-    tag = "git"
+    tag = "auth"
 )]
-#[get("/api/v1/content")]
-pub async fn content(
+#[post("/api/v1/user/password")]
+pub async fn change_password(
     db: web::Data<Database>,
+    backend: web::Data<dyn AuthBackend>,
     req: HttpRequest,
-    query: web::Query<ContentQuery>,
+    payload: web::Json<ChangePasswordRequest>,
 ) -> impl Responder {
-    let requester = optional_requester(&db, &req).await;
-    match service::git_content(&db, requester.clone(), query.into_inner()).await {
-        Ok(res) => HttpResponse::Ok().json(res),
-        Err(msg) if msg == "forbidden" => {
-            if requester.is_none() {
-                HttpResponse::Unauthorized().json(error_message("unauthorized"))
-            } else {
-                HttpResponse::Forbidden().json(error_message("forbidden"))
-            }
-        }
-        Err(msg) if msg == "repository not found" => HttpResponse::NotFound().json(error_message(&msg)),
-        Err(e) => to_http_500(e),
+    let current = match require_session_token(&db, &req).await {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+    let client = ClientInfo::from_request(&req);
+    match service::account_change_password(&db, backend.get_ref(), current.user, Some(&current._id), payload.into_inner(), client.ip.as_deref()).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
     }
 }
 
 #[utoipa::path(
-    get,
-    path = "/api/v1/commits",
-    params(CommitsQuery),
+    post,
+    path = "/api/v1/user/email",
+    security(("bearerAuth" = [])),
+    request_body = ChangeEmailRequest,
     responses(
-        (status = 200, description = "List commits", body = [CommitInfo]),
-        (status = 400, description = "Invalid branch or revspec"),
+        (status = 200, description = "Email changed", body = OkResponse),
+        (status = 400, description = "Wrong password or invalid email", body = ErrorResponse),
         (status = 401, description = "Unauthorized"),
-        (status = 403, description = "Forbidden"),
-        (status = 404, description = "Repository not found")
+        (status = 403, description = "Session token required"),
+        (status = 409, description = "Email already in use", body = ErrorResponse),
+        (status = 429, description = "Too many failed attempts", body = ErrorResponse)
     ),
-    tag = "git"
+    tag = "auth"
 )]
-#[get("/api/v1/commits")]
-pub async fn commits(
+#[post("/api/v1/user/email")]
+pub async fn change_email(
     db: web::Data<Database>,
+    backend: web::Data<dyn AuthBackend>,
+    mailer: web::Data<dyn Mailer>,
     req: HttpRequest,
-    query: web::Query<CommitsQuery>,
+    payload: web::Json<ChangeEmailRequest>,
 ) -> impl Responder {
-    let requester = optional_requester(&db, &req).await;
-    match service::git_commits(&db, requester.clone(), query.into_inner()).await {
-        Ok(list) => HttpResponse::Ok().json(list),
-        Err(msg) if msg == "forbidden" => {
-            if requester.is_none() {
-                HttpResponse::Unauthorized().json(error_message("unauthorized"))
-            } else {
-                HttpResponse::Forbidden().json(error_message("forbidden"))
-            }
-        }
-        Err(msg) if msg == "invalid branch or revspec" => {
-            HttpResponse::BadRequest().json(error_message("invalid branch or revspec"))
-        }
-        Err(msg) if msg == "repository not found" => HttpResponse::NotFound().json(error_message(&msg)),
-        Err(e) => to_http_500(e),
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    let client = ClientInfo::from_request(&req);
+    match service::account_change_email(&db, backend.get_ref(), mailer.get_ref(), requester, payload.into_inner(), client.ip.as_deref()).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
     }
 }
 
-// Download as ZIP
 #[utoipa::path(
-    get,
-    path = "/api/v1/download",
-    params(ContentQuery),
+    post,
+    path = "/api/v1/user/email/verification",
+    security(("bearerAuth" = [])),
     responses(
-        (status = 200, description = "ZIP archive of the specified path", content_type = "application/zip"),
-        (status = 400, description = "Invalid branch or revspec"),
+        (status = 200, description = "Verification link sent to the current address", body = OkResponse),
+        (status = 400, description = "Email already verified", body = ErrorResponse),
         (status = 401, description = "Unauthorized"),
-        (status = 403, description = "Forbidden"),
-        (status = 404, description = "Repository not found")
+        (status = 403, description = "Session token required")
     ),
-    tag = "git"
+    tag = "auth"
 )]
-#[get("/api/v1/download")]
-pub async fn download(
-    db: web::Data<Database>,
-    req: HttpRequest,
-    query: web::Query<ContentQuery>,
-) -> impl Responder {
-    let requester = optional_requester(&db, &req).await;
-    match service::git_download(&db, requester.clone(), query.into_inner()).await {
-        Ok((filename, bytes)) => {
-            use actix_web::http::header::{ContentDisposition, DispositionType, DispositionParam};
+#[post("/api/v1/user/email/verification")]
+pub async fn resend_verification(db: web::Data<Database>, mailer: web::Data<dyn Mailer>, req: HttpRequest) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::account_resend_verification(&db, mailer.get_ref(), requester).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/email/verify",
+    request_body = VerifyEmailRequest,
+    responses(
+        (status = 200, description = "Email address verified", body = OkResponse),
+        (status = 400, description = "Invalid or expired token", body = ErrorResponse)
+    ),
+    tag = "auth"
+)]
+#[post("/api/v1/email/verify")]
+pub async fn verify_email(db: web::Data<Database>, payload: web::Json<VerifyEmailRequest>) -> impl Responder {
+    match service::auth_verify_email(&db, &payload.token).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(AuthError::InvalidCredentials) => HttpResponse::BadRequest().json(error_message("invalid or expired token")),
+        Err(e) => to_http_500(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/username",
+    security(("bearerAuth" = [])),
+    request_body = ChangeUsernameRequest,
+    responses(
+        (status = 200, description = "Username changed; the old name redirects to the new one", body = OkResponse),
+        (status = 400, description = "Invalid name", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required"),
+        (status = 409, description = "Name already taken", body = ErrorResponse)
+    ),
+    tag = "auth"
+)]
+#[post("/api/v1/user/username")]
+pub async fn change_username(db: web::Data<Database>, req: HttpRequest, payload: web::Json<ChangeUsernameRequest>) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::account_change_username(&db, requester, payload.into_inner()).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+// ----------------- access tokens -----------------
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/tokens",
+    security(("bearerAuth" = [])),
+    responses(
+        (status = 200, description = "Personal access tokens of the requester", body = [TokenInfo]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required")
+    ),
+    tag = "auth"
+)]
+#[get("/api/v1/user/tokens")]
+pub async fn list_tokens(db: web::Data<Database>, req: HttpRequest) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::tokens_list(&db, requester).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/tokens",
+    security(("bearerAuth" = [])),
+    request_body = CreateTokenRequest,
+    responses(
+        (status = 201, description = "Token created; the value is only returned once", body = CreatedTokenResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required")
+    ),
+    tag = "auth"
+)]
+#[post("/api/v1/user/tokens")]
+pub async fn create_token(db: web::Data<Database>, req: HttpRequest, payload: web::Json<CreateTokenRequest>) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::token_create(&db, requester, payload.into_inner(), ClientInfo::from_request(&req)).await {
+        Ok(created) => HttpResponse::Created().json(created),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/user/tokens",
+    security(("bearerAuth" = [])),
+    params(RevokeTokenQuery),
+    responses(
+        (status = 200, description = "Token revoked", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required"),
+        (status = 404, description = "Token not found")
+    ),
+    tag = "auth"
+)]
+#[delete("/api/v1/user/tokens")]
+pub async fn revoke_token(db: web::Data<Database>, req: HttpRequest, query: web::Query<RevokeTokenQuery>) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::token_revoke(&db, requester, &query.id).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+// ----------------- ssh keys -----------------
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/keys",
+    security(("bearerAuth" = [])),
+    responses(
+        (status = 200, description = "SSH keys of the requester", body = [SshKeyInfo]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required")
+    ),
+    tag = "auth"
+)]
+#[get("/api/v1/user/keys")]
+pub async fn list_ssh_keys(db: web::Data<Database>, req: HttpRequest) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::ssh_keys_list(&db, requester).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/keys",
+    security(("bearerAuth" = [])),
+    request_body = AddSshKeyRequest,
+    responses(
+        (status = 201, description = "SSH key added", body = SshKeyInfo),
+        (status = 400, description = "Not an OpenSSH public key", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required"),
+        (status = 409, description = "The key is already in use", body = ErrorResponse)
+    ),
+    tag = "auth"
+)]
+#[post("/api/v1/user/keys")]
+pub async fn add_ssh_key(db: web::Data<Database>, req: HttpRequest, payload: web::Json<AddSshKeyRequest>) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::ssh_key_add(&db, requester, payload.into_inner()).await {
+        Ok(key) => HttpResponse::Created().json(key),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/user/keys",
+    security(("bearerAuth" = [])),
+    params(DeleteQuery),
+    responses(
+        (status = 200, description = "SSH key removed", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required"),
+        (status = 404, description = "SSH key not found")
+    ),
+    tag = "auth"
+)]
+#[delete("/api/v1/user/keys")]
+pub async fn delete_ssh_key(db: web::Data<Database>, req: HttpRequest, query: web::Query<DeleteQuery>) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::ssh_key_delete(&db, requester, &query.id).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+// ----------------- linked identities -----------------
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/identities",
+    security(("bearerAuth" = [])),
+    responses(
+        (status = 200, description = "Single sign-on accounts linked to the requester", body = [IdentityInfo]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required")
+    ),
+    tag = "auth"
+)]
+#[get("/api/v1/user/identities")]
+pub async fn list_identities(db: web::Data<Database>, oidc: web::Data<Oidc>, req: HttpRequest) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::identities_list(&db, &oidc, requester).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/user/identities",
+    security(("bearerAuth" = [])),
+    params(DeleteQuery),
+    responses(
+        (status = 200, description = "Identity unlinked", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required, or the identity comes from the LDAP directory"),
+        (status = 404, description = "Identity not found")
+    ),
+    tag = "auth"
+)]
+#[delete("/api/v1/user/identities")]
+pub async fn unlink_identity(db: web::Data<Database>, req: HttpRequest, query: web::Query<DeleteQuery>) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::identity_unlink(&db, requester, &query.id).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+// ----------------- invites -----------------
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/invites",
+    security(("bearerAuth" = [])),
+    responses(
+        (status = 200, description = "Unexpired invites created by the requester; all of them for admins", body = [InviteInfo]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required, or not allowed to invite")
+    ),
+    tag = "auth"
+)]
+#[get("/api/v1/user/invites")]
+pub async fn list_invites(db: web::Data<Database>, req: HttpRequest) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::invites_list(&db, requester).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/invites",
+    security(("bearerAuth" = [])),
+    request_body = CreateInviteRequest,
+    responses(
+        (status = 201, description = "Invite created; the code is only returned once", body = CreatedInviteResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required, or not allowed to invite")
+    ),
+    tag = "auth"
+)]
+#[post("/api/v1/user/invites")]
+pub async fn create_invite(db: web::Data<Database>, req: HttpRequest, payload: web::Json<CreateInviteRequest>) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::invite_create(&db, requester, payload.into_inner()).await {
+        Ok(created) => HttpResponse::Created().json(created),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/user/invites",
+    security(("bearerAuth" = [])),
+    params(DeleteQuery),
+    responses(
+        (status = 200, description = "Invite revoked", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required, or not allowed to invite"),
+        (status = 404, description = "Invite not found")
+    ),
+    tag = "auth"
+)]
+#[delete("/api/v1/user/invites")]
+pub async fn revoke_invite(db: web::Data<Database>, req: HttpRequest, query: web::Query<DeleteQuery>) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::invite_revoke(&db, requester, &query.id).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+// ----------------- sessions -----------------
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/sessions",
+    security(("bearerAuth" = [])),
+    responses(
+        (status = 200, description = "Active sessions and personal tokens", body = [SessionInfo]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required")
+    ),
+    tag = "auth"
+)]
+#[get("/api/v1/user/sessions")]
+pub async fn list_sessions(db: web::Data<Database>, req: HttpRequest) -> impl Responder {
+    let current = match require_session_token(&db, &req).await {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+    match service::sessions_list(&db, current.user, &current._id).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/user/sessions",
+    security(("bearerAuth" = [])),
+    params(RevokeSessionQuery),
+    responses(
+        (status = 200, description = "Session or token revoked", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required"),
+        (status = 404, description = "Session not found")
+    ),
+    tag = "auth"
+)]
+#[delete("/api/v1/user/sessions")]
+pub async fn revoke_session(db: web::Data<Database>, req: HttpRequest, query: web::Query<RevokeSessionQuery>) -> impl Responder {
+    let requester = match require_session(&db, &req).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::session_revoke(&db, requester, &query.id).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/sessions/revoke-all",
+    security(("bearerAuth" = [])),
+    responses(
+        (status = 200, description = "All other sessions signed out; personal tokens are kept", body = RevokedResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Session token required")
+    ),
+    tag = "auth"
+)]
+#[post("/api/v1/user/sessions/revoke-all")]
+pub async fn revoke_all_sessions(db: web::Data<Database>, req: HttpRequest) -> impl Responder {
+    let current = match require_session_token(&db, &req).await {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+    match service::sessions_revoke_others(&db, current.user, &current._id).await {
+        Ok(revoked) => HttpResponse::Ok().json(RevokedResponse { revoked }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+// ----------------- repos -----------------
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/create",
+    security(("bearerAuth" = [])),
+    request_body = CreateRepoRequest,
+    responses(
+        (status = 201, description = "Repository created", body = Repository),
+        (status = 400, description = "Empty or invalid name", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an owner of the target organization, or email not verified"),
+        (status = 404, description = "Owner not found"),
+        (status = 409, description = "Already exists")
+    ),
+    tag = "repos"
+)]
+#[post("/api/v1/create")]
+pub async fn create_repo(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    payload: web::Json<CreateRepoRequest>,
+) -> impl Responder {
+    let user_id = match require_requester(&db, &req, Scope::RepoWrite).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+
+    match service::repo_create(&db, user_id, payload.into_inner()).await {
+        Ok(repo) => HttpResponse::Created().json(repo),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/delete",
+    security(("bearerAuth" = [])),
+    params(DeleteQuery),
+    responses(
+        (status = 200, description = "Repository deleted", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository not found")
+    ),
+    tag = "repos"
+)]
+#[delete("/api/v1/delete")]
+pub async fn delete_repo(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<DeleteQuery>,
+) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+
+    match service::repo_delete(&db, requester, &query.id).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) if msg == "forbidden" => HttpResponse::Forbidden().json(error_message(&msg)),
+        Err(msg) if msg == "repository not found" => HttpResponse::NotFound().json(error_message(&msg)),
+        Err(e) => to_http_500(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/fork",
+    security(("bearerAuth" = [])),
+    request_body = ForkRepoRequest,
+    responses(
+        (status = 201, description = "Fork created", body = Repository),
+        (status = 400, description = "Invalid name", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository or owner not found"),
+        (status = 409, description = "Already exists", body = ErrorResponse)
+    ),
+    tag = "repos"
+)]
+#[post("/api/v1/fork")]
+pub async fn fork_repo(db: web::Data<Database>, req: HttpRequest, payload: web::Json<ForkRepoRequest>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::RepoWrite).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::repo_fork(&db, requester, payload.into_inner()).await {
+        Ok(repo) => HttpResponse::Created().json(repo),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/forks",
+    params(ForksQuery),
+    responses(
+        (status = 200, description = "Forks of the repository visible to the requester", body = [Repository]),
+        (status = 404, description = "Repository not found")
+    ),
+    tag = "repos"
+)]
+#[get("/api/v1/forks")]
+pub async fn list_forks(db: web::Data<Database>, req: HttpRequest, query: web::Query<ForksQuery>) -> impl Responder {
+    let requester = optional_requester(&db, &req, Scope::RepoRead).await;
+    match service::repo_forks(&db, requester, &query.id).await {
+        Ok(forks) => HttpResponse::Ok().json(forks),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/update",
+    security(("bearerAuth" = [])),
+    request_body = UpdateRepoRequest,
+    responses(
+        (status = 200, description = "Repository settings saved", body = Repository),
+        (status = 400, description = "Invalid website or topic, or a private fork made public", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository or branch not found")
+    ),
+    tag = "repos"
+)]
+#[post("/api/v1/update")]
+pub async fn update_repo(db: web::Data<Database>, req: HttpRequest, payload: web::Json<UpdateRepoRequest>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::repo_update(&db, requester, payload.into_inner()).await {
+        Ok(repo) => HttpResponse::Ok().json(repo),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/rename",
+    security(("bearerAuth" = [])),
+    request_body = RenameRepoRequest,
+    responses(
+        (status = 200, description = "Repository renamed; the old name redirects to it", body = Repository),
+        (status = 400, description = "Invalid name", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository not found"),
+        (status = 409, description = "Already exists", body = ErrorResponse)
+    ),
+    tag = "repos"
+)]
+#[post("/api/v1/rename")]
+pub async fn rename_repo(db: web::Data<Database>, req: HttpRequest, payload: web::Json<RenameRepoRequest>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::repo_rename(&db, requester, payload.into_inner()).await {
+        Ok(repo) => HttpResponse::Ok().json(repo),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/transfer",
+    security(("bearerAuth" = [])),
+    request_body = TransferRepoRequest,
+    responses(
+        (status = 200, description = "Repository moved to another user or to an organization the requester owns", body = Repository),
+        (status = 400, description = "Target is an organization the requester does not own, or a suspended user", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository or owner not found"),
+        (status = 409, description = "The new owner already has a repository with this name", body = ErrorResponse)
+    ),
+    tag = "repos"
+)]
+#[post("/api/v1/transfer")]
+pub async fn transfer_repo(db: web::Data<Database>, req: HttpRequest, payload: web::Json<TransferRepoRequest>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::repo_transfer(&db, requester, payload.into_inner()).await {
+        Ok(repo) => HttpResponse::Ok().json(repo),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/repos",
+    params(ReposQuery),
+    responses(
+        (status = 200, description = "List repositories", body = [Repository])
+    ),
+    tag = "repos"
+)]
+#[get("/api/v1/repos")]
+pub async fn list_repos(db: web::Data<Database>, req: HttpRequest, query: web::Query<ReposQuery>) -> impl Responder {
+    let requester = optional_requester(&db, &req, Scope::RepoRead).await;
+    match service::repo_list(&db, requester, query.into_inner()).await {
+        Ok(repos) => HttpResponse::Ok().json(repos),
+        Err(e) => to_http_500(e),
+    }
+}
+
+// ----------------- git browsing -----------------
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/branches",
+    params(BranchesQuery),
+    responses(
+        (status = 200, description = "List branches", body = BranchesResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository not found")
+    ),
+    tag = "git"
+)]
+#[get("/api/v1/branches")]
+pub async fn branches(db: web::Data<Database>, req: HttpRequest, query: web::Query<BranchesQuery>) -> impl Responder {
+    let requester = optional_requester(&db, &req, Scope::RepoRead).await;
+    match service::git_branches(&db, requester.clone(), &query.id).await {
+        Ok(list) => HttpResponse::Ok().json(BranchesResponse { branches: list }),
+        Err(msg) if msg == "forbidden" => {
+            if requester.is_none() {
+                HttpResponse::Unauthorized().json(error_message("unauthorized"))
+            } else {
+                HttpResponse::Forbidden().json(error_message("forbidden"))
+            }
+        }
+        Err(msg) if msg == "repository not found" => HttpResponse::NotFound().json(error_message(&msg)),
+        Err(e) => to_http_500(e),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/branch",
+    params(DeleteBranchQuery),
+    responses(
+        (status = 200, description = "Branch deleted"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository not found"),
+        (status = 400, description = "Bad request")
+    ),
+    tag = "git"
+)]
+#[delete("/api/v1/branch")]
+pub async fn delete_branch(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<DeleteBranchQuery>,
+) -> impl Responder {
+    let requester = optional_requester(&db, &req, Scope::RepoWrite).await;
+
+    match service::git_remove_branch(&db, requester.clone(), &query.id, &query.branch).await {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
+            "message": format!("Branch '{}' deleted", query.branch)
+        })),
+        Err(msg) if msg == "forbidden" => {
+            if requester.is_none() {
+                HttpResponse::Unauthorized().json(error_message("unauthorized"))
+            } else {
+                HttpResponse::Forbidden().json(error_message("forbidden"))
+            }
+        }
+        Err(msg) if msg == "repository not found" => {
+            HttpResponse::NotFound().json(error_message(&msg))
+        }
+        Err(msg) if msg.contains("cannot delete branch") => {
+            HttpResponse::BadRequest().json(error_message(&msg))
+        }
+        Err(e) => to_http_500(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/content",
+    params(ContentQuery),
+    responses(
+        (status = 200, description = "Get tree or blob content", body = ContentResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository not found")
+    ),
+    tag = "git"
+)]
+#[get("/api/v1/content")]
+pub async fn content(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<ContentQuery>,
+) -> impl Responder {
+    let requester = optional_requester(&db, &req, Scope::RepoRead).await;
+    match service::git_content(&db, requester.clone(), query.into_inner()).await {
+        Ok(res) => HttpResponse::Ok().json(res),
+        Err(msg) if msg == "forbidden" => {
+            if requester.is_none() {
+                HttpResponse::Unauthorized().json(error_message("unauthorized"))
+            } else {
+                HttpResponse::Forbidden().json(error_message("forbidden"))
+            }
+        }
+        Err(msg) if msg == "repository not found" => HttpResponse::NotFound().json(error_message(&msg)),
+        Err(e) => to_http_500(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/commits",
+    params(CommitsQuery),
+    responses(
+        (status = 200, description = "List commits", body = [CommitInfo]),
+        (status = 400, description = "Invalid branch or revspec"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository not found")
+    ),
+    tag = "git"
+)]
+#[get("/api/v1/commits")]
+pub async fn commits(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<CommitsQuery>,
+) -> impl Responder {
+    let requester = optional_requester(&db, &req, Scope::RepoRead).await;
+    match service::git_commits(&db, requester.clone(), query.into_inner()).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(msg) if msg == "forbidden" => {
+            if requester.is_none() {
+                HttpResponse::Unauthorized().json(error_message("unauthorized"))
+            } else {
+                HttpResponse::Forbidden().json(error_message("forbidden"))
+            }
+        }
+        Err(msg) if msg == "invalid branch or revspec" => {
+            HttpResponse::BadRequest().json(error_message("invalid branch or revspec"))
+        }
+        Err(msg) if msg == "repository not found" => HttpResponse::NotFound().json(error_message(&msg)),
+        Err(e) => to_http_500(e),
+    }
+}
+
+// Download as ZIP
+#[utoipa::path(
+    get,
+    path = "/api/v1/download",
+    params(ContentQuery),
+    responses(
+        (status = 200, description = "ZIP archive of the specified path", content_type = "application/zip"),
+        (status = 400, description = "Invalid branch or revspec"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository not found")
+    ),
+    tag = "git"
+)]
+#[get("/api/v1/download")]
+pub async fn download(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<ContentQuery>,
+) -> impl Responder {
+    let requester = optional_requester(&db, &req, Scope::RepoRead).await;
+    match service::git_download(&db, requester.clone(), query.into_inner()).await {
+        Ok((filename, bytes)) => {
+            use actix_web::http::header::{ContentDisposition, DispositionType, DispositionParam};
             let cd = ContentDisposition {
                 disposition: DispositionType::Attachment,
                 parameters: vec![DispositionParam::Filename(filename)],
@@ -380,8 +1232,756 @@ pub async fn download(
         Err(msg) if msg == "invalid branch or revspec" => {
             HttpResponse::BadRequest().json(error_message("invalid branch or revspec"))
         }
-        Err(msg) if msg == "repository not found" => HttpResponse::NotFound().json(error_message(&msg)),
-        Err(e) => to_http_500(e),
+        Err(msg) if msg == "repository not found" => HttpResponse::NotFound().json(error_message(&msg)),
+        Err(e) => to_http_500(e),
+    }
+}
+
+// ----------------- collaborators -----------------
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/collaborators",
+    security(("bearerAuth" = [])),
+    params(CollaboratorsQuery),
+    responses(
+        (status = 200, description = "List collaborators", body = [CollaboratorInfo]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository not found")
+    ),
+    tag = "collaborators"
+)]
+#[get("/api/v1/collaborators")]
+pub async fn list_collaborators(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<CollaboratorsQuery>,
+) -> impl Responder {
+    let requester = optional_requester(&db, &req, Scope::RepoRead).await;
+    match service::collaborators_list(&db, requester.clone(), &query.id).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(msg) if msg == "forbidden" => {
+            if requester.is_none() {
+                HttpResponse::Unauthorized().json(error_message("unauthorized"))
+            } else {
+                HttpResponse::Forbidden().json(error_message("forbidden"))
+            }
+        }
+        Err(msg) if msg == "repository not found" => HttpResponse::NotFound().json(error_message(&msg)),
+        Err(e) => to_http_500(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/collaborators",
+    security(("bearerAuth" = [])),
+    request_body = AddCollaboratorRequest,
+    responses(
+        (status = 200, description = "Collaborator added or role updated", body = OkResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository or user not found")
+    ),
+    tag = "collaborators"
+)]
+#[post("/api/v1/collaborators")]
+pub async fn add_collaborator(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    payload: web::Json<AddCollaboratorRequest>,
+) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+
+    match service::collaborator_add(&db, requester, payload.into_inner()).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) if msg == "forbidden" => HttpResponse::Forbidden().json(error_message(&msg)),
+        Err(msg) if msg == "repository not found" || msg == "user not found" => {
+            HttpResponse::NotFound().json(error_message(&msg))
+        }
+        Err(msg) if msg == "owner cannot be a collaborator" => HttpResponse::BadRequest().json(error_message(&msg)),
+        Err(e) => to_http_500(e),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/collaborators",
+    security(("bearerAuth" = [])),
+    params(RemoveCollaboratorQuery),
+    responses(
+        (status = 200, description = "Collaborator removed", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository or collaborator not found")
+    ),
+    tag = "collaborators"
+)]
+#[delete("/api/v1/collaborators")]
+pub async fn remove_collaborator(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<RemoveCollaboratorQuery>,
+) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+
+    match service::collaborator_remove(&db, requester, &query.id, &query.username).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) if msg == "forbidden" => HttpResponse::Forbidden().json(error_message(&msg)),
+        Err(msg) if msg == "repository not found" || msg == "user not found" => {
+            HttpResponse::NotFound().json(error_message(&msg))
+        }
+        Err(e) => to_http_500(e),
+    }
+}
+
+// ----------------- deploy keys -----------------
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/deploy-keys",
+    security(("bearerAuth" = [])),
+    params(DeployQuery),
+    responses(
+        (status = 200, description = "Deploy keys of the repository", body = [DeployKeyInfo]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository not found")
+    ),
+    tag = "deploy"
+)]
+#[get("/api/v1/deploy-keys")]
+pub async fn list_deploy_keys(db: web::Data<Database>, req: HttpRequest, query: web::Query<DeployQuery>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::deploy_keys_list(&db, requester, &query.id).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/deploy-keys",
+    security(("bearerAuth" = [])),
+    request_body = AddDeployKeyRequest,
+    responses(
+        (status = 201, description = "Deploy key added", body = DeployKeyInfo),
+        (status = 400, description = "Not an OpenSSH public key", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository not found"),
+        (status = 409, description = "The key is already in use", body = ErrorResponse)
+    ),
+    tag = "deploy"
+)]
+#[post("/api/v1/deploy-keys")]
+pub async fn add_deploy_key(db: web::Data<Database>, req: HttpRequest, payload: web::Json<AddDeployKeyRequest>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::deploy_key_add(&db, requester, payload.into_inner()).await {
+        Ok(key) => HttpResponse::Created().json(key),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/deploy-keys",
+    security(("bearerAuth" = [])),
+    params(DeleteQuery),
+    responses(
+        (status = 200, description = "Deploy key removed", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Deploy key not found")
+    ),
+    tag = "deploy"
+)]
+#[delete("/api/v1/deploy-keys")]
+pub async fn delete_deploy_key(db: web::Data<Database>, req: HttpRequest, query: web::Query<DeleteQuery>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::deploy_key_delete(&db, requester, &query.id).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/deploy-tokens",
+    security(("bearerAuth" = [])),
+    params(DeployQuery),
+    responses(
+        (status = 200, description = "Deploy tokens of the repository", body = [DeployTokenInfo]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository not found")
+    ),
+    tag = "deploy"
+)]
+#[get("/api/v1/deploy-tokens")]
+pub async fn list_deploy_tokens(db: web::Data<Database>, req: HttpRequest, query: web::Query<DeployQuery>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::deploy_tokens_list(&db, requester, &query.id).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/deploy-tokens",
+    security(("bearerAuth" = [])),
+    request_body = CreateDeployTokenRequest,
+    responses(
+        (status = 201, description = "Deploy token created", body = CreatedDeployTokenResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Repository not found")
+    ),
+    tag = "deploy"
+)]
+#[post("/api/v1/deploy-tokens")]
+pub async fn create_deploy_token(db: web::Data<Database>, req: HttpRequest, payload: web::Json<CreateDeployTokenRequest>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::deploy_token_create(&db, requester, payload.into_inner()).await {
+        Ok(created) => HttpResponse::Created().json(created),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/deploy-tokens",
+    security(("bearerAuth" = [])),
+    params(DeleteQuery),
+    responses(
+        (status = 200, description = "Deploy token revoked", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Deploy token not found")
+    ),
+    tag = "deploy"
+)]
+#[delete("/api/v1/deploy-tokens")]
+pub async fn revoke_deploy_token(db: web::Data<Database>, req: HttpRequest, query: web::Query<DeleteQuery>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::deploy_token_revoke(&db, requester, &query.id).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+// ----------------- admin -----------------
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/stats",
+    security(("bearerAuth" = [])),
+    responses(
+        (status = 200, description = "Instance statistics", body = InstanceStats),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an administrator")
+    ),
+    tag = "admin"
+)]
+#[get("/api/v1/admin/stats")]
+pub async fn admin_stats(db: web::Data<Database>, req: HttpRequest) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::admin_stats(&db, requester).await {
+        Ok(stats) => HttpResponse::Ok().json(stats),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/users",
+    security(("bearerAuth" = [])),
+    params(AdminSearchQuery),
+    responses(
+        (status = 200, description = "Accounts matching the search", body = [AdminUserInfo]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an administrator")
+    ),
+    tag = "admin"
+)]
+#[get("/api/v1/admin/users")]
+pub async fn admin_users(db: web::Data<Database>, req: HttpRequest, query: web::Query<AdminSearchQuery>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::admin_users(&db, requester, query.into_inner()).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/users/suspend",
+    security(("bearerAuth" = [])),
+    request_body = SuspendUserRequest,
+    responses(
+        (status = 200, description = "Suspension set; suspending signs the user out everywhere", body = OkResponse),
+        (status = 400, description = "Administrators cannot suspend themselves", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an administrator"),
+        (status = 404, description = "User not found")
+    ),
+    tag = "admin"
+)]
+#[post("/api/v1/admin/users/suspend")]
+pub async fn admin_suspend_user(db: web::Data<Database>, req: HttpRequest, payload: web::Json<SuspendUserRequest>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::admin_set_suspended(&db, requester, payload.into_inner()).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/users",
+    security(("bearerAuth" = [])),
+    params(AdminUserQuery),
+    responses(
+        (status = 200, description = "User and their repositories deleted", body = OkResponse),
+        (status = 400, description = "Administrators cannot delete themselves, nor the last owner of an organization", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an administrator"),
+        (status = 404, description = "User not found")
+    ),
+    tag = "admin"
+)]
+#[delete("/api/v1/admin/users")]
+pub async fn admin_delete_user(db: web::Data<Database>, req: HttpRequest, query: web::Query<AdminUserQuery>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::admin_delete_user(&db, requester, &query.username).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/repos",
+    security(("bearerAuth" = [])),
+    params(AdminSearchQuery),
+    responses(
+        (status = 200, description = "Repositories matching the search, including private ones", body = [AdminRepoInfo]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an administrator")
+    ),
+    tag = "admin"
+)]
+#[get("/api/v1/admin/repos")]
+pub async fn admin_repos(db: web::Data<Database>, req: HttpRequest, query: web::Query<AdminSearchQuery>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::admin_repos(&db, requester, query.into_inner()).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/repos",
+    security(("bearerAuth" = [])),
+    params(DeleteQuery),
+    responses(
+        (status = 200, description = "Repository deleted", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an administrator"),
+        (status = 404, description = "Repository not found")
+    ),
+    tag = "admin"
+)]
+#[delete("/api/v1/admin/repos")]
+pub async fn admin_delete_repo(db: web::Data<Database>, req: HttpRequest, query: web::Query<DeleteQuery>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::admin_delete_repo(&db, requester, &query.id).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/repos/transfer",
+    security(("bearerAuth" = [])),
+    request_body = TransferRepoRequest,
+    responses(
+        (status = 200, description = "Repository moved to the new owner", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an administrator"),
+        (status = 404, description = "Repository or owner not found"),
+        (status = 409, description = "The new owner already has a repository with this name", body = ErrorResponse)
+    ),
+    tag = "admin"
+)]
+#[post("/api/v1/admin/repos/transfer")]
+pub async fn admin_transfer_repo(db: web::Data<Database>, req: HttpRequest, payload: web::Json<TransferRepoRequest>) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::admin_transfer_repo(&db, requester, payload.into_inner()).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+// ----------------- organizations -----------------
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/orgs",
+    security(("bearerAuth" = [])),
+    request_body = CreateOrgRequest,
+    responses(
+        (status = 201, description = "Organization created", body = crate::db::Organization),
+        (status = 400, description = "Invalid name"),
+        (status = 401, description = "Unauthorized"),
+        (status = 409, description = "Name already taken")
+    ),
+    tag = "orgs"
+)]
+#[post("/api/v1/orgs")]
+pub async fn create_org(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    payload: web::Json<CreateOrgRequest>,
+) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::org_create(&db, requester, payload.into_inner()).await {
+        Ok(org) => HttpResponse::Created().json(org),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/orgs/members",
+    params(OrgQuery),
+    responses(
+        (status = 200, description = "List organization members", body = [OrgMemberInfo]),
+        (status = 404, description = "Organization not found")
+    ),
+    tag = "orgs"
+)]
+#[get("/api/v1/orgs/members")]
+pub async fn org_members(db: web::Data<Database>, query: web::Query<OrgQuery>) -> impl Responder {
+    match service::org_members(&db, &query.org).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/orgs/members",
+    security(("bearerAuth" = [])),
+    request_body = SetOrgMemberRequest,
+    responses(
+        (status = 200, description = "Member added or role updated", body = OkResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Organization or user not found")
+    ),
+    tag = "orgs"
+)]
+#[post("/api/v1/orgs/members")]
+pub async fn set_org_member(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    payload: web::Json<SetOrgMemberRequest>,
+) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::org_member_set(&db, requester, payload.into_inner()).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/orgs/members",
+    security(("bearerAuth" = [])),
+    params(RemoveOrgMemberQuery),
+    responses(
+        (status = 200, description = "Member removed", body = OkResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Organization or member not found")
+    ),
+    tag = "orgs"
+)]
+#[delete("/api/v1/orgs/members")]
+pub async fn remove_org_member(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<RemoveOrgMemberQuery>,
+) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::org_member_remove(&db, requester, &query.org, &query.username).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/orgs/teams",
+    security(("bearerAuth" = [])),
+    params(OrgQuery),
+    responses(
+        (status = 200, description = "List teams of the organization", body = [TeamInfo]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Organization not found")
+    ),
+    tag = "orgs"
+)]
+#[get("/api/v1/orgs/teams")]
+pub async fn org_teams(db: web::Data<Database>, req: HttpRequest, query: web::Query<OrgQuery>) -> impl Responder {
+    let requester = optional_requester(&db, &req, Scope::RepoRead).await;
+    match service::org_teams(&db, requester.clone(), &query.org).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(msg) if msg == "forbidden" && requester.is_none() => {
+            HttpResponse::Unauthorized().json(error_message("unauthorized"))
+        }
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/orgs/teams",
+    security(("bearerAuth" = [])),
+    request_body = CreateTeamRequest,
+    responses(
+        (status = 200, description = "Team created", body = OkResponse),
+        (status = 400, description = "Invalid name"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Organization not found"),
+        (status = 409, description = "Team already exists")
+    ),
+    tag = "orgs"
+)]
+#[post("/api/v1/orgs/teams")]
+pub async fn create_team(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    payload: web::Json<CreateTeamRequest>,
+) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::team_create(&db, requester, payload.into_inner()).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/orgs/teams",
+    security(("bearerAuth" = [])),
+    params(TeamQuery),
+    responses(
+        (status = 200, description = "Team deleted", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Organization or team not found")
+    ),
+    tag = "orgs"
+)]
+#[delete("/api/v1/orgs/teams")]
+pub async fn delete_team(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<TeamQuery>,
+) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::team_delete(&db, requester, &query.org, &query.team).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/orgs/teams/members",
+    security(("bearerAuth" = [])),
+    request_body = TeamMemberQuery,
+    responses(
+        (status = 200, description = "Member added to team", body = OkResponse),
+        (status = 400, description = "User is not an organization member"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Organization, team or user not found")
+    ),
+    tag = "orgs"
+)]
+#[post("/api/v1/orgs/teams/members")]
+pub async fn add_team_member(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    payload: web::Json<TeamMemberQuery>,
+) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::team_member_add(&db, requester, payload.into_inner()).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/orgs/teams/members",
+    security(("bearerAuth" = [])),
+    params(TeamMemberQuery),
+    responses(
+        (status = 200, description = "Member removed from team", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Organization, team or member not found")
+    ),
+    tag = "orgs"
+)]
+#[delete("/api/v1/orgs/teams/members")]
+pub async fn remove_team_member(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<TeamMemberQuery>,
+) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::team_member_remove(&db, requester, query.into_inner()).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/orgs/teams/repos",
+    security(("bearerAuth" = [])),
+    request_body = TeamRepoQuery,
+    responses(
+        (status = 200, description = "Repository granted to team", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Organization, team or repository not found")
+    ),
+    tag = "orgs"
+)]
+#[post("/api/v1/orgs/teams/repos")]
+pub async fn add_team_repo(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    payload: web::Json<TeamRepoQuery>,
+) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::team_repo_add(&db, requester, payload.into_inner()).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/orgs/teams/repos",
+    security(("bearerAuth" = [])),
+    params(TeamRepoQuery),
+    responses(
+        (status = 200, description = "Repository revoked from team", body = OkResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Organization, team or repository not found")
+    ),
+    tag = "orgs"
+)]
+#[delete("/api/v1/orgs/teams/repos")]
+pub async fn remove_team_repo(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<TeamRepoQuery>,
+) -> impl Responder {
+    let requester = match require_requester(&db, &req, Scope::Admin).await {
+        Ok(uid) => uid,
+        Err(resp) => return resp,
+    };
+    match service::team_repo_remove(&db, requester, query.into_inner()).await {
+        Ok(()) => HttpResponse::Ok().json(OkResponse { ok: true }),
+        Err(msg) => service_error(msg),
     }
 }
 
@@ -389,13 +1989,87 @@ pub async fn download(
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(login)
+        .service(captcha_info)
+        .service(register)
         .service(logout)
+        .service(forgot_password)
+        .service(reset_password)
+        .service(totp_setup)
+        .service(totp_enable)
+        .service(totp_disable)
+        .service(change_password)
+        .service(change_email)
+        .service(resend_verification)
+        .service(verify_email)
+        .service(change_username)
+        .service(list_tokens)
+        .service(create_token)
+        .service(revoke_token)
+        .service(list_ssh_keys)
+        .service(add_ssh_key)
+        .service(delete_ssh_key)
+        .service(list_identities)
+        .service(unlink_identity)
+        .service(list_invites)
+        .service(create_invite)
+        .service(revoke_invite)
+        .service(list_sessions)
+        .service(revoke_session)
+        .service(revoke_all_sessions)
         .service(create_repo)
         .service(delete_repo)
+        .service(update_repo)
+        .service(fork_repo)
+        .service(list_forks)
+        .service(rename_repo)
+        .service(transfer_repo)
         .service(list_repos)
         .service(branches)
         .service(delete_branch)
         .service(content)
         .service(commits)
-        .service(download);
-}
\ No newline at end of file
+        .service(download)
+        .service(list_collaborators)
+        .service(add_collaborator)
+        .service(remove_collaborator)
+        .service(list_deploy_keys)
+        .service(add_deploy_key)
+        .service(delete_deploy_key)
+        .service(list_deploy_tokens)
+        .service(create_deploy_token)
+        .service(revoke_deploy_token)
+        .service(admin_stats)
+        .service(admin_users)
+        .service(admin_suspend_user)
+        .service(admin_delete_user)
+        .service(admin_repos)
+        .service(admin_delete_repo)
+        .service(admin_transfer_repo)
+        .service(create_org)
+        .service(org_members)
+        .service(set_org_member)
+        .service(remove_org_member)
+        .service(org_teams)
+        .service(create_team)
+        .service(delete_team)
+        .service(add_team_member)
+        .service(remove_team_member)
+        .service(add_team_repo)
+        .service(remove_team_repo);
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+
+    #[test]
+    fn service_errors_map_to_statuses() {
+        assert_eq!(service_error("invalid name".into()).status(), StatusCode::BAD_REQUEST);
+        assert_eq!(service_error("name must not be empty".into()).status(), StatusCode::BAD_REQUEST);
+        assert_eq!(service_error("already exists".into()).status(), StatusCode::CONFLICT);
+        assert_eq!(service_error("owner not found".into()).status(), StatusCode::NOT_FOUND);
+        assert_eq!(service_error("forbidden".into()).status(), StatusCode::FORBIDDEN);
+        assert_eq!(service_error("too many failed attempts".into()).status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(service_error("disk full".into()).status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -5,21 +5,446 @@ use tokio::fs;
 use std::io::Write;
 use mongodb::bson::DateTime;
 use bson::oid::ObjectId;
-use crate::db::Repository;
+use crate::db::{OrgRole, Owner, Repository, Token};
+use crate::policy::{self, Action, Actor};
+use crate::auth::ClientInfo;
+use crate::auth_backend::AuthBackend;
+use crate::mail::Mailer;
+use crate::oidc::Oidc;
 
 // AUTH
-pub async fn auth_register(db: &Database, username: String, email: String, password: String) -> Result<(), AuthError> {
-    crate::auth::register(db, username, email, password).await
+pub async fn auth_register(
+    db: &Database,
+    mailer: &dyn Mailer,
+    username: String,
+    email: String,
+    password: String,
+    invite: Option<String>,
+) -> Result<(), AuthError> {
+    crate::auth::register(db, mailer, username, email, password, invite.as_deref()).await
+}
+
+pub async fn auth_verify_email(db: &Database, token: &str) -> Result<(), AuthError> {
+    crate::auth::verify_email(db, token).await
+}
+
+pub async fn auth_login(
+    db: &Database,
+    backend: &dyn AuthBackend,
+    login: String,
+    password: String,
+    otp: Option<String>,
+    client: ClientInfo,
+) -> Result<String, AuthError> {
+    crate::auth::login(db, backend, login, password, otp, client).await
+}
+
+pub async fn auth_begin_login(
+    db: &Database,
+    backend: &dyn AuthBackend,
+    login: String,
+    password: String,
+    remember: bool,
+    client: ClientInfo,
+) -> Result<crate::auth::LoginStep, AuthError> {
+    crate::auth::begin_login(db, backend, login, password, remember, client).await
+}
+
+pub async fn auth_finish_login(db: &Database, challenge: &str, code: &str, client: ClientInfo) -> Result<(String, bool), AuthError> {
+    crate::auth::finish_login(db, challenge, code, client).await
+}
+
+pub async fn auth_begin_sso(db: &Database, oidc: &Oidc, provider: &str, link_user: Option<ObjectId>) -> Result<(String, String), AuthError> {
+    crate::auth::begin_sso(db, oidc, provider, link_user).await
+}
+
+pub async fn auth_finish_sso(
+    db: &Database,
+    mailer: &dyn Mailer,
+    oidc: &Oidc,
+    provider: &str,
+    state: &str,
+    code: &str,
+    client: ClientInfo,
+) -> Result<crate::auth::SsoOutcome, AuthError> {
+    crate::auth::finish_sso(db, mailer, oidc, provider, state, code, client).await
 }
 
-pub async fn auth_login(db: &Database, login: String, password: String) -> Result<String, AuthError> {
-    crate::auth::login(db, login, password).await
+pub async fn session_renew(db: &Database, token: &str) -> Result<Option<Token>, AuthError> {
+    crate::auth::renew_session(db, token).await
+}
+
+pub async fn auth_request_password_reset(db: &Database, mailer: &dyn crate::mail::Mailer, login: &str) -> Result<(), AuthError> {
+    crate::auth::request_password_reset(db, mailer, login).await
+}
+
+pub async fn auth_reset_password(db: &Database, token: &str, new_password: &str) -> Result<(), AuthError> {
+    crate::auth::reset_password(db, token, new_password).await
+}
+
+// TWO-FACTOR
+pub async fn totp_setup(db: &Database, user_id: ObjectId) -> Result<TotpSetupResponse, AuthError> {
+    let (secret, otpauth_uri) = crate::auth::totp_setup(db, user_id).await?;
+    Ok(TotpSetupResponse { secret, otpauth_uri })
+}
+
+pub async fn totp_enable(db: &Database, user_id: ObjectId, code: &str) -> Result<RecoveryCodesResponse, AuthError> {
+    let recovery_codes = crate::auth::totp_enable(db, user_id, code).await?;
+    Ok(RecoveryCodesResponse { recovery_codes })
+}
+
+pub async fn totp_disable(db: &Database, backend: &dyn AuthBackend, user_id: ObjectId, password: &str, ip: Option<&str>) -> Result<(), AuthError> {
+    crate::auth::totp_disable(db, backend, user_id, password, ip).await
 }
 
 pub async fn auth_logout(db: &Database, token: String) -> Result<(), AuthError> {
     crate::auth::logout(db, token).await
 }
 
+// ACCESS TOKENS
+pub async fn token_info(db: &Database, token: String) -> Result<Token, AuthError> {
+    crate::auth::authenticate_token(db, &token).await
+}
+
+/// Like `token_info`, but only for browser session tokens.
+pub async fn session_info(db: &Database, token: String) -> Result<Token, AuthError> {
+    crate::auth::authenticate_session(db, &token).await
+}
+
+fn token_to_info(t: Token) -> TokenInfo {
+    TokenInfo {
+        id: t._id.to_hex(),
+        name: t.name.unwrap_or_default(),
+        scopes: t.scopes,
+        created_at: t.created_at,
+        expires_at: t.expires_at,
+        last_used_at: t.last_used_at,
+    }
+}
+
+pub async fn tokens_list(db: &Database, requester: ObjectId) -> Result<Vec<TokenInfo>, String> {
+    let tokens = db.list_personal_tokens(&requester).await.map_err(|e| e.to_string())?;
+    Ok(tokens.into_iter().map(token_to_info).collect())
+}
+
+pub async fn token_create(
+    db: &Database,
+    requester: ObjectId,
+    payload: CreateTokenRequest,
+    client: ClientInfo,
+) -> Result<CreatedTokenResponse, String> {
+    let name = payload.name.trim().to_string();
+    if name.is_empty() {
+        return Err("name must not be empty".into());
+    }
+    if payload.scopes.is_empty() {
+        return Err("at least one scope is required".into());
+    }
+    if payload.expires_in_days == Some(0) {
+        return Err("invalid expiry".into());
+    }
+
+    let mut scopes = payload.scopes;
+    scopes.sort();
+    scopes.dedup();
+    let (token, value) = crate::auth::create_personal_token(db, requester, name, scopes, payload.expires_in_days, client)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(CreatedTokenResponse { token: value, info: token_to_info(token) })
+}
+
+pub async fn token_revoke(db: &Database, requester: ObjectId, id: &str) -> Result<(), String> {
+    let oid = ObjectId::parse_str(id).map_err(|_| "token not found".to_string())?;
+    match db.delete_personal_token(&requester, &oid).await {
+        Ok(0) => Err("token not found".into()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// SESSIONS
+/// All active sessions and personal tokens of the requester; `current` marks the caller's own.
+pub async fn sessions_list(db: &Database, requester: ObjectId, current: &ObjectId) -> Result<Vec<SessionInfo>, String> {
+    let tokens = db.list_user_tokens(&requester).await.map_err(|e| e.to_string())?;
+    Ok(tokens
+        .into_iter()
+        .map(|t| SessionInfo {
+            id: t._id.to_hex(),
+            current: t._id == *current,
+            kind: t.kind,
+            name: t.name,
+            ip: t.ip,
+            user_agent: t.user_agent,
+            created_at: t.created_at,
+            expires_at: t.expires_at,
+            last_used_at: t.last_used_at,
+        })
+        .collect())
+}
+
+pub async fn session_revoke(db: &Database, requester: ObjectId, id: &str) -> Result<(), String> {
+    let oid = ObjectId::parse_str(id).map_err(|_| "session not found".to_string())?;
+    match db.delete_user_token(&requester, &oid).await {
+        Ok(0) => Err("session not found".into()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Signs the requester out of every session except `current`; personal tokens are kept.
+pub async fn sessions_revoke_others(db: &Database, requester: ObjectId, current: &ObjectId) -> Result<u64, String> {
+    db.delete_user_sessions(&requester, Some(current)).await.map_err(|e| e.to_string())
+}
+
+// LINKED IDENTITIES
+pub async fn identities_list(db: &Database, oidc: &Oidc, requester: ObjectId) -> Result<Vec<IdentityInfo>, String> {
+    let identities = db.list_identities(&requester).await.map_err(|e| e.to_string())?;
+    Ok(identities
+        .into_iter()
+        .map(|i| IdentityInfo {
+            id: i._id.to_hex(),
+            provider_name: oidc.provider(&i.provider).map(|p| p.name.clone()).unwrap_or_else(|| i.provider.clone()),
+            provider: i.provider,
+            email: i.email,
+            created_at: i.created_at,
+            last_used_at: i.last_used_at,
+        })
+        .collect())
+}
+
+pub async fn identity_unlink(db: &Database, requester: ObjectId, id: &str) -> Result<(), String> {
+    let oid = ObjectId::parse_str(id).map_err(|_| "identity not found".to_string())?;
+    // directory accounts stay tied to the directory, which decides who may sign in
+    let identities = db.list_identities(&requester).await.map_err(|e| e.to_string())?;
+    if identities.iter().any(|i| i._id == oid && i.provider == crate::auth_backend::LDAP_PROVIDER) {
+        return Err("forbidden".into());
+    }
+    match db.delete_identity(&requester, &oid).await {
+        Ok(0) => Err("identity not found".into()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// SSH KEYS
+/// A key identifies either one account or one repository, never both.
+async fn ssh_key_in_use(db: &Database, fingerprint: &str) -> Result<bool, String> {
+    Ok(db.find_ssh_key(fingerprint).await.map_err(|e| e.to_string())?.is_some()
+        || db.find_deploy_key(fingerprint).await.map_err(|e| e.to_string())?.is_some())
+}
+
+fn ssh_key_to_info(k: crate::db::SshKey) -> SshKeyInfo {
+    SshKeyInfo {
+        id: k._id.to_hex(),
+        name: k.name,
+        fingerprint: k.fingerprint,
+        public_key: k.public_key,
+        created_at: k.created_at,
+        last_used_at: k.last_used_at,
+    }
+}
+
+pub async fn ssh_keys_list(db: &Database, requester: ObjectId) -> Result<Vec<SshKeyInfo>, String> {
+    let keys = db.list_ssh_keys(&requester).await.map_err(|e| e.to_string())?;
+    Ok(keys.into_iter().map(ssh_key_to_info).collect())
+}
+
+pub async fn ssh_key_add(db: &Database, requester: ObjectId, payload: AddSshKeyRequest) -> Result<SshKeyInfo, String> {
+    let (public_key, fingerprint) = crate::ssh::parse_public_key(&payload.key).ok_or_else(|| "invalid key".to_string())?;
+    let name = match payload.name.trim() {
+        "" => payload.key.split_whitespace().nth(2).unwrap_or("SSH key").to_string(),
+        n => n.to_string(),
+    };
+    if ssh_key_in_use(db, &fingerprint).await? {
+        return Err("already exists".into());
+    }
+    let key = crate::db::SshKey {
+        _id: ObjectId::new(),
+        user: requester,
+        name,
+        fingerprint,
+        public_key,
+        created_at: DateTime::now(),
+        last_used_at: None,
+    };
+    db.create_ssh_key(key.clone()).await.map_err(|e| e.to_string())?;
+    Ok(ssh_key_to_info(key))
+}
+
+pub async fn ssh_key_delete(db: &Database, requester: ObjectId, id: &str) -> Result<(), String> {
+    let oid = ObjectId::parse_str(id).map_err(|_| "ssh key not found".to_string())?;
+    match db.delete_ssh_key(&requester, &oid).await {
+        Ok(0) => Err("ssh key not found".into()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// INVITES
+fn invite_to_info(i: crate::db::Invite) -> InviteInfo {
+    InviteInfo {
+        id: i._id.to_hex(),
+        note: i.note,
+        max_uses: i.max_uses,
+        uses: i.uses,
+        created_at: i.created_at,
+        expires_at: i.expires_at,
+    }
+}
+
+/// Loads the requester; fails with "forbidden" unless they may create invites.
+async fn inviter(db: &Database, requester: &ObjectId) -> Result<crate::db::User, String> {
+    let user = db
+        .find_user_by_id(requester)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "user not found".to_string())?;
+    if !crate::auth::can_invite(&user) {
+        return Err("forbidden".into());
+    }
+    Ok(user)
+}
+
+/// Invites created by the requester; admins see everyone's.
+pub async fn invites_list(db: &Database, requester: ObjectId) -> Result<Vec<InviteInfo>, String> {
+    let user = inviter(db, &requester).await?;
+    let created_by = if crate::auth::is_admin(&user) { None } else { Some(&requester) };
+    let invites = db.list_invites(created_by).await.map_err(|e| e.to_string())?;
+    Ok(invites.into_iter().map(invite_to_info).collect())
+}
+
+pub async fn invite_create(db: &Database, requester: ObjectId, payload: CreateInviteRequest) -> Result<CreatedInviteResponse, String> {
+    inviter(db, &requester).await?;
+    let max_uses = payload.max_uses.unwrap_or(1);
+    if max_uses == 0 {
+        return Err("invalid max uses".into());
+    }
+    if payload.expires_in_days == Some(0) {
+        return Err("invalid expiry".into());
+    }
+    let note = payload.note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+
+    let (invite, code) = crate::auth::create_invite(db, requester, note, max_uses, payload.expires_in_days)
+        .await
+        .map_err(|e| e.to_string())?;
+    let url = format!("{}/register?invite={}", crate::mail::public_url(), code);
+    Ok(CreatedInviteResponse { code, url, info: invite_to_info(invite) })
+}
+
+/// Revokes an invite; admins may revoke anyone's.
+pub async fn invite_revoke(db: &Database, requester: ObjectId, id: &str) -> Result<(), String> {
+    let user = inviter(db, &requester).await?;
+    let oid = ObjectId::parse_str(id).map_err(|_| "invite not found".to_string())?;
+    let created_by = if crate::auth::is_admin(&user) { None } else { Some(&requester) };
+    match db.delete_invite(&oid, created_by).await {
+        Ok(0) => Err("invite not found".into()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// ACCOUNT
+/// Loads the requester and checks their current password with the configured backends
+/// under the login throttle; fails with "wrong password" or "too many failed attempts".
+async fn confirm_password(db: &Database, backend: &dyn AuthBackend, requester: &ObjectId, password: &str, ip: Option<&str>) -> Result<crate::db::User, String> {
+    let user = db
+        .find_user_by_id(requester)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "user not found".to_string())?;
+    match crate::auth::verify_password(db, backend, &user.username, password, ip).await {
+        Ok(u) if u._id == user._id => {}
+        Ok(_) | Err(AuthError::InvalidCredentials) => return Err("wrong password".into()),
+        Err(AuthError::TooManyAttempts(_)) => return Err("too many failed attempts".into()),
+        Err(e) => return Err(e.to_string()),
+    }
+    crate::throttle::record_success(db, requester).await;
+    Ok(user)
+}
+
+/// Changes the requester's password, signs out every other session and revokes personal
+/// access tokens; `current` is kept.
+pub async fn account_change_password(
+    db: &Database,
+    backend: &dyn AuthBackend,
+    requester: ObjectId,
+    current: Option<&ObjectId>,
+    payload: ChangePasswordRequest,
+    ip: Option<&str>,
+) -> Result<(), String> {
+    confirm_password(db, backend, &requester, &payload.current_password, ip).await?;
+    if !crate::auth::is_valid_password(&payload.new_password) {
+        return Err("invalid password".into());
+    }
+    let hash = crate::auth::hash_password(&payload.new_password).map_err(|e| e.to_string())?;
+    db.update_password(&requester, &hash).await.map_err(|e| e.to_string())?;
+    db.delete_user_sessions(&requester, current).await.map_err(|e| e.to_string())?;
+    db.delete_personal_tokens(&requester).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Changes the requester's email and sends a verification link to the new address.
+pub async fn account_change_email(
+    db: &Database,
+    backend: &dyn AuthBackend,
+    mailer: &dyn Mailer,
+    requester: ObjectId,
+    payload: ChangeEmailRequest,
+    ip: Option<&str>,
+) -> Result<(), String> {
+    let mut user = confirm_password(db, backend, &requester, &payload.password, ip).await?;
+    let email = payload.email.trim();
+    if !crate::auth::is_valid_email(email) {
+        return Err("invalid email".into());
+    }
+    if let Some(other) = db.find_user_by_login(email).await.map_err(|e| e.to_string())? {
+        if other._id != requester {
+            return Err("already exists".into());
+        }
+        return Ok(());
+    }
+    db.update_email(&requester, email).await.map_err(|e| e.to_string())?;
+    user.email = email.to_string();
+    crate::auth::send_email_verification(db, mailer, &user).await.map_err(|e| e.to_string())
+}
+
+/// Sends a fresh verification link to the requester's current address.
+pub async fn account_resend_verification(db: &Database, mailer: &dyn Mailer, requester: ObjectId) -> Result<(), String> {
+    let user = db
+        .find_user_by_id(&requester)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "user not found".to_string())?;
+    if user.email_verified {
+        return Err("email already verified".into());
+    }
+    crate::auth::send_email_verification(db, mailer, &user).await.map_err(|e| e.to_string())
+}
+
+/// Renames the requester; the old name keeps redirecting to them until someone else takes it.
+pub async fn account_change_username(db: &Database, requester: ObjectId, payload: ChangeUsernameRequest) -> Result<(), String> {
+    let user = db
+        .find_user_by_id(&requester)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "user not found".to_string())?;
+    let name = payload.username.trim().to_string();
+    if name == user.username {
+        return Ok(());
+    }
+    if !crate::auth::is_valid_name(&name) {
+        return Err("invalid name".into());
+    }
+    if db.find_owner_by_name(&name).await.map_err(|e| e.to_string())?.is_some() {
+        return Err("already exists".into());
+    }
+
+    db.update_username(&requester, &name).await.map_err(|e| e.to_string())?;
+    db.delete_name_redirect(&name).await.map_err(|e| e.to_string())?;
+    db.set_name_redirect(&user.username, &requester).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 // HELPERS
 
 pub async fn get_user_id_from_token(db: &Database, token: String) -> Result<ObjectId, AuthError> {
@@ -27,6 +452,20 @@ pub async fn get_user_id_from_token(db: &Database, token: String) -> Result<Obje
     Ok(id)
 }
 
+/// The requester as a policy actor on `repo`; anonymous when `requester_user_id` is `None`.
+pub async fn repo_actor(db: &Database, requester_user_id: Option<ObjectId>, repo: &Repository) -> Result<Actor, String> {
+    Actor::resolve(db, requester_user_id, repo).await.map_err(|e| e.to_string())
+}
+
+/// Fails with "forbidden" unless the requester may perform `action` on `repo`.
+async fn authorize(db: &Database, requester_user_id: Option<ObjectId>, repo: &Repository, action: Action) -> Result<(), String> {
+    if policy::can(&repo_actor(db, requester_user_id, repo).await?, action, repo) {
+        Ok(())
+    } else {
+        Err("forbidden".into())
+    }
+}
+
 pub async fn username_by_id(db: &Database, user_id: &ObjectId) -> Result<Option<String>, String> {
     match db.find_user_by_id(user_id).await {
         Ok(Some(user)) => Ok(Some(user.username)),
@@ -35,28 +474,58 @@ pub async fn username_by_id(db: &Database, user_id: &ObjectId) -> Result<Option<
     }
 }
 
+/// Name of the user or organization owning a repository.
+pub async fn owner_name_by_id(db: &Database, owner_id: &ObjectId) -> Result<Option<String>, String> {
+    match db.find_owner_by_id(owner_id).await {
+        Ok(owner) => Ok(owner.map(|o| o.name().to_string())),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 // REPOS
+/// Fails unless the user exists and may create repositories, i.e. has verified their email.
+async fn ensure_can_create(db: &Database, user_id: &ObjectId) -> Result<(), String> {
+    let creator = db
+        .find_user_by_id(user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "user not found".to_string())?;
+    if crate::auth::email_verification_pending(&creator) {
+        return Err("email not verified".into());
+    }
+    Ok(())
+}
+
 pub async fn repo_create(db: &Database, user_id: ObjectId, payload: CreateRepoRequest) -> Result<Repository, String> {
+    ensure_can_create(db, &user_id).await?;
 
     let name = payload.name.trim().to_string();
     if name.is_empty() {
         return Err("name must not be empty".into());
     }
-    
-    if db.is_repo_exists(&user_id, &name).await.map_err(|e| e.to_string())? == true {
+    if !is_valid_repo_name(&name) {
+        return Err("invalid name".into());
+    }
+
+    let owner_id = match payload.owner.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        None => user_id,
+        Some(owner_name) => *owned_namespace(db, &user_id, owner_name).await?.id(),
+    };
+
+    if db.is_repo_exists(&owner_id, &name).await.map_err(|e| e.to_string())? == true {
         return Err("already exists".into())
     }
-    
-   
 
     let now: DateTime = DateTime::now();
     let repo_id = bson::oid::ObjectId::new();
     let repo_doc = Repository {
         _id: repo_id,
-        user: user_id,
+        user: owner_id,
         name,
         description: payload.description.unwrap_or_default(),
         is_private: payload.is_private.unwrap_or(false),
+        website: None,
+        topics: Vec::new(),
         forked_from: None,
         created_at: now,
         updated_at: now,
@@ -68,20 +537,257 @@ pub async fn repo_create(db: &Database, user_id: ObjectId, payload: CreateRepoRe
         let _ = db.delete_repository_by_id(&repo_doc._id).await;
         return Err(e.to_string());
     }
+    // a former path of a renamed repository now belongs to the new one
+    db.delete_repo_redirect(&repo_doc.user, &repo_doc.name).await.map_err(|e| e.to_string())?;
 
     Ok(repo_doc)
 }
 
+/// The user themselves or an organization they own; the namespaces a user may create
+/// or fork repositories in.
+async fn owned_namespace(db: &Database, user_id: &ObjectId, owner_name: &str) -> Result<Owner, String> {
+    match db.find_owner_by_name(owner_name).await.map_err(|e| e.to_string())? {
+        Some(Owner::User(u)) if &u._id == user_id => Ok(Owner::User(u)),
+        Some(Owner::User(_)) => Err("forbidden".into()),
+        Some(Owner::Org(org)) => {
+            let member = db.find_org_member(&org._id, user_id).await.map_err(|e| e.to_string())?;
+            if member.map(|m| m.role) != Some(OrgRole::Owner) {
+                return Err("forbidden".into());
+            }
+            Ok(Owner::Org(org))
+        }
+        None => Err("owner not found".into()),
+    }
+}
+
+/// Whether a repository may be handed to `target`: any other user may receive one,
+/// while an organization only takes repositories from its owners.
+fn may_transfer_to(requester: &ObjectId, target: &Owner, org_role: Option<OrgRole>) -> bool {
+    match target {
+        Owner::User(u) => &u._id == requester || !u.suspended,
+        Owner::Org(_) => org_role == Some(OrgRole::Owner),
+    }
+}
+
+/// Repository names end up in URLs and clone paths, so they are kept to a safe set of characters.
+fn is_valid_repo_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 100
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !name.starts_with('.')
+        && !name.ends_with(".git")
+}
+
+const MAX_TOPICS: usize = 20;
+
+/// Topics are short lowercase tags such as `rust` or `web-server`.
+fn is_valid_topic(topic: &str) -> bool {
+    !topic.is_empty()
+        && topic.len() <= 35
+        && topic.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !topic.starts_with('-')
+}
+
+fn is_valid_website(url: &str) -> bool {
+    (url.starts_with("https://") || url.starts_with("http://"))
+        && url.len() <= 255
+        && !url.contains(|c: char| c.is_whitespace() || c == '"' || c == '<' || c == '>')
+}
+
+/// Updates the editable attributes of a repository; see `UpdateRepoRequest`.
+pub async fn repo_update(db: &Database, requester: ObjectId, payload: UpdateRepoRequest) -> Result<Repository, String> {
+    let mut repository = resolve_repo_by_id(db, &payload.id).await?;
+    authorize(db, Some(requester), &repository, Action::Admin).await?;
+
+    if let Some(description) = payload.description {
+        repository.description = description.trim().to_string();
+    }
+    if let Some(website) = payload.website {
+        let website = website.trim();
+        repository.website = match website {
+            "" => None,
+            w if is_valid_website(w) => Some(w.to_string()),
+            _ => return Err("invalid website".into()),
+        };
+    }
+    if let Some(topics) = payload.topics {
+        let mut clean: Vec<String> = Vec::new();
+        for topic in topics {
+            let topic = topic.trim().to_ascii_lowercase();
+            if topic.is_empty() || clean.contains(&topic) {
+                continue;
+            }
+            if !is_valid_topic(&topic) {
+                return Err("invalid topic".into());
+            }
+            clean.push(topic);
+        }
+        if clean.len() > MAX_TOPICS {
+            return Err("too many topics".into());
+        }
+        repository.topics = clean;
+    }
+    if let Some(is_private) = payload.is_private {
+        // making the fork public would expose the private parent's history
+        if !is_private && repository.is_private {
+            let parent = match repository.forked_from.as_deref() {
+                Some(id) => db.find_repo_by_hex(id).await.map_err(|e| e.to_string())?,
+                None => None,
+            };
+            if parent.is_some_and(|p| p.is_private) {
+                return Err("forks of private repositories must stay private".into());
+            }
+        }
+        repository.is_private = is_private;
+    }
+
+    if let Some(branch) = payload.default_branch.as_deref().map(str::trim).filter(|b| !b.is_empty()) {
+        let branches = crate::repo::list_branches(&repository.user, &repository._id)
+            .await
+            .map_err(|e| e.to_string())?;
+        match branches.iter().find(|b| b.name == branch) {
+            None => return Err("branch not found".into()),
+            Some(b) if b.is_head => {}
+            Some(_) => crate::repo::set_default_branch(&repository.user, &repository._id, branch)
+                .await
+                .map_err(|e| e.to_string())?,
+        }
+    }
+
+    db.update_repo_settings(&repository).await.map_err(|e| e.to_string())?;
+    resolve_repo_by_id(db, &payload.id).await
+}
+
+/// Renames a repository; the old name keeps redirecting to it.
+pub async fn repo_rename(db: &Database, requester: ObjectId, payload: RenameRepoRequest) -> Result<Repository, String> {
+    let repository = resolve_repo_by_id(db, &payload.id).await?;
+    authorize(db, Some(requester), &repository, Action::Admin).await?;
+
+    let name = payload.name.trim().to_string();
+    if name == repository.name {
+        return Ok(repository);
+    }
+    if !is_valid_repo_name(&name) {
+        return Err("invalid name".into());
+    }
+    if db.is_repo_exists(&repository.user, &name).await.map_err(|e| e.to_string())? {
+        return Err("already exists".into());
+    }
+
+    db.rename_repository(&repository._id, &name).await.map_err(|e| e.to_string())?;
+    db.delete_repo_redirect(&repository.user, &name).await.map_err(|e| e.to_string())?;
+    db.set_repo_redirect(&repository.user, &repository.name, &repository._id)
+        .await
+        .map_err(|e| e.to_string())?;
+    resolve_repo_by_id(db, &payload.id).await
+}
+
+/// Moves a repository to another user, to the requester or to an organization they own.
+/// Requires admin rights on the repository.
+pub async fn repo_transfer(db: &Database, requester: ObjectId, payload: TransferRepoRequest) -> Result<Repository, String> {
+    let repository = resolve_repo_by_id(db, &payload.id).await?;
+    authorize(db, Some(requester), &repository, Action::Admin).await?;
+    let owner = db
+        .find_owner_by_name(payload.owner.trim())
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "owner not found".to_string())?;
+    let org_role = match &owner {
+        Owner::Org(org) => db.find_org_member(&org._id, &requester).await.map_err(|e| e.to_string())?.map(|m| m.role),
+        Owner::User(_) => None,
+    };
+    if !may_transfer_to(&requester, &owner, org_role) {
+        return Err("cannot transfer to this owner".into());
+    }
+    move_repository(db, &repository, &owner).await?;
+    resolve_repo_by_id(db, &payload.id).await
+}
+
+/// Forks a repository the requester can read into their namespace or an organization
+/// they own. A fork of a private repository is private as well.
+pub async fn repo_fork(db: &Database, requester: ObjectId, payload: ForkRepoRequest) -> Result<Repository, String> {
+    let parent = resolve_repo_by_id(db, &payload.id).await?;
+    if !policy::can(&repo_actor(db, Some(requester), &parent).await?, Action::Read, &parent) {
+        return Err("repository not found".into());
+    }
+    ensure_can_create(db, &requester).await?;
+
+    let owner_id = match payload.owner.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        None => requester,
+        Some(owner_name) => *owned_namespace(db, &requester, owner_name).await?.id(),
+    };
+    let name = match payload.name.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(n) if !is_valid_repo_name(n) => return Err("invalid name".into()),
+        Some(n) => n.to_string(),
+        None => parent.name.clone(),
+    };
+    if db.is_repo_exists(&owner_id, &name).await.map_err(|e| e.to_string())? {
+        return Err("already exists".into());
+    }
+
+    let now: DateTime = DateTime::now();
+    let fork = Repository {
+        _id: ObjectId::new(),
+        user: owner_id,
+        name,
+        description: parent.description.clone(),
+        is_private: parent.is_private,
+        website: parent.website.clone(),
+        topics: parent.topics.clone(),
+        forked_from: Some(parent._id.to_hex()),
+        created_at: now,
+        updated_at: now,
+    };
+
+    db.create_repository(fork.clone()).await.map_err(|e| e.to_string())?;
+    if let Err(e) = crate::repo::fork(&parent.user, &parent._id, &fork.user, &fork._id).await {
+        let _ = db.delete_repository_by_id(&fork._id).await;
+        let _ = fs::remove_dir_all(crate::repo::repo_path(&fork.user, &fork._id)).await;
+        return Err(e.to_string());
+    }
+    db.delete_repo_redirect(&fork.user, &fork.name).await.map_err(|e| e.to_string())?;
+    Ok(fork)
+}
+
+/// Forks of a repository that the requester may see.
+pub async fn repo_forks(db: &Database, requester: Option<ObjectId>, id: &str) -> Result<Vec<Repository>, String> {
+    let parent = resolve_repo_by_id(db, id).await?;
+    if !policy::can(&repo_actor(db, requester, &parent).await?, Action::Read, &parent) {
+        return Err("repository not found".into());
+    }
+    let mut visible = Vec::new();
+    for fork in db.find_forks(&parent._id).await.map_err(|e| e.to_string())? {
+        if policy::can(&repo_actor(db, requester, &fork).await?, Action::Read, &fork) {
+            visible.push(fork);
+        }
+    }
+    Ok(visible)
+}
+
+/// The repository `repo` was forked from, unless it is gone or hidden from the requester.
+pub async fn repo_fork_parent(db: &Database, requester: Option<ObjectId>, repo: &Repository) -> Result<Option<Repository>, String> {
+    let parent = match repo.forked_from.as_deref() {
+        Some(id) => db.find_repo_by_hex(id).await.map_err(|e| e.to_string())?,
+        None => None,
+    };
+    match parent {
+        Some(p) if policy::can(&repo_actor(db, requester, &p).await?, Action::Read, &p) => Ok(Some(p)),
+        _ => Ok(None),
+    }
+}
+
 pub async fn repo_delete(db: &Database, requester: ObjectId, repo_id_hex: &str) -> Result<(), String> {
     let repository = db
         .find_repo_by_hex(repo_id_hex)
         .await
         .map_err(|e| e.to_string())?
-        .ok_or_else(|| "not found".to_string())?;
-    if repository.user != requester {
-        return Err("forbidden".into());
-    }
+        .ok_or_else(|| "repository not found".to_string())?;
+    authorize(db, Some(requester), &repository, Action::Admin).await?;
+    remove_repository(db, &repository).await
+}
 
+/// Deletes a repository from disk together with everything that refers to it.
+async fn remove_repository(db: &Database, repository: &Repository) -> Result<(), String> {
     let path = crate::repo::repo_path(&repository.user, &repository._id);
     if let Err(e) = fs::remove_dir_all(&path).await {
         if e.kind() != std::io::ErrorKind::NotFound {
@@ -89,6 +795,12 @@ pub async fn repo_delete(db: &Database, requester: ObjectId, repo_id_hex: &str)
         }
     }
 
+    db.delete_collaborators_for_repo(&repository._id).await.map_err(|e| e.to_string())?;
+    db.delete_deploy_credentials_for_repo(&repository._id).await.map_err(|e| e.to_string())?;
+    db.delete_repo_redirects_for_repo(&repository._id).await.map_err(|e| e.to_string())?;
+    db.clear_forked_from(&repository._id).await.map_err(|e| e.to_string())?;
+    db.pull_repo_from_teams(&repository._id).await.map_err(|e| e.to_string())?;
+
     match db.delete_repository_by_id(&repository._id).await {
         Ok(1) => Ok(()),
         Ok(_) => Err("failed to delete repository".into()),
@@ -96,6 +808,48 @@ pub async fn repo_delete(db: &Database, requester: ObjectId, repo_id_hex: &str)
     }
 }
 
+/// Hands a repository to another owner, moving it on disk to the new owner's directory.
+/// Collaborators and team grants are dropped, and the old `owner/name` path redirects
+/// to the new one.
+async fn move_repository(db: &Database, repository: &Repository, new_owner: &Owner) -> Result<(), String> {
+    if new_owner.id() == &repository.user {
+        return Ok(());
+    }
+    if db.is_repo_exists(new_owner.id(), &repository.name).await.map_err(|e| e.to_string())? {
+        return Err("already exists".into());
+    }
+
+    // the redirect is written first so a failure below leaves nothing half-moved;
+    // while the repository still sits at the old path the direct match wins over it
+    db.delete_repo_redirect(new_owner.id(), &repository.name).await.map_err(|e| e.to_string())?;
+    db.set_repo_redirect(&repository.user, &repository.name, &repository._id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let from = crate::repo::repo_path(&repository.user, &repository._id);
+    let to = crate::repo::repo_path(new_owner.id(), &repository._id);
+    let moved = async {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| e.to_string())?;
+        }
+        fs::rename(&from, &to).await.map_err(|e| e.to_string())?;
+        if let Err(e) = db.set_repo_owner(&repository._id, new_owner.id()).await {
+            let _ = fs::rename(&to, &from).await;
+            return Err(e.to_string());
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = moved {
+        let _ = db.delete_repo_redirect(&repository.user, &repository.name).await;
+        return Err(e);
+    }
+
+    db.delete_collaborators_for_repo(&repository._id).await.map_err(|e| e.to_string())?;
+    db.pull_repo_from_teams(&repository._id).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 pub async fn repo_list(
     db: &Database,
     requester_user_id: Option<ObjectId>,
@@ -104,9 +858,9 @@ pub async fn repo_list(
     
     use mongodb::bson::doc;
 
-    let owner_user_id: Option<ObjectId> = if let Some(owner_username) = &query.owner {
-        match db.find_user_by_login(owner_username).await {
-            Ok(Some(u)) => Some(u._id),
+    let owner_id: Option<ObjectId> = if let Some(owner_name) = &query.owner {
+        match db.find_owner_by_name(owner_name).await {
+            Ok(Some(o)) => Some(o.id().clone()),
             Ok(None) => return Ok(Vec::new()),
             Err(e) => return Err(e.to_string()),
         }
@@ -122,28 +876,41 @@ pub async fn repo_list(
         }
     }
 
-    let privacy_filter = if let Some(owner_id) = owner_user_id {
-        let can_see_private = requester_user_id.as_ref() == Some(&owner_id);
-        if can_see_private {
-            doc! { "user": &owner_id }
-        } else {
-            doc! { "user": &owner_id, "is_private": false }
-        }
-    } else if let Some(uid) = requester_user_id {
-        doc! {
-            "$or": [
-                { "is_private": false },
-                { "is_private": true, "user": &uid }
-            ]
+    // namespaces whose private repositories the requester sees in full, plus
+    // single repositories shared through collaboration or teams
+    let (own_namespaces, shared_repo_ids) = match &requester_user_id {
+        Some(uid) => {
+            let mut namespaces = vec![uid.clone()];
+            for (org, role) in db.orgs_for_user(uid).await.map_err(|e| e.to_string())? {
+                if role == OrgRole::Owner {
+                    namespaces.push(org._id);
+                }
+            }
+            let mut ids = db.collaborator_repo_ids(uid).await.map_err(|e| e.to_string())?;
+            ids.extend(db.team_repo_ids(uid).await.map_err(|e| e.to_string())?);
+            (namespaces, ids)
         }
-    } else {
-        doc! { "is_private": false }
+        None => (Vec::new(), Vec::new()),
     };
 
-    let mut filter = privacy_filter;
-    if !text_or.is_empty() {
-        filter.insert("$or", text_or);
-    }
+    let visible = doc! {
+        "$or": [
+            { "is_private": false },
+            { "user": { "$in": &own_namespaces } },
+            { "_id": { "$in": &shared_repo_ids } }
+        ]
+    };
+
+    let privacy_filter = match owner_id {
+        Some(oid) => doc! { "$and": [ { "user": oid }, visible ] },
+        None => visible,
+    };
+
+    let filter = if text_or.is_empty() {
+        privacy_filter
+    } else {
+        doc! { "$and": [ privacy_filter, { "$or": text_or } ] }
+    };
 
     let sort_doc = match query.filter.as_deref() {
         Some("newest") => doc! { "created_at": -1 },
@@ -158,7 +925,7 @@ async fn resolve_repo_by_id(db: &Database, repo_id_hex: &str) -> Result<Reposito
     db.find_repo_by_hex(repo_id_hex)
         .await
         .map_err(|e| e.to_string())?
-        .ok_or_else(|| "not found".to_string())
+        .ok_or_else(|| "repository not found".to_string())
 }
 
 // GIT
@@ -168,10 +935,7 @@ pub async fn git_branches(
     id: &String,
 ) -> Result<Vec<Branch>, String> {
     let repo = resolve_repo_by_id(db, id).await?;
-    let can_see = requester_user_id == Some(repo.user.clone());
-    if repo.is_private && !can_see {
-        return Err("forbidden".into());
-    }
+    authorize(db, requester_user_id, &repo, Action::Read).await?;
     crate::repo::list_branches(&repo.user, &repo._id).await.map_err(|e| e.to_string())
 }
 
@@ -182,11 +946,7 @@ pub async fn git_remove_branch(
     branch: &String,
 ) -> Result<(), String> {
     let repo = resolve_repo_by_id(db, id).await?;
-
-    let can_edit = requester_user_id == Some(repo.user.clone());
-    if repo.is_private && !can_edit {
-        return Err("forbidden".into());
-    }
+    authorize(db, requester_user_id, &repo, Action::DeleteBranch).await?;
 
     crate::repo::delete_branch(&repo.user, &repo._id, branch)
         .await
@@ -202,10 +962,7 @@ pub async fn git_content(
     use base64::Engine;
 
     let repo = resolve_repo_by_id(db, &query.id).await?;
-    let can_see = requester_user_id == Some(repo.user.clone());
-    if repo.is_private && !can_see {
-        return Err("forbidden".into());
-    }
+    authorize(db, requester_user_id, &repo, Action::Read).await?;
 
     let branch_opt = query.branch.as_deref();
     let rev = query.commit.as_deref().unwrap_or_else(|| branch_opt.unwrap_or("HEAD"));
@@ -236,10 +993,7 @@ pub async fn git_commits(
     query: CommitsQuery,
 ) -> Result<Vec<CommitInfo>, String> {
     let repo = resolve_repo_by_id(db, &query.id).await?;
-    let can_see = requester_user_id == Some(repo.user.clone());
-    if repo.is_private && !can_see {
-        return Err("forbidden".into());
-    }
+    authorize(db, requester_user_id, &repo, Action::Read).await?;
 
     let branch = query.branch.as_deref().unwrap_or("HEAD");
     let limit = query.limit.unwrap_or(50);
@@ -258,10 +1012,7 @@ pub async fn git_download(
     use zip::CompressionMethod;
 
     let repo = resolve_repo_by_id(db, &query.id).await?;
-    let can_see = requester_user_id == Some(repo.user.clone());
-    if repo.is_private && !can_see {
-        return Err("forbidden".into());
-    }
+    authorize(db, requester_user_id, &repo, Action::Read).await?;
 
     let branch_opt = query.branch.as_deref();
     let rev = query
@@ -302,4 +1053,680 @@ pub async fn git_download(
     }
 
     Ok((default_name, buf))
-}
\ No newline at end of file
+}
+// COLLABORATORS
+pub async fn collaborators_list(
+    db: &Database,
+    requester_user_id: Option<ObjectId>,
+    id: &str,
+) -> Result<Vec<CollaboratorInfo>, String> {
+    let repo = resolve_repo_by_id(db, id).await?;
+    authorize(db, requester_user_id, &repo, Action::Write).await?;
+
+    let mut out = Vec::new();
+    for c in db.list_collaborators(&repo._id).await.map_err(|e| e.to_string())? {
+        if let Some(user) = db.find_user_by_id(&c.user).await.map_err(|e| e.to_string())? {
+            out.push(CollaboratorInfo {
+                username: user.username,
+                display_name: user.display_name,
+                role: c.role,
+            });
+        }
+    }
+    Ok(out)
+}
+
+pub async fn collaborator_add(
+    db: &Database,
+    requester: ObjectId,
+    payload: AddCollaboratorRequest,
+) -> Result<(), String> {
+    let repo = resolve_repo_by_id(db, &payload.id).await?;
+    authorize(db, Some(requester), &repo, Action::Admin).await?;
+
+    let user = db
+        .find_user_by_login(payload.username.trim())
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "user not found".to_string())?;
+    if user._id == repo.user {
+        return Err("owner cannot be a collaborator".into());
+    }
+
+    db.set_collaborator(&repo._id, &user._id, payload.role).await.map_err(|e| e.to_string())
+}
+
+pub async fn collaborator_remove(
+    db: &Database,
+    requester: ObjectId,
+    id: &str,
+    username: &str,
+) -> Result<(), String> {
+    let repo = resolve_repo_by_id(db, id).await?;
+    let user = db
+        .find_user_by_login(username)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "user not found".to_string())?;
+
+    // collaborators may always remove themselves
+    if user._id != requester {
+        authorize(db, Some(requester), &repo, Action::Admin).await?;
+    }
+
+    match db.delete_collaborator(&repo._id, &user._id).await {
+        Ok(0) => Err("user not found".into()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// DEPLOY KEYS
+fn deploy_key_to_info(k: crate::db::DeployKey) -> DeployKeyInfo {
+    DeployKeyInfo {
+        id: k._id.to_hex(),
+        name: k.name,
+        fingerprint: k.fingerprint,
+        public_key: k.public_key,
+        write: k.write,
+        created_at: k.created_at,
+        last_used_at: k.last_used_at,
+    }
+}
+
+fn deploy_token_to_info(t: crate::db::DeployToken) -> DeployTokenInfo {
+    DeployTokenInfo {
+        id: t._id.to_hex(),
+        name: t.name,
+        write: t.write,
+        created_at: t.created_at,
+        expires_at: t.expires_at,
+        last_used_at: t.last_used_at,
+    }
+}
+
+pub async fn deploy_keys_list(db: &Database, requester: ObjectId, id: &str) -> Result<Vec<DeployKeyInfo>, String> {
+    let repo = resolve_repo_by_id(db, id).await?;
+    authorize(db, Some(requester), &repo, Action::Admin).await?;
+    let keys = db.list_deploy_keys(&repo._id).await.map_err(|e| e.to_string())?;
+    Ok(keys.into_iter().map(deploy_key_to_info).collect())
+}
+
+pub async fn deploy_key_add(db: &Database, requester: ObjectId, payload: AddDeployKeyRequest) -> Result<DeployKeyInfo, String> {
+    let repo = resolve_repo_by_id(db, &payload.id).await?;
+    authorize(db, Some(requester), &repo, Action::Admin).await?;
+
+    let (public_key, fingerprint) = crate::ssh::parse_public_key(&payload.key).ok_or_else(|| "invalid key".to_string())?;
+    let name = match payload.name.trim() {
+        "" => payload.key.split_whitespace().nth(2).unwrap_or("Deploy key").to_string(),
+        n => n.to_string(),
+    };
+    if ssh_key_in_use(db, &fingerprint).await? {
+        return Err("already exists".into());
+    }
+    let key = crate::db::DeployKey {
+        _id: ObjectId::new(),
+        repo: repo._id,
+        name,
+        fingerprint,
+        public_key,
+        write: payload.write,
+        created_by: requester,
+        created_at: DateTime::now(),
+        last_used_at: None,
+    };
+    db.create_deploy_key(key.clone()).await.map_err(|e| e.to_string())?;
+    Ok(deploy_key_to_info(key))
+}
+
+pub async fn deploy_key_delete(db: &Database, requester: ObjectId, id: &str) -> Result<(), String> {
+    let oid = ObjectId::parse_str(id).map_err(|_| "deploy key not found".to_string())?;
+    let key = db
+        .find_deploy_key_by_id(&oid)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "deploy key not found".to_string())?;
+    let repo = resolve_repo_by_id(db, &key.repo.to_hex()).await?;
+    authorize(db, Some(requester), &repo, Action::Admin).await?;
+    db.delete_deploy_key(&oid).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub async fn deploy_tokens_list(db: &Database, requester: ObjectId, id: &str) -> Result<Vec<DeployTokenInfo>, String> {
+    let repo = resolve_repo_by_id(db, id).await?;
+    authorize(db, Some(requester), &repo, Action::Admin).await?;
+    let tokens = db.list_deploy_tokens(&repo._id).await.map_err(|e| e.to_string())?;
+    Ok(tokens.into_iter().map(deploy_token_to_info).collect())
+}
+
+pub async fn deploy_token_create(
+    db: &Database,
+    requester: ObjectId,
+    payload: CreateDeployTokenRequest,
+) -> Result<CreatedDeployTokenResponse, String> {
+    let repo = resolve_repo_by_id(db, &payload.id).await?;
+    authorize(db, Some(requester), &repo, Action::Admin).await?;
+
+    let name = payload.name.trim().to_string();
+    if name.is_empty() {
+        return Err("name must not be empty".into());
+    }
+    if payload.expires_in_days == Some(0) {
+        return Err("invalid expiry".into());
+    }
+    let (token, value) = crate::auth::create_deploy_token(db, repo._id, requester, name, payload.write, payload.expires_in_days)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(CreatedDeployTokenResponse { token: value, info: deploy_token_to_info(token) })
+}
+
+pub async fn deploy_token_revoke(db: &Database, requester: ObjectId, id: &str) -> Result<(), String> {
+    let oid = ObjectId::parse_str(id).map_err(|_| "deploy token not found".to_string())?;
+    let token = db
+        .find_deploy_token_by_id(&oid)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "deploy token not found".to_string())?;
+    let repo = resolve_repo_by_id(db, &token.repo.to_hex()).await?;
+    authorize(db, Some(requester), &repo, Action::Admin).await?;
+    db.delete_deploy_token(&oid).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// ORGANIZATIONS
+async fn resolve_org(db: &Database, name: &str) -> Result<crate::db::Organization, String> {
+    db.find_org_by_name(name)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "organization not found".to_string())
+}
+
+async fn org_role(db: &Database, org_id: &ObjectId, user_id: &ObjectId) -> Result<Option<OrgRole>, String> {
+    Ok(db
+        .find_org_member(org_id, user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .map(|m| m.role))
+}
+
+async fn resolve_user(db: &Database, login: &str) -> Result<crate::db::User, String> {
+    db.find_user_by_login(login.trim())
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "user not found".to_string())
+}
+
+pub async fn org_create(db: &Database, requester: ObjectId, payload: CreateOrgRequest) -> Result<crate::db::Organization, String> {
+    let name = payload.name.trim().to_string();
+    if !crate::auth::is_valid_name(&name) {
+        return Err("invalid name".into());
+    }
+    if db.find_owner_by_name(&name).await.map_err(|e| e.to_string())?.is_some() {
+        return Err("already exists".into());
+    }
+
+    let org = crate::db::Organization {
+        _id: ObjectId::new(),
+        display_name: payload.display_name.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).unwrap_or_else(|| name.clone()),
+        name,
+        description: payload.description.unwrap_or_default(),
+        avatar_url: None,
+        created_at: DateTime::now(),
+    };
+    db.create_org(org.clone()).await.map_err(|e| e.to_string())?;
+    db.delete_name_redirect(&org.name).await.map_err(|e| e.to_string())?;
+    db.set_org_member(&org._id, &requester, OrgRole::Owner).await.map_err(|e| e.to_string())?;
+    Ok(org)
+}
+
+pub async fn org_members(db: &Database, org_name: &str) -> Result<Vec<OrgMemberInfo>, String> {
+    let org = resolve_org(db, org_name).await?;
+    let mut out = Vec::new();
+    for m in db.list_org_members(&org._id).await.map_err(|e| e.to_string())? {
+        if let Some(user) = db.find_user_by_id(&m.user).await.map_err(|e| e.to_string())? {
+            out.push(OrgMemberInfo {
+                username: user.username,
+                display_name: user.display_name,
+                role: m.role,
+            });
+        }
+    }
+    Ok(out)
+}
+
+pub async fn org_member_set(db: &Database, requester: ObjectId, payload: SetOrgMemberRequest) -> Result<(), String> {
+    let org = resolve_org(db, &payload.org).await?;
+    if org_role(db, &org._id, &requester).await? != Some(OrgRole::Owner) {
+        return Err("forbidden".into());
+    }
+    let user = resolve_user(db, &payload.username).await?;
+
+    if payload.role != OrgRole::Owner && org_role(db, &org._id, &user._id).await? == Some(OrgRole::Owner) {
+        ensure_other_owner(db, &org._id, &user._id).await?;
+    }
+    db.set_org_member(&org._id, &user._id, payload.role).await.map_err(|e| e.to_string())
+}
+
+pub async fn org_member_remove(db: &Database, requester: ObjectId, org_name: &str, username: &str) -> Result<(), String> {
+    let org = resolve_org(db, org_name).await?;
+    let user = resolve_user(db, username).await?;
+    // members may always leave on their own
+    if user._id != requester && org_role(db, &org._id, &requester).await? != Some(OrgRole::Owner) {
+        return Err("forbidden".into());
+    }
+    if org_role(db, &org._id, &user._id).await? == Some(OrgRole::Owner) {
+        ensure_other_owner(db, &org._id, &user._id).await?;
+    }
+
+    match db.delete_org_member(&org._id, &user._id).await {
+        Ok(0) => Err("user not found".into()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Whether someone other than `leaving` owns the organization `members` belong to.
+fn has_other_owner(members: &[crate::db::OrgMember], leaving: &ObjectId) -> bool {
+    members.iter().any(|m| m.role == OrgRole::Owner && m.user != *leaving)
+}
+
+/// Refuses to demote or remove the last owner of an organization.
+async fn ensure_other_owner(db: &Database, org_id: &ObjectId, leaving: &ObjectId) -> Result<(), String> {
+    let members = db.list_org_members(org_id).await.map_err(|e| e.to_string())?;
+    if has_other_owner(&members, leaving) {
+        Ok(())
+    } else {
+        Err("organization must keep at least one owner".into())
+    }
+}
+
+pub async fn org_teams(db: &Database, requester: Option<ObjectId>, org_name: &str) -> Result<Vec<TeamInfo>, String> {
+    let org = resolve_org(db, org_name).await?;
+    let is_member = match requester {
+        Some(uid) => org_role(db, &org._id, &uid).await?.is_some(),
+        None => false,
+    };
+    if !is_member {
+        return Err("forbidden".into());
+    }
+
+    let mut out = Vec::new();
+    for team in db.list_teams(&org._id).await.map_err(|e| e.to_string())? {
+        let mut members = Vec::new();
+        for uid in &team.members {
+            if let Some(name) = username_by_id(db, uid).await? {
+                members.push(name);
+            }
+        }
+        let mut repos = Vec::new();
+        for rid in &team.repos {
+            if let Some(r) = db.find_repo(rid).await.map_err(|e| e.to_string())? {
+                repos.push(r.name);
+            }
+        }
+        out.push(TeamInfo {
+            name: team.name,
+            description: team.description,
+            permission: team.permission,
+            members,
+            repos,
+        });
+    }
+    Ok(out)
+}
+
+async fn resolve_owned_team(
+    db: &Database,
+    requester: ObjectId,
+    org_name: &str,
+    team_name: &str,
+) -> Result<(crate::db::Organization, crate::db::Team), String> {
+    let org = resolve_org(db, org_name).await?;
+    if org_role(db, &org._id, &requester).await? != Some(OrgRole::Owner) {
+        return Err("forbidden".into());
+    }
+    let team = db
+        .find_team(&org._id, team_name)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "team not found".to_string())?;
+    Ok((org, team))
+}
+
+pub async fn team_create(db: &Database, requester: ObjectId, payload: CreateTeamRequest) -> Result<(), String> {
+    let org = resolve_org(db, &payload.org).await?;
+    if org_role(db, &org._id, &requester).await? != Some(OrgRole::Owner) {
+        return Err("forbidden".into());
+    }
+    let name = payload.name.trim().to_string();
+    if !crate::auth::is_valid_name(&name) {
+        return Err("invalid name".into());
+    }
+    if db.find_team(&org._id, &name).await.map_err(|e| e.to_string())?.is_some() {
+        return Err("already exists".into());
+    }
+
+    let team = crate::db::Team {
+        _id: ObjectId::new(),
+        org: org._id,
+        name,
+        description: payload.description.unwrap_or_default(),
+        permission: payload.permission,
+        members: Vec::new(),
+        repos: Vec::new(),
+        created_at: DateTime::now(),
+    };
+    db.create_team(team).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub async fn team_delete(db: &Database, requester: ObjectId, org_name: &str, team_name: &str) -> Result<(), String> {
+    let (_org, team) = resolve_owned_team(db, requester, org_name, team_name).await?;
+    db.delete_team(&team._id).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub async fn team_member_add(db: &Database, requester: ObjectId, query: TeamMemberQuery) -> Result<(), String> {
+    let (org, team) = resolve_owned_team(db, requester, &query.org, &query.team).await?;
+    let user = resolve_user(db, &query.username).await?;
+    if org_role(db, &org._id, &user._id).await?.is_none() {
+        return Err("user is not a member of the organization".into());
+    }
+    db.team_add_member(&team._id, &user._id).await.map_err(|e| e.to_string())
+}
+
+pub async fn team_member_remove(db: &Database, requester: ObjectId, query: TeamMemberQuery) -> Result<(), String> {
+    let (_org, team) = resolve_owned_team(db, requester, &query.org, &query.team).await?;
+    let user = resolve_user(db, &query.username).await?;
+    match db.team_remove_member(&team._id, &user._id).await {
+        Ok(0) => Err("user not found".into()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub async fn team_repo_add(db: &Database, requester: ObjectId, query: TeamRepoQuery) -> Result<(), String> {
+    let (org, team) = resolve_owned_team(db, requester, &query.org, &query.team).await?;
+    let repo = db
+        .find_repo_by_user_and_name(&org._id, &query.repo)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "repository not found".to_string())?;
+    db.team_add_repo(&team._id, &repo._id).await.map_err(|e| e.to_string())
+}
+
+pub async fn team_repo_remove(db: &Database, requester: ObjectId, query: TeamRepoQuery) -> Result<(), String> {
+    let (org, team) = resolve_owned_team(db, requester, &query.org, &query.team).await?;
+    let repo = db
+        .find_repo_by_user_and_name(&org._id, &query.repo)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "repository not found".to_string())?;
+    match db.team_remove_repo(&team._id, &repo._id).await {
+        Ok(0) => Err("repository not found".into()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// ADMIN
+/// Loads the requester; fails with "forbidden" unless they administer the instance.
+async fn require_admin(db: &Database, requester: &ObjectId) -> Result<crate::db::User, String> {
+    let user = db
+        .find_user_by_id(requester)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "user not found".to_string())?;
+    if !crate::auth::is_admin(&user) {
+        return Err("forbidden".into());
+    }
+    Ok(user)
+}
+
+const ADMIN_PAGE_SIZE: i64 = 50;
+
+fn admin_page(page: Option<u64>) -> u64 {
+    (page.unwrap_or(1).max(1) - 1) * ADMIN_PAGE_SIZE as u64
+}
+
+/// Case-insensitive substring match on `fields`; the search text is taken literally.
+fn search_filter(q: Option<&str>, fields: &[&str]) -> mongodb::bson::Document {
+    use mongodb::bson::doc;
+    let q = match q.map(str::trim).filter(|q| !q.is_empty()) {
+        Some(q) => q,
+        None => return doc! {},
+    };
+    let pattern: String = q
+        .chars()
+        .flat_map(|c| {
+            let escape = "\\.+*?()|[]{}^$".contains(c);
+            escape.then_some('\\').into_iter().chain(std::iter::once(c))
+        })
+        .collect();
+    let or: Vec<_> = fields
+        .iter()
+        .map(|f| {
+            let mut cond = mongodb::bson::Document::new();
+            cond.insert(*f, doc! { "$regex": pattern.as_str(), "$options": "i" });
+            cond
+        })
+        .collect();
+    doc! { "$or": or }
+}
+
+fn admin_user_info(u: crate::db::User) -> AdminUserInfo {
+    AdminUserInfo {
+        id: u._id.to_hex(),
+        admin: crate::auth::is_admin(&u),
+        username: u.username,
+        display_name: u.display_name,
+        email: u.email,
+        email_verified: u.email_verified,
+        suspended: u.suspended,
+        totp_enabled: u.totp_enabled,
+        created_at: u.created_at,
+    }
+}
+
+async fn find_user_by_name(db: &Database, username: &str) -> Result<crate::db::User, String> {
+    db.find_user_by_login(username.trim())
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "user not found".to_string())
+}
+
+pub async fn admin_stats(db: &Database, requester: ObjectId) -> Result<InstanceStats, String> {
+    use mongodb::bson::doc;
+    require_admin(db, &requester).await?;
+    let err = |e: mongodb::error::Error| e.to_string();
+    Ok(InstanceStats {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        users: db.count_users(doc! {}).await.map_err(err)?,
+        suspended_users: db.count_users(doc! { "suspended": true }).await.map_err(err)?,
+        repositories: db.count_repos(doc! {}).await.map_err(err)?,
+        private_repositories: db.count_repos(doc! { "is_private": true }).await.map_err(err)?,
+        organizations: db.count_orgs().await.map_err(err)?,
+    })
+}
+
+pub async fn admin_users(db: &Database, requester: ObjectId, query: AdminSearchQuery) -> Result<Vec<AdminUserInfo>, String> {
+    require_admin(db, &requester).await?;
+    let filter = search_filter(query.q.as_deref(), &["username", "display_name", "email"]);
+    let users = db
+        .search_users(filter, admin_page(query.page), ADMIN_PAGE_SIZE)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(users.into_iter().map(admin_user_info).collect())
+}
+
+pub async fn admin_user(db: &Database, requester: ObjectId, username: &str) -> Result<AdminUserInfo, String> {
+    require_admin(db, &requester).await?;
+    find_user_by_name(db, username).await.map(admin_user_info)
+}
+
+/// Suspending signs the user out everywhere; their repositories stay available.
+pub async fn admin_set_suspended(db: &Database, requester: ObjectId, payload: SuspendUserRequest) -> Result<(), String> {
+    require_admin(db, &requester).await?;
+    let user = find_user_by_name(db, &payload.username).await?;
+    if user._id == requester {
+        return Err("cannot modify your own account".into());
+    }
+    db.set_user_suspended(&user._id, payload.suspended).await.map_err(|e| e.to_string())?;
+    if payload.suspended {
+        db.delete_all_user_tokens(&user._id).await.map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Deletes an account, every repository it owns and the invites and deploy credentials
+/// it created. Organizations it belonged to stay; the last owner of one cannot be deleted
+/// until ownership is handed to someone else.
+pub async fn admin_delete_user(db: &Database, requester: ObjectId, username: &str) -> Result<(), String> {
+    use mongodb::bson::doc;
+    require_admin(db, &requester).await?;
+    let user = find_user_by_name(db, username).await?;
+    if user._id == requester {
+        return Err("cannot modify your own account".into());
+    }
+    for (org, role) in db.orgs_for_user(&user._id).await.map_err(|e| e.to_string())? {
+        if role == OrgRole::Owner {
+            ensure_other_owner(db, &org._id, &user._id).await?;
+        }
+    }
+
+    for repo in db.find_repos_with_filter_sort(doc! { "user": user._id }, doc! {}).await.map_err(|e| e.to_string())? {
+        remove_repository(db, &repo).await?;
+    }
+    let _ = fs::remove_dir(crate::repo::owner_path(&user._id)).await;
+    db.delete_user_account(&user._id).await.map_err(|e| e.to_string())?;
+    tracing::info!("Admin {} deleted user '{}'", requester, user.username);
+    Ok(())
+}
+
+pub async fn admin_repos(db: &Database, requester: ObjectId, query: AdminSearchQuery) -> Result<Vec<AdminRepoInfo>, String> {
+    require_admin(db, &requester).await?;
+    let filter = search_filter(query.q.as_deref(), &["name", "description"]);
+    let repos = db
+        .search_repos(filter, admin_page(query.page), ADMIN_PAGE_SIZE)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut owners = std::collections::HashMap::new();
+    let mut out = Vec::with_capacity(repos.len());
+    for r in repos {
+        if !owners.contains_key(&r.user) {
+            let name = db
+                .find_owner_by_id(&r.user)
+                .await
+                .map_err(|e| e.to_string())?
+                .map(|o| o.name().to_string())
+                .unwrap_or_default();
+            owners.insert(r.user, name);
+        }
+        out.push(AdminRepoInfo {
+            id: r._id.to_hex(),
+            owner: owners[&r.user].clone(),
+            name: r.name,
+            description: r.description,
+            is_private: r.is_private,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+        });
+    }
+    Ok(out)
+}
+
+pub async fn admin_delete_repo(db: &Database, requester: ObjectId, id: &str) -> Result<(), String> {
+    require_admin(db, &requester).await?;
+    let repo = resolve_repo_by_id(db, id).await?;
+    remove_repository(db, &repo).await?;
+    tracing::info!("Admin {} deleted repository {}", requester, repo._id);
+    Ok(())
+}
+
+pub async fn admin_transfer_repo(db: &Database, requester: ObjectId, payload: TransferRepoRequest) -> Result<(), String> {
+    require_admin(db, &requester).await?;
+    let repo = resolve_repo_by_id(db, &payload.id).await?;
+    let owner = db
+        .find_owner_by_name(payload.owner.trim())
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "owner not found".to_string())?;
+    move_repository(db, &repo, &owner).await?;
+    tracing::info!("Admin {} transferred repository {} to '{}'", requester, repo._id, owner.name());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repo_names_are_url_safe() {
+        assert!(is_valid_repo_name("gitlit"));
+        assert!(is_valid_repo_name("my_repo-2.0"));
+        for bad in ["", "a/b", "my repo", "repo.git", ".hidden", &"x".repeat(101)] {
+            assert!(!is_valid_repo_name(bad), "{bad:?} should be rejected");
+        }
+    }
+
+    fn user(suspended: bool) -> Owner {
+        Owner::User(crate::db::User {
+            _id: ObjectId::new(),
+            username: "alice".into(),
+            email: "alice@example.com".into(),
+            email_verified: true,
+            password: String::new(),
+            display_name: "alice".into(),
+            avatar_url: None,
+            created_at: DateTime::now(),
+            totp_secret: None,
+            totp_enabled: false,
+            recovery_codes: Vec::new(),
+            admin: false,
+            suspended,
+        })
+    }
+
+    #[test]
+    fn repositories_transfer_to_other_users() {
+        let requester = ObjectId::new();
+        assert!(may_transfer_to(&requester, &user(false), None));
+        assert!(!may_transfer_to(&requester, &user(true), None));
+    }
+
+    #[test]
+    fn last_organization_owner_is_detected() {
+        let org = ObjectId::new();
+        let member = |user: ObjectId, role: OrgRole| crate::db::OrgMember {
+            _id: ObjectId::new(),
+            org,
+            user,
+            role,
+            created_at: DateTime::now(),
+        };
+        let (alice, bob) = (ObjectId::new(), ObjectId::new());
+        let members = vec![member(alice, OrgRole::Owner), member(bob, OrgRole::Member)];
+        assert!(!has_other_owner(&members, &alice));
+        assert!(has_other_owner(&members, &bob));
+
+        let members = vec![member(alice, OrgRole::Owner), member(bob, OrgRole::Owner)];
+        assert!(has_other_owner(&members, &alice));
+    }
+
+    #[test]
+    fn organizations_only_take_repositories_from_owners() {
+        let requester = ObjectId::new();
+        let org = Owner::Org(crate::db::Organization {
+            _id: ObjectId::new(),
+            name: "acme".into(),
+            display_name: "Acme".into(),
+            description: String::new(),
+            avatar_url: None,
+            created_at: DateTime::now(),
+        });
+        assert!(may_transfer_to(&requester, &org, Some(OrgRole::Owner)));
+        assert!(!may_transfer_to(&requester, &org, Some(OrgRole::Member)));
+        assert!(!may_transfer_to(&requester, &org, None));
+    }
+}
@@ -1,12 +1,90 @@
 use std::env;
+use crate::auth_backend::AuthBackend;
 use crate::db::Database;
 use crate::errors::AuthError;
-use crate::db::{User, Token};
+use crate::db::{DeployToken, EmailVerification, Identity, Invite, LoginChallenge, OidcLogin, PasswordReset, Scope, User, Token, TokenKind};
+use crate::mail::{self, Email, Mailer};
+use crate::oidc::{self, Oidc};
+use crate::throttle;
+use crate::totp;
 use uuid::Uuid;
-use bcrypt::{hash, verify, DEFAULT_COST};
+use bcrypt::{hash, DEFAULT_COST};
 use mongodb::bson::{oid::ObjectId, DateTime};
 
-const TOKEN_TTL_SECS: i64 = 24 * 60 * 60; // 24 hours
+const DEFAULT_SESSION_TTL_SECS: i64 = 24 * 60 * 60; // 24 hours
+const DEFAULT_REMEMBER_TTL_SECS: i64 = 30 * 24 * 60 * 60; // 30 days
+const CHALLENGE_TTL_SECS: i64 = 5 * 60; // 5 minutes to enter the 2FA code
+const RESET_TTL_SECS: i64 = 60 * 60; // password reset links are valid for an hour
+const VERIFY_TTL_SECS: i64 = 24 * 60 * 60; // email verification links are valid for a day
+const SSO_TTL_SECS: i64 = 10 * 60; // 10 minutes to complete a single sign-on
+const TOTP_ISSUER: &str = "GitLit";
+/// Makes personal access tokens recognizable, e.g. for secret scanners.
+const PERSONAL_TOKEN_PREFIX: &str = "glt_";
+const DEPLOY_TOKEN_PREFIX: &str = "gld_";
+const TOKEN_PREFIX_LEN: usize = 12;
+/// `last_used_at` is only rewritten when older than this, to avoid a write per request.
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+const INVITE_CODE_PREFIX: &str = "inv_";
+
+/// Top-level paths that can never be used as a user or organization name.
+pub const RESERVED_NAMES: &[&str] = &[
+    "static", "login", "register", "logout", "api", "new", "organizations", "settings",
+    "forgot-password", "reset-password", "verify-email", "admin",
+];
+
+/// Names share the `/{owner}` namespace, so they must be URL-safe and not reserved.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 39
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !name.starts_with('.')
+        && !RESERVED_NAMES.contains(&name.to_ascii_lowercase().as_str())
+}
+
+/// Passwords must be between 8 and 128 characters long.
+pub fn is_valid_password(password: &str) -> bool {
+    let len = password.chars().count();
+    (8..=128).contains(&len)
+}
+
+/// Basic shape check: something before a single `@` and a dotted domain after it.
+pub fn is_valid_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !domain.contains('@')
+                && email.len() <= 254
+                && !email.chars().any(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+/// With `REQUIRE_VERIFIED_EMAIL=true`, unverified accounts may not push or create repositories.
+pub fn email_verification_pending(user: &User) -> bool {
+    !user.email_verified && env::var("REQUIRE_VERIFIED_EMAIL").unwrap_or_else(|_| "false".to_string()) == "true"
+}
+
+pub fn hash_password(password: &str) -> Result<String, AuthError> {
+    hash(password, DEFAULT_COST).map_err(|e| AuthError::Internal(e.to_string()))
+}
+
+/// Tokens are stored only as a SHA-256 hex digest.
+pub fn hash_token(value: &str) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Non-secret leading part of a token used to narrow the lookup.
+pub fn token_prefix(value: &str) -> String {
+    value.chars().take(TOKEN_PREFIX_LEN).collect()
+}
 
 fn now_millis() -> i64 {
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -20,16 +98,171 @@ fn dt_from_millis(ms: i64) -> DateTime {
     DateTime::from_millis(ms)
 }
 
-pub async fn register(db: &Database, username: String, email: String, password: String) -> Result<(), AuthError> {
-    if env::var("ALLOW_REGISTER").unwrap_or_else(|_| "false".to_string()) != "true" {
+/// Lifetime of a login session, from `SESSION_TTL_SECS` or, for "remember me"
+/// logins, `REMEMBER_TTL_SECS`.
+pub fn session_ttl_secs(remember: bool) -> i64 {
+    let (var, default) = if remember {
+        ("REMEMBER_TTL_SECS", DEFAULT_REMEMBER_TTL_SECS)
+    } else {
+        ("SESSION_TTL_SECS", DEFAULT_SESSION_TTL_SECS)
+    };
+    env::var(var)
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(default)
+}
+
+/// Who may create an account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistrationMode {
+    Open,
+    /// Only with a valid invite code.
+    Invite,
+    Closed,
+}
+
+/// From `REGISTRATION_MODE` (`open`, `invite` or `closed`); when unset, `ALLOW_REGISTER=true`
+/// means open and anything else closed.
+pub fn registration_mode() -> RegistrationMode {
+    match env::var("REGISTRATION_MODE").unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+        "open" => RegistrationMode::Open,
+        "invite" => RegistrationMode::Invite,
+        "closed" => RegistrationMode::Closed,
+        _ if env::var("ALLOW_REGISTER").unwrap_or_else(|_| "false".to_string()) == "true" => RegistrationMode::Open,
+        _ => RegistrationMode::Closed,
+    }
+}
+
+/// Whether the user administers the instance: flagged as admin or listed in
+/// `ADMIN_USERS` (comma-separated usernames). Suspended accounts never are.
+pub fn is_admin(user: &User) -> bool {
+    if user.suspended {
+        return false;
+    }
+    user.admin
+        || env::var("ADMIN_USERS")
+            .unwrap_or_default()
+            .split(',')
+            .any(|name| name.trim().eq_ignore_ascii_case(&user.username))
+}
+
+/// Admins may always create invites; other users only with `INVITES_FROM_USERS=true`.
+pub fn can_invite(user: &User) -> bool {
+    is_admin(user) || env::var("INVITES_FROM_USERS").unwrap_or_default() == "true"
+}
+
+/// Creates an invite valid for `max_uses` registrations; returns it with the code,
+/// which is not retrievable later.
+pub async fn create_invite(
+    db: &Database,
+    creator: ObjectId,
+    note: Option<String>,
+    max_uses: u32,
+    expires_in_days: Option<u32>,
+) -> Result<(Invite, String), AuthError> {
+    let now = now_millis();
+    let code = format!("{}{}", INVITE_CODE_PREFIX, Uuid::new_v4().simple());
+    let invite = Invite {
+        _id: ObjectId::new(),
+        code_hash: hash_token(&code),
+        created_by: creator,
+        note,
+        max_uses: max_uses.min(i32::MAX as u32) as i32,
+        uses: 0,
+        used_by: Vec::new(),
+        created_at: dt_from_millis(now),
+        expires_at: expires_in_days.map(|d| dt_from_millis(now + d as i64 * 24 * 60 * 60 * 1000)),
+    };
+    db.create_invite(invite.clone())
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    Ok((invite, code))
+}
+
+/// Creates an account; in invite mode `invite` must hold a valid code, which is used up
+/// only once the account exists.
+pub async fn register(
+    db: &Database,
+    mailer: &dyn Mailer,
+    username: String,
+    email: String,
+    password: String,
+    invite: Option<&str>,
+) -> Result<(), AuthError> {
+    let mode = registration_mode();
+    if mode == RegistrationMode::Closed {
         return Err(AuthError::RegistrationDisabled);
     }
-    let pw_len = password.chars().count();
-    if pw_len < 8 || pw_len > 128 {
+    let invite = match invite.map(str::trim).filter(|c| !c.is_empty()) {
+        Some(code) => Some(code),
+        None if mode == RegistrationMode::Invite => return Err(AuthError::InvalidInvite),
+        None => None,
+    };
+    let email = check_new_account(db, &username, &email, &password).await?;
+    let password_hash = hash_password(&password)?;
+
+    let now = now_millis();
+
+    let user_id = ObjectId::new();
+    // in open mode an invite is not needed, so it is left untouched
+    let claimed = match invite {
+        Some(code) if mode == RegistrationMode::Invite => {
+            match db.claim_invite(&hash_token(code), &user_id).await {
+                Ok(Some(inv)) => Some(inv),
+                Ok(None) => return Err(AuthError::InvalidInvite),
+                Err(e) => return Err(AuthError::Internal(e.to_string())),
+            }
+        }
+        _ => None,
+    };
+
+    let user = User {
+        _id: user_id,
+        username: username.clone(),
+        email: email.clone(),
+        email_verified: false,
+        password: password_hash,
+        display_name: username.clone(),
+        avatar_url: None,
+        created_at: dt_from_millis(now),
+        totp_secret: None,
+        totp_enabled: false,
+        recovery_codes: Vec::new(),
+        admin: false,
+        suspended: false,
+    };
+
+    if let Err(e) = db.create_user(user.clone()).await {
+        if let Some(inv) = &claimed {
+            let _ = db.release_invite(&inv._id, &user_id).await;
+        }
+        return Err(AuthError::Internal(e.to_string()));
+    }
+    // a former name of someone else now belongs to the new account
+    db.delete_name_redirect(&username)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+
+    send_email_verification(db, mailer, &user).await
+}
+
+/// Checks the name, address and password of a new account and that neither the name
+/// nor the address is taken; returns the trimmed address.
+async fn check_new_account(db: &Database, username: &str, email: &str, password: &str) -> Result<String, AuthError> {
+    if !is_valid_password(password) {
         return Err(AuthError::InvalidCredentials);
     }
 
-    match db.find_user_by_login(&username).await {
+    if !is_valid_name(username) {
+        return Err(AuthError::InvalidCredentials);
+    }
+    let email = email.trim().to_string();
+    if !is_valid_email(&email) {
+        return Err(AuthError::InvalidCredentials);
+    }
+
+    match db.find_owner_by_name(username).await {
         Ok(Some(_)) => return Err(AuthError::InvalidCredentials),
         Ok(None) => {},
         Err(e) => return Err(AuthError::Internal(e.to_string())),
@@ -39,52 +272,165 @@ pub async fn register(db: &Database, username: String, email: String, password:
         Ok(None) => {},
         Err(e) => return Err(AuthError::Internal(e.to_string())),
     }
+    Ok(email)
+}
 
-    let password_hash = hash(password, DEFAULT_COST)
-        .map_err(|e| AuthError::Internal(e.to_string()))?;
-
-    let now = now_millis();
-
+/// Creates an account on behalf of an operator: the registration mode does not apply
+/// and the address is taken as verified.
+pub async fn create_account(
+    db: &Database,
+    username: String,
+    email: String,
+    password: String,
+    admin: bool,
+) -> Result<User, AuthError> {
+    let email = check_new_account(db, &username, &email, &password).await?;
     let user = User {
         _id: ObjectId::new(),
         username: username.clone(),
-        email: email.clone(),
-        password: password_hash,
+        email,
+        email_verified: true,
+        password: hash_password(&password)?,
         display_name: username.clone(),
         avatar_url: None,
-        created_at: dt_from_millis(now),
+        created_at: dt_from_millis(now_millis()),
+        totp_secret: None,
+        totp_enabled: false,
+        recovery_codes: Vec::new(),
+        admin,
+        suspended: false,
     };
 
-    db.create_user(user)
+    db.create_user(user.clone())
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    db.delete_name_redirect(&username)
         .await
         .map_err(|e| AuthError::Internal(e.to_string()))?;
+    Ok(user)
+}
 
+/// Emails a link confirming the user's current address; a failed delivery is only logged.
+pub async fn send_email_verification(db: &Database, mailer: &dyn Mailer, user: &User) -> Result<(), AuthError> {
+    let now = now_millis();
+    let value = Uuid::new_v4().simple().to_string();
+    let verification = EmailVerification {
+        _id: ObjectId::new(),
+        user: user._id,
+        email: user.email.clone(),
+        token_hash: hash_token(&value),
+        created_at: dt_from_millis(now),
+        expires_at: dt_from_millis(now + VERIFY_TTL_SECS * 1000),
+    };
+    db.replace_email_verification(verification)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+
+    let email = Email {
+        to: user.email.clone(),
+        subject: "Verify your GitLit email address".to_string(),
+        body: format!(
+            "Hi {},\n\nPlease confirm this email address by opening the link below. It expires in {} hours.\n\n{}/verify-email?token={}\n",
+            user.username,
+            VERIFY_TTL_SECS / 3600,
+            mail::public_url(),
+            value
+        ),
+    };
+    if let Err(e) = mailer.send(&email).await {
+        tracing::error!("Failed to send verification email to user '{}': {}", user.username, e);
+    }
     Ok(())
 }
 
-pub async fn login(db: &Database, login: String, password: String) -> Result<String, AuthError> {
-    let user = db
-        .find_user_by_login(&login)
+/// Confirms an address from a verification link.
+pub async fn verify_email(db: &Database, token: &str) -> Result<(), AuthError> {
+    let verification = db
+        .take_email_verification(&hash_token(token))
         .await
         .map_err(|e| AuthError::Internal(e.to_string()))?
         .ok_or(AuthError::InvalidCredentials)?;
-
-    let ok = verify(password, &user.password)
+    if dt_from_millis(now_millis()) > verification.expires_at {
+        return Err(AuthError::InvalidCredentials);
+    }
+    // the link is void if the address was changed after it was sent
+    let updated = db
+        .set_email_verified(&verification.user, &verification.email)
+        .await
         .map_err(|e| AuthError::Internal(e.to_string()))?;
-    if !ok {
+    if !updated {
         return Err(AuthError::InvalidCredentials);
     }
+    Ok(())
+}
+
+/// Where a login or token request came from, recorded on the issued token.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ClientInfo {
+    pub fn from_request(req: &actix_web::HttpRequest) -> Self {
+        ClientInfo {
+            ip: req.connection_info().realip_remote_addr().map(|s| s.to_string()),
+            user_agent: req
+                .headers()
+                .get(actix_web::http::header::USER_AGENT)
+                .and_then(|h| h.to_str().ok())
+                .map(|s| s.chars().take(256).collect()),
+        }
+    }
+}
+
+/// Outcome of the password step of an interactive login.
+pub enum LoginStep {
+    Token(String),
+    /// The account uses 2FA; the challenge has to be completed with [`finish_login`].
+    SecondFactor(ObjectId),
+}
+
+/// Checks a password with the configured backends under the login throttle. Failures count against the account and
+/// `ip`; callers reset the counter once the login is complete, which for web logins means after any second factor.
+pub async fn verify_password(db: &Database, backend: &dyn AuthBackend, login: &str, password: &str, ip: Option<&str>) -> Result<User, AuthError> {
+    let known = db
+        .find_user_by_login(login)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    let account = throttle::account_key(known.as_ref().map(|u| &u._id), login);
+    throttle::check(db, &account, ip).await?;
 
+    match backend.authenticate(db, login, password).await? {
+        Some(user) if user.suspended => Err(AuthError::Suspended),
+        Some(user) => Ok(user),
+        None => {
+            throttle::record_failure(db, &account, ip).await;
+            Err(AuthError::InvalidCredentials)
+        }
+    }
+}
+
+async fn issue_token(db: &Database, user_id: ObjectId, remember: bool, client: ClientInfo) -> Result<String, AuthError> {
+    throttle::record_success(db, &user_id).await;
     let now = now_millis();
-    let exp = now + TOKEN_TTL_SECS * 1000;
+    let exp = now + session_ttl_secs(remember) * 1000;
 
     let token_value = Uuid::new_v4().to_string();
     let token = Token {
         _id: ObjectId::new(),
-        user: user._id,
-        token: token_value.clone(),
+        user: user_id,
+        prefix: token_prefix(&token_value),
+        token_hash: hash_token(&token_value),
         created_at: dt_from_millis(now),
         expires_at: Some(dt_from_millis(exp)),
+        kind: TokenKind::Session,
+        name: None,
+        scopes: Vec::new(),
+        last_used_at: None,
+        ip: client.ip,
+        user_agent: client.user_agent,
+        remember,
     };
 
     db.create_token(token)
@@ -94,9 +440,564 @@ pub async fn login(db: &Database, login: String, password: String) -> Result<Str
     Ok(token_value)
 }
 
+/// Accepts either a current TOTP code or an unused recovery code, which is then spent.
+/// Wrong codes count as failed logins, so codes cannot be guessed faster than passwords.
+async fn verify_second_factor(db: &Database, user: &User, code: &str, ip: Option<&str>) -> Result<bool, AuthError> {
+    let account = throttle::account_key(Some(&user._id), &user.username);
+    throttle::check(db, &account, ip).await?;
+
+    if let Some(secret) = &user.totp_secret {
+        if totp::verify(secret, code) {
+            return Ok(true);
+        }
+    }
+    let spent = db
+        .consume_recovery_code(&user._id, &totp::hash_recovery_code(code))
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    if !spent {
+        throttle::record_failure(db, &account, ip).await;
+    }
+    Ok(spent)
+}
+
+/// Single-request login used by the API; `otp` is required for accounts with 2FA.
+pub async fn login(
+    db: &Database,
+    backend: &dyn AuthBackend,
+    login: String,
+    password: String,
+    otp: Option<String>,
+    client: ClientInfo,
+) -> Result<String, AuthError> {
+    let user = verify_password(db, backend, &login, &password, client.ip.as_deref()).await?;
+
+    if user.totp_enabled {
+        let code = otp.ok_or(AuthError::OtpRequired)?;
+        if !verify_second_factor(db, &user, &code, client.ip.as_deref()).await? {
+            return Err(AuthError::InvalidCredentials);
+        }
+    }
+
+    issue_token(db, user._id, false, client).await
+}
+
+/// Password step of the web login; 2FA accounts get a short-lived challenge instead of a token.
+pub async fn begin_login(
+    db: &Database,
+    backend: &dyn AuthBackend,
+    login: String,
+    password: String,
+    remember: bool,
+    client: ClientInfo,
+) -> Result<LoginStep, AuthError> {
+    let user = verify_password(db, backend, &login, &password, client.ip.as_deref()).await?;
+    start_login(db, &user, remember, client).await
+}
+
+/// Issues a session for an authenticated user, or a 2FA challenge when the account has it enabled.
+async fn start_login(db: &Database, user: &User, remember: bool, client: ClientInfo) -> Result<LoginStep, AuthError> {
+    if user.suspended {
+        return Err(AuthError::Suspended);
+    }
+    if !user.totp_enabled {
+        return issue_token(db, user._id, remember, client).await.map(LoginStep::Token);
+    }
+
+    let now = now_millis();
+    let challenge = LoginChallenge {
+        _id: ObjectId::new(),
+        user: user._id,
+        created_at: dt_from_millis(now),
+        expires_at: dt_from_millis(now + CHALLENGE_TTL_SECS * 1000),
+        remember,
+    };
+    db.create_login_challenge(challenge.clone())
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    Ok(LoginStep::SecondFactor(challenge._id))
+}
+
+/// Second step of the web login: checks the code against the challenge's user.
+/// Returns the token and whether it is a "remember me" session.
+pub async fn finish_login(db: &Database, challenge_id: &str, code: &str, client: ClientInfo) -> Result<(String, bool), AuthError> {
+    let id = ObjectId::parse_str(challenge_id).map_err(|_| AuthError::InvalidCredentials)?;
+    let challenge = db
+        .find_login_challenge(&id)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?
+        .ok_or(AuthError::InvalidCredentials)?;
+    if dt_from_millis(now_millis()) > challenge.expires_at {
+        let _ = db.delete_login_challenge(&id).await;
+        return Err(AuthError::InvalidCredentials);
+    }
+
+    let user = db
+        .find_user_by_id(&challenge.user)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?
+        .ok_or(AuthError::InvalidCredentials)?;
+    if user.suspended {
+        return Err(AuthError::Suspended);
+    }
+    if !verify_second_factor(db, &user, code, client.ip.as_deref()).await? {
+        return Err(AuthError::InvalidCredentials);
+    }
+
+    db.delete_login_challenge(&id)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    let token = issue_token(db, user._id, challenge.remember, client).await?;
+    Ok((token, challenge.remember))
+}
+
+/// Starts a single sign-on with `provider_id`; with `link_user` the identity is linked to that
+/// account instead of signing in. Returns the URL to send the browser to and the state to
+/// keep in a cookie until the callback.
+pub async fn begin_sso(db: &Database, oidc: &Oidc, provider_id: &str, link_user: Option<ObjectId>) -> Result<(String, String), AuthError> {
+    let provider = oidc.provider(provider_id).ok_or(AuthError::Sso("unknown provider".into()))?;
+    let (url, pending) = oidc.begin(provider).await.map_err(|e| {
+        tracing::warn!("OIDC provider '{}': {}", provider.id, e);
+        AuthError::Sso(format!("{} is not available", provider.name))
+    })?;
+
+    let now = now_millis();
+    let login = OidcLogin {
+        _id: ObjectId::new(),
+        state: pending.state.clone(),
+        provider: provider.id.clone(),
+        nonce: pending.nonce,
+        verifier: pending.verifier,
+        link_user,
+        created_at: dt_from_millis(now),
+        expires_at: dt_from_millis(now + SSO_TTL_SECS * 1000),
+    };
+    db.create_oidc_login(login)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    Ok((url, pending.state))
+}
+
+/// Result of a completed single sign-on.
+pub enum SsoOutcome {
+    Login(LoginStep),
+    /// The identity was linked to the account that started the sign-on.
+    Linked,
+}
+
+/// Completes a single sign-on. The identity is matched to an account by an earlier link,
+/// then by an email address both sides have verified; otherwise an account is created
+/// when registration is open.
+pub async fn finish_sso(
+    db: &Database,
+    mailer: &dyn Mailer,
+    oidc: &Oidc,
+    provider_id: &str,
+    state: &str,
+    code: &str,
+    client: ClientInfo,
+) -> Result<SsoOutcome, AuthError> {
+    let pending = db
+        .take_oidc_login(state)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?
+        .filter(|p| p.provider == provider_id && p.expires_at > dt_from_millis(now_millis()))
+        .ok_or(AuthError::Sso("the sign-in expired, please try again".into()))?;
+    let provider = oidc.provider(provider_id).ok_or(AuthError::Sso("unknown provider".into()))?;
+    let claims = oidc.finish(provider, code, &pending.nonce, &pending.verifier).await.map_err(|e| {
+        tracing::warn!("OIDC provider '{}': {}", provider.id, e);
+        AuthError::Sso(format!("could not verify the sign-in with {}", provider.name))
+    })?;
+
+    let existing = db
+        .find_identity(&provider.id, &claims.subject)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+
+    if let Some(user_id) = pending.link_user {
+        return match existing {
+            Some(identity) if identity.user == user_id => Ok(SsoOutcome::Linked),
+            Some(_) => Err(AuthError::Sso(format!("this {} account is linked to another user", provider.name))),
+            None => {
+                link_identity(db, user_id, &provider.id, &claims).await?;
+                Ok(SsoOutcome::Linked)
+            }
+        };
+    }
+
+    let user = match existing {
+        Some(identity) => {
+            let _ = db.touch_identity(&identity._id, claims.email.as_deref()).await;
+            db.find_user_by_id(&identity.user)
+                .await
+                .map_err(|e| AuthError::Internal(e.to_string()))?
+                .ok_or(AuthError::Sso("the linked account no longer exists".into()))?
+        }
+        None => {
+            let user = match sso_user_by_email(db, &claims).await? {
+                Some(u) => u,
+                None => provision_sso_user(db, mailer, &provider.name, &claims).await?,
+            };
+            link_identity(db, user._id, &provider.id, &claims).await?;
+            user
+        }
+    };
+    start_login(db, &user, false, client).await.map(SsoOutcome::Login)
+}
+
+async fn link_identity(db: &Database, user_id: ObjectId, provider: &str, claims: &oidc::Claims) -> Result<(), AuthError> {
+    let identity = Identity {
+        _id: ObjectId::new(),
+        user: user_id,
+        provider: provider.to_string(),
+        subject: claims.subject.clone(),
+        email: claims.email.clone(),
+        created_at: dt_from_millis(now_millis()),
+        last_used_at: None,
+    };
+    db.create_identity(identity)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    Ok(())
+}
+
+/// The account with the identity's email address, when both sides have verified it.
+async fn sso_user_by_email(db: &Database, claims: &oidc::Claims) -> Result<Option<User>, AuthError> {
+    let email = match (&claims.email, claims.email_verified) {
+        (Some(e), true) => e,
+        _ => return Ok(None),
+    };
+    let user = db
+        .find_user_by_login(email)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    match user {
+        Some(u) if u.email == *email && u.email_verified => Ok(Some(u)),
+        Some(_) => Err(AuthError::Sso(
+            "an account with this email address exists; sign in with your password and link it in your account settings".into(),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Creates an account for a new identity when registration is open. The account gets a
+/// random password; the user can set one with a password reset.
+async fn provision_sso_user(db: &Database, mailer: &dyn Mailer, provider_name: &str, claims: &oidc::Claims) -> Result<User, AuthError> {
+    if registration_mode() != RegistrationMode::Open {
+        return Err(AuthError::Sso(format!("no account is linked to this {} account", provider_name)));
+    }
+    let email = claims
+        .email
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| is_valid_email(e))
+        .ok_or_else(|| AuthError::Sso(format!("{} did not share an email address", provider_name)))?;
+    if db
+        .find_user_by_login(email)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?
+        .is_some()
+    {
+        return Err(AuthError::Sso(
+            "an account with this email address exists; sign in with your password and link it in your account settings".into(),
+        ));
+    }
+
+    let username = sso_username(db, claims, email).await?;
+    let user = User {
+        _id: ObjectId::new(),
+        username: username.clone(),
+        email: email.to_string(),
+        email_verified: claims.email_verified,
+        password: hash_password(&Uuid::new_v4().simple().to_string())?,
+        display_name: claims.name.clone().filter(|n| !n.trim().is_empty()).unwrap_or_else(|| username.clone()),
+        avatar_url: None,
+        created_at: dt_from_millis(now_millis()),
+        totp_secret: None,
+        totp_enabled: false,
+        recovery_codes: Vec::new(),
+        admin: false,
+        suspended: false,
+    };
+    db.create_user(user.clone())
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    db.delete_name_redirect(&username)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    tracing::info!("Created account '{}' for a new {} identity", username, provider_name);
+
+    if !user.email_verified {
+        send_email_verification(db, mailer, &user).await?;
+    }
+    Ok(user)
+}
+
+/// A free username based on the identity's preferred username or email address.
+async fn sso_username(db: &Database, claims: &oidc::Claims, email: &str) -> Result<String, AuthError> {
+    let wanted = claims
+        .preferred_username
+        .as_deref()
+        .unwrap_or_else(|| email.split('@').next().unwrap_or_default());
+    let mut base: String = wanted
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '-' })
+        .collect::<String>()
+        .trim_start_matches('.')
+        .chars()
+        .take(32)
+        .collect();
+    if base.is_empty() {
+        base = "user".to_string();
+    }
+
+    for n in 0..20 {
+        let candidate = if n == 0 { base.clone() } else { format!("{}-{}", base, rand::random::<u16>() % 10000) };
+        if !is_valid_name(&candidate) {
+            continue;
+        }
+        let taken = db
+            .find_owner_by_name(&candidate)
+            .await
+            .map_err(|e| AuthError::Internal(e.to_string()))?
+            .is_some();
+        if !taken {
+            return Ok(candidate);
+        }
+    }
+    Err(AuthError::Sso("could not find a free username".into()))
+}
+
+/// The local account for a directory entry, matched through its identity, then by
+/// verified email address; created on first login otherwise. Name and email are
+/// refreshed from the directory every time. `None` when the entry conflicts with an
+/// account that is not linked to it.
+pub async fn sync_directory_account(db: &Database, provider: &str, entry: &oidc::Claims) -> Result<Option<User>, AuthError> {
+    let email = entry.email.as_deref().filter(|e| is_valid_email(e));
+    let existing = db
+        .find_identity(provider, &entry.subject)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+
+    if let Some(identity) = existing {
+        let _ = db.touch_identity(&identity._id, email).await;
+        let user = match db
+            .find_user_by_id(&identity.user)
+            .await
+            .map_err(|e| AuthError::Internal(e.to_string()))?
+        {
+            Some(u) => u,
+            None => return Ok(None),
+        };
+        let display_name = entry.name.clone().unwrap_or_else(|| user.display_name.clone());
+        let email = match email {
+            // the address may meanwhile belong to another account
+            Some(e) if e != user.email && !email_taken(db, e).await? => e.to_string(),
+            _ => user.email.clone(),
+        };
+        if email != user.email || display_name != user.display_name {
+            db.sync_profile(&user._id, &email, &display_name)
+                .await
+                .map_err(|e| AuthError::Internal(e.to_string()))?;
+            return db.find_user_by_id(&user._id).await.map_err(|e| AuthError::Internal(e.to_string()));
+        }
+        return Ok(Some(user));
+    }
+
+    let email = match email {
+        Some(e) => e,
+        None => {
+            tracing::warn!("Directory entry '{}' has no valid email address", entry.subject);
+            return Ok(None);
+        }
+    };
+    let user = match db
+        .find_user_by_login(email)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?
+    {
+        Some(u) if u.email == email && u.email_verified => u,
+        Some(u) => {
+            tracing::warn!("Directory entry '{}' conflicts with account '{}'", entry.subject, u.username);
+            return Ok(None);
+        }
+        None => {
+            let username = sso_username(db, entry, email).await?;
+            let user = User {
+                _id: ObjectId::new(),
+                username: username.clone(),
+                email: email.to_string(),
+                email_verified: true,
+                password: hash_password(&Uuid::new_v4().simple().to_string())?,
+                display_name: entry.name.clone().unwrap_or_else(|| username.clone()),
+                avatar_url: None,
+                created_at: dt_from_millis(now_millis()),
+                totp_secret: None,
+                totp_enabled: false,
+                recovery_codes: Vec::new(),
+                admin: false,
+                suspended: false,
+            };
+            db.create_user(user.clone())
+                .await
+                .map_err(|e| AuthError::Internal(e.to_string()))?;
+            db.delete_name_redirect(&username)
+                .await
+                .map_err(|e| AuthError::Internal(e.to_string()))?;
+            tracing::info!("Created account '{}' for directory entry '{}'", username, entry.subject);
+            user
+        }
+    };
+    link_identity(db, user._id, provider, entry).await?;
+    Ok(Some(user))
+}
+
+async fn email_taken(db: &Database, email: &str) -> Result<bool, AuthError> {
+    Ok(db
+        .find_user_by_login(email)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?
+        .is_some())
+}
+
+/// Starts TOTP enrollment and returns `(secret, otpauth_uri)`.
+pub async fn totp_setup(db: &Database, user_id: ObjectId) -> Result<(String, String), AuthError> {
+    let user = db
+        .find_user_by_id(&user_id)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?
+        .ok_or(AuthError::InvalidCredentials)?;
+    if user.totp_enabled {
+        return Err(AuthError::OtpAlreadyEnabled);
+    }
+
+    let secret = totp::generate_secret();
+    db.set_pending_totp(&user_id, &secret)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    let uri = totp::otpauth_uri(TOTP_ISSUER, &user.username, &secret);
+    Ok((secret, uri))
+}
+
+/// Confirms enrollment with a code from the app and returns the plain recovery codes.
+pub async fn totp_enable(db: &Database, user_id: ObjectId, code: &str) -> Result<Vec<String>, AuthError> {
+    let user = db
+        .find_user_by_id(&user_id)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?
+        .ok_or(AuthError::InvalidCredentials)?;
+    if user.totp_enabled {
+        return Err(AuthError::OtpAlreadyEnabled);
+    }
+    let secret = user.totp_secret.as_deref().ok_or(AuthError::InvalidCredentials)?;
+    if !totp::verify(secret, code) {
+        return Err(AuthError::InvalidCredentials);
+    }
+
+    let codes = totp::generate_recovery_codes();
+    let hashes = codes.iter().map(|c| totp::hash_recovery_code(c)).collect();
+    db.enable_totp(&user_id, hashes)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    Ok(codes)
+}
+
+/// Turns 2FA off; requires the account password, checked under the login throttle.
+pub async fn totp_disable(db: &Database, backend: &dyn AuthBackend, user_id: ObjectId, password: &str, ip: Option<&str>) -> Result<(), AuthError> {
+    let user = db
+        .find_user_by_id(&user_id)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?
+        .ok_or(AuthError::InvalidCredentials)?;
+    if verify_password(db, backend, &user.username, password, ip).await?._id != user._id {
+        return Err(AuthError::InvalidCredentials);
+    }
+    throttle::record_success(db, &user_id).await;
+    db.disable_totp(&user_id)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))
+}
+
+/// Emails a reset link to the account matching `login`. Unknown accounts are
+/// ignored so the response does not reveal which logins exist.
+pub async fn request_password_reset(db: &Database, mailer: &dyn Mailer, login: &str) -> Result<(), AuthError> {
+    let user = match db
+        .find_user_by_login(login.trim())
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?
+    {
+        Some(u) => u,
+        None => return Ok(()),
+    };
+
+    let now = now_millis();
+    let value = Uuid::new_v4().simple().to_string();
+    let reset = PasswordReset {
+        _id: ObjectId::new(),
+        user: user._id,
+        token_hash: hash_token(&value),
+        created_at: dt_from_millis(now),
+        expires_at: dt_from_millis(now + RESET_TTL_SECS * 1000),
+    };
+    db.replace_password_reset(reset)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+
+    let email = Email {
+        to: user.email.clone(),
+        subject: "Reset your GitLit password".to_string(),
+        body: format!(
+            "Hi {},\n\nUse the link below to choose a new password. It expires in {} minutes and can be used once.\n\n{}/reset-password?token={}\n\nIf you did not ask for this, you can ignore this email.\n",
+            user.username,
+            RESET_TTL_SECS / 60,
+            mail::public_url(),
+            value
+        ),
+    };
+    if let Err(e) = mailer.send(&email).await {
+        tracing::error!("Failed to send password reset email to user '{}': {}", user.username, e);
+    }
+    Ok(())
+}
+
+/// Sets a new password from a reset link, signs the user out everywhere and revokes their access tokens.
+pub async fn reset_password(db: &Database, token: &str, new_password: &str) -> Result<(), AuthError> {
+    if !is_valid_password(new_password) {
+        return Err(AuthError::InvalidPassword);
+    }
+    let reset = db
+        .take_password_reset(&hash_token(token))
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?
+        .ok_or(AuthError::InvalidCredentials)?;
+    if dt_from_millis(now_millis()) > reset.expires_at {
+        return Err(AuthError::InvalidCredentials);
+    }
+    set_password(db, &reset.user, new_password).await
+}
+
+/// Replaces a user's password, signs them out of every session and revokes their
+/// personal access tokens, which may have been created with the old password.
+pub async fn set_password(db: &Database, user_id: &ObjectId, new_password: &str) -> Result<(), AuthError> {
+    if !is_valid_password(new_password) {
+        return Err(AuthError::InvalidPassword);
+    }
+    let hash = hash_password(new_password)?;
+    db.update_password(user_id, &hash)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    db.delete_user_sessions(user_id, None)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    db.delete_personal_tokens(user_id)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    Ok(())
+}
+
 pub async fn logout(db: &Database, token: String) -> Result<(), AuthError> {
     let deleted = db
-        .delete_token(&token)
+        .delete_token(&token_prefix(&token), &hash_token(&token))
         .await
         .map_err(|e| AuthError::Internal(e.to_string()))?;
 
@@ -107,20 +1008,213 @@ pub async fn logout(db: &Database, token: String) -> Result<(), AuthError> {
     Ok(())
 }
 
-pub async fn auth(db: &Database, token: String) -> Result<ObjectId, AuthError> {
+/// Resolves a presented token to its record, enforcing expiry and tracking last use.
+pub async fn authenticate_token(db: &Database, token: &str) -> Result<Token, AuthError> {
     let t = db
-        .find_token(&token)
+        .find_token(&token_prefix(token), &hash_token(token))
         .await
         .map_err(|e| AuthError::Internal(e.to_string()))?
         .ok_or(AuthError::InvalidCredentials)?;
 
+    let now = now_millis();
     if let Some(exp) = t.expires_at {
-        let now = now_millis();
         if dt_from_millis(now) > exp {
-            let _ = db.delete_token(&token).await;
+            let _ = db.delete_token(&t.prefix, &t.token_hash).await;
             return Err(AuthError::InvalidCredentials);
         }
     }
 
-    Ok(t.user)
-}
\ No newline at end of file
+    let stale = t
+        .last_used_at
+        .map(|used| now - used.timestamp_millis() > LAST_USED_RESOLUTION_SECS * 1000)
+        .unwrap_or(true);
+    if stale {
+        let _ = db.touch_token(&t._id).await;
+    }
+    Ok(t)
+}
+
+/// Accepts `t` only if it is of `kind`, so a token cannot be used where another kind is expected.
+fn require_kind(t: Token, kind: TokenKind) -> Result<Token, AuthError> {
+    if t.kind == kind {
+        Ok(t)
+    } else {
+        Err(AuthError::InvalidCredentials)
+    }
+}
+
+/// Resolves the token of a browser session. Personal access tokens are rejected, so
+/// one put into the cookie cannot stand in for a login.
+pub async fn authenticate_session(db: &Database, token: &str) -> Result<Token, AuthError> {
+    require_kind(authenticate_token(db, token).await?, TokenKind::Session)
+}
+
+/// Resolves a personal access token given in place of a password, e.g. to git.
+/// Session tokens are rejected, so a stolen cookie does not grant git access.
+pub async fn authenticate_personal_token(db: &Database, token: &str) -> Result<Token, AuthError> {
+    require_kind(authenticate_token(db, token).await?, TokenKind::Personal)
+}
+
+/// Whether `value` looks like a deploy token rather than a password or personal token.
+pub fn is_deploy_token(value: &str) -> bool {
+    value.starts_with(DEPLOY_TOKEN_PREFIX)
+}
+
+/// Creates a deploy token for one repository and returns it with its secret value,
+/// which is not recoverable afterwards. `expires_in_days` of `None` never expires.
+pub async fn create_deploy_token(
+    db: &Database,
+    repo_id: ObjectId,
+    created_by: ObjectId,
+    name: String,
+    write: bool,
+    expires_in_days: Option<u32>,
+) -> Result<(DeployToken, String), AuthError> {
+    let now = now_millis();
+    let value = format!("{}{}", DEPLOY_TOKEN_PREFIX, Uuid::new_v4().simple());
+    let token = DeployToken {
+        _id: ObjectId::new(),
+        repo: repo_id,
+        name,
+        prefix: token_prefix(&value),
+        token_hash: hash_token(&value),
+        write,
+        created_by,
+        created_at: dt_from_millis(now),
+        expires_at: expires_in_days.map(|d| dt_from_millis(now + d as i64 * 24 * 60 * 60 * 1000)),
+        last_used_at: None,
+    };
+
+    db.create_deploy_token(token.clone())
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    Ok((token, value))
+}
+
+pub async fn authenticate_deploy_token(db: &Database, token: &str) -> Result<DeployToken, AuthError> {
+    let t = db
+        .find_deploy_token(&token_prefix(token), &hash_token(token))
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?
+        .ok_or(AuthError::InvalidCredentials)?;
+
+    let now = now_millis();
+    if t.expires_at.is_some_and(|exp| dt_from_millis(now) > exp) {
+        return Err(AuthError::InvalidCredentials);
+    }
+    let stale = t
+        .last_used_at
+        .map(|used| now - used.timestamp_millis() > LAST_USED_RESOLUTION_SECS * 1000)
+        .unwrap_or(true);
+    if stale {
+        let _ = db.touch_deploy_token(&t._id).await;
+    }
+    Ok(t)
+}
+
+/// Slides a browser session forward once less than half of its lifetime is left,
+/// so active users stay signed in. Returns the token if its expiry was moved.
+/// Personal access tokens and API logins keep their fixed expiry.
+pub async fn renew_session(db: &Database, token: &str) -> Result<Option<Token>, AuthError> {
+    let mut t = authenticate_token(db, token).await?;
+    if t.kind != TokenKind::Session {
+        return Ok(None);
+    }
+    let exp = match t.expires_at {
+        Some(exp) => exp,
+        None => return Ok(None),
+    };
+
+    let ttl_ms = session_ttl_secs(t.remember) * 1000;
+    let now = now_millis();
+    if exp.timestamp_millis() - now > ttl_ms / 2 {
+        return Ok(None);
+    }
+
+    let new_exp = dt_from_millis(now + ttl_ms);
+    db.set_token_expiry(&t._id, new_exp)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    t.expires_at = Some(new_exp);
+    Ok(Some(t))
+}
+
+/// The user behind a browser session token.
+pub async fn auth(db: &Database, token: String) -> Result<ObjectId, AuthError> {
+    authenticate_session(db, &token).await.map(|t| t.user)
+}
+
+/// Creates a named personal access token and returns it with its secret value,
+/// which is not recoverable afterwards. `expires_in_days` of `None` never expires.
+pub async fn create_personal_token(
+    db: &Database,
+    user_id: ObjectId,
+    name: String,
+    scopes: Vec<Scope>,
+    expires_in_days: Option<u32>,
+    client: ClientInfo,
+) -> Result<(Token, String), AuthError> {
+    let now = now_millis();
+    let value = format!("{}{}", PERSONAL_TOKEN_PREFIX, Uuid::new_v4().simple());
+    let token = Token {
+        _id: ObjectId::new(),
+        user: user_id,
+        prefix: token_prefix(&value),
+        token_hash: hash_token(&value),
+        created_at: dt_from_millis(now),
+        expires_at: expires_in_days.map(|d| dt_from_millis(now + d as i64 * 24 * 60 * 60 * 1000)),
+        kind: TokenKind::Personal,
+        name: Some(name),
+        scopes,
+        last_used_at: None,
+        ip: client.ip,
+        user_agent: client.user_agent,
+        remember: false,
+    };
+
+    db.create_token(token.clone())
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+    Ok((token, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(kind: TokenKind) -> Token {
+        Token {
+            _id: ObjectId::new(),
+            user: ObjectId::new(),
+            prefix: String::new(),
+            token_hash: String::new(),
+            created_at: DateTime::now(),
+            expires_at: None,
+            kind,
+            name: None,
+            scopes: vec![Scope::RepoRead],
+            last_used_at: None,
+            ip: None,
+            user_agent: None,
+            remember: false,
+        }
+    }
+
+    #[test]
+    fn personal_token_is_not_a_browser_session() {
+        assert!(require_kind(token(TokenKind::Session), TokenKind::Session).is_ok());
+        assert!(matches!(
+            require_kind(token(TokenKind::Personal), TokenKind::Session),
+            Err(AuthError::InvalidCredentials)
+        ));
+    }
+
+    #[test]
+    fn session_token_is_not_a_git_password() {
+        assert!(require_kind(token(TokenKind::Personal), TokenKind::Personal).is_ok());
+        assert!(matches!(
+            require_kind(token(TokenKind::Session), TokenKind::Personal),
+            Err(AuthError::InvalidCredentials)
+        ));
+    }
+}
@@ -0,0 +1,273 @@
+use std::env;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bcrypt::verify;
+use ldap3::{ldap_escape, Ldap as LdapClient, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use tracing::warn;
+
+use crate::db::{Database, User};
+use crate::errors::AuthError;
+use crate::oidc::Claims;
+
+/// Provider name of the identities that tie local accounts to directory entries.
+pub const LDAP_PROVIDER: &str = "ldap";
+const LDAP_TIMEOUT_SECS: u64 = 10;
+
+/// Checks a login and password and returns the local account they belong to.
+#[async_trait]
+pub trait AuthBackend: Send + Sync + fmt::Debug {
+    /// `Ok(None)` when this backend rejects the credentials.
+    async fn authenticate(&self, db: &Database, login: &str, password: &str) -> Result<Option<User>, AuthError>;
+}
+
+/// Builds the backends listed in `AUTH_BACKENDS` (comma-separated, tried in order):
+/// `local` (the default) and `ldap`.
+pub fn from_env() -> Arc<dyn AuthBackend> {
+    let names: Vec<String> = env::var("AUTH_BACKENDS")
+        .unwrap_or_else(|_| "local".to_string())
+        .split(',')
+        .map(|n| n.trim().to_ascii_lowercase())
+        .filter(|n| !n.is_empty())
+        .collect();
+    let directory = names.iter().any(|n| n == LDAP_PROVIDER);
+
+    let backends: Vec<Box<dyn AuthBackend>> = names
+        .iter()
+        .map(|name| -> Box<dyn AuthBackend> {
+            match name.as_str() {
+                "local" => Box::new(Local { skip_directory_accounts: directory }),
+                "ldap" => Box::new(Ldap::from_env().expect("invalid LDAP configuration")),
+                other => panic!("unknown auth backend '{}'", other),
+            }
+        })
+        .collect();
+    if backends.is_empty() {
+        panic!("AUTH_BACKENDS lists no backends");
+    }
+    Arc::new(Chain(backends))
+}
+
+/// Tries each backend in turn; the first one that accepts the credentials wins.
+#[derive(Debug)]
+struct Chain(Vec<Box<dyn AuthBackend>>);
+
+#[async_trait]
+impl AuthBackend for Chain {
+    async fn authenticate(&self, db: &Database, login: &str, password: &str) -> Result<Option<User>, AuthError> {
+        let mut failure = None;
+        for backend in &self.0 {
+            match backend.authenticate(db, login, password).await {
+                Ok(Some(user)) => return Ok(Some(user)),
+                Ok(None) => {}
+                Err(e) => {
+                    warn!("Auth backend {:?} failed: {}", backend, e);
+                    failure = Some(e);
+                }
+            }
+        }
+        // an unreachable directory should not read as a wrong password
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The bcrypt hash stored with the account.
+#[derive(Debug)]
+pub struct Local {
+    /// While LDAP is enabled, accounts created from the directory only sign in through it,
+    /// so removing someone from the directory or its group also locks them out here.
+    skip_directory_accounts: bool,
+}
+
+#[async_trait]
+impl AuthBackend for Local {
+    async fn authenticate(&self, db: &Database, login: &str, password: &str) -> Result<Option<User>, AuthError> {
+        let user = match db
+            .find_user_by_login(login)
+            .await
+            .map_err(|e| AuthError::Internal(e.to_string()))?
+        {
+            Some(u) => u,
+            None => return Ok(None),
+        };
+        if self.skip_directory_accounts
+            && db
+                .list_identities(&user._id)
+                .await
+                .map_err(|e| AuthError::Internal(e.to_string()))?
+                .iter()
+                .any(|i| i.provider == LDAP_PROVIDER)
+        {
+            return Ok(None);
+        }
+        let ok = verify(password, &user.password).map_err(|e| AuthError::Internal(e.to_string()))?;
+        Ok(ok.then_some(user))
+    }
+}
+
+/// Binds as the user against an LDAP directory (`LDAP_URL`). The entry is found with a
+/// search as `LDAP_BIND_DN`, or anonymously, below `LDAP_BASE_DN` using `LDAP_USER_FILTER`
+/// (`{login}` is replaced by the escaped login) and the optional `LDAP_GROUP_FILTER`.
+/// `LDAP_ATTR_USERNAME`, `LDAP_ATTR_EMAIL` and `LDAP_ATTR_NAME` map the entry to the account.
+pub struct Ldap {
+    url: String,
+    starttls: bool,
+    bind_dn: Option<String>,
+    bind_password: String,
+    base_dn: String,
+    user_filter: String,
+    group_filter: Option<String>,
+    attr_username: String,
+    attr_email: String,
+    attr_name: String,
+}
+
+impl fmt::Debug for Ldap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ldap").field("url", &self.url).field("base_dn", &self.base_dn).finish()
+    }
+}
+
+impl Ldap {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |name: &str, default: &str| env::var(name).ok().filter(|v| !v.trim().is_empty()).unwrap_or_else(|| default.to_string());
+        let url = env::var("LDAP_URL").map_err(|_| "LDAP_URL is not set".to_string())?;
+        let base_dn = env::var("LDAP_BASE_DN").map_err(|_| "LDAP_BASE_DN is not set".to_string())?;
+        let user_filter = var("LDAP_USER_FILTER", "(|(uid={login})(mail={login}))");
+        if !user_filter.contains("{login}") {
+            return Err("LDAP_USER_FILTER must contain {login}".into());
+        }
+        Ok(Ldap {
+            url,
+            starttls: env::var("LDAP_STARTTLS").unwrap_or_default() == "true",
+            bind_dn: env::var("LDAP_BIND_DN").ok().filter(|d| !d.trim().is_empty()),
+            bind_password: env::var("LDAP_BIND_PASSWORD").unwrap_or_default(),
+            base_dn,
+            user_filter,
+            group_filter: env::var("LDAP_GROUP_FILTER").ok().filter(|f| !f.trim().is_empty()),
+            attr_username: var("LDAP_ATTR_USERNAME", "uid"),
+            attr_email: var("LDAP_ATTR_EMAIL", "mail"),
+            attr_name: var("LDAP_ATTR_NAME", "cn"),
+        })
+    }
+
+    fn filter(&self, login: &str) -> String {
+        let user = self.user_filter.replace("{login}", &ldap_escape(login));
+        match &self.group_filter {
+            Some(group) => format!("(&{}{})", user, group),
+            None => user,
+        }
+    }
+
+    /// The directory entry for `login` if it is allowed in and `password` binds as it.
+    async fn find_entry(&self, ldap: &mut LdapClient, login: &str, password: &str) -> ldap3::result::Result<Option<SearchEntry>> {
+        match &self.bind_dn {
+            Some(dn) => ldap.simple_bind(dn, &self.bind_password).await?.success()?,
+            None => ldap.simple_bind("", "").await?.success()?,
+        };
+        let attrs = [self.attr_username.as_str(), self.attr_email.as_str(), self.attr_name.as_str()];
+        let (entries, _) = ldap
+            .search(&self.base_dn, Scope::Subtree, &self.filter(login), attrs)
+            .await?
+            .success()?;
+        if entries.len() != 1 {
+            if entries.len() > 1 {
+                warn!("LDAP login '{}' matches {} entries, refusing", login, entries.len());
+            }
+            return Ok(None);
+        }
+        let entry = SearchEntry::construct(entries.into_iter().next().expect("one entry"));
+
+        if ldap.simple_bind(&entry.dn, password).await?.rc != 0 {
+            return Ok(None);
+        }
+        Ok(Some(entry))
+    }
+
+    fn attr(entry: &SearchEntry, name: &str) -> Option<String> {
+        entry
+            .attrs
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .and_then(|(_, v)| v.first())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+}
+
+#[async_trait]
+impl AuthBackend for Ldap {
+    async fn authenticate(&self, db: &Database, login: &str, password: &str) -> Result<Option<User>, AuthError> {
+        // an empty password would be an unauthenticated bind, which most servers accept
+        if login.is_empty() || password.is_empty() {
+            return Ok(None);
+        }
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(Duration::from_secs(LDAP_TIMEOUT_SECS))
+            .set_starttls(self.starttls);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.url)
+            .await
+            .map_err(|e| AuthError::Internal(format!("LDAP connection failed: {}", e)))?;
+        ldap3::drive!(conn);
+
+        let found = tokio::time::timeout(Duration::from_secs(LDAP_TIMEOUT_SECS), self.find_entry(&mut ldap, login, password)).await;
+        let _ = ldap.unbind().await;
+        let entry = match found {
+            Ok(Ok(Some(e))) => e,
+            Ok(Ok(None)) => return Ok(None),
+            Ok(Err(e)) => return Err(AuthError::Internal(format!("LDAP search failed: {}", e))),
+            Err(_) => return Err(AuthError::Internal("LDAP server timed out".into())),
+        };
+
+        let username = match Self::attr(&entry, &self.attr_username) {
+            Some(u) => u,
+            None => {
+                warn!("LDAP entry '{}' has no {} attribute", entry.dn, self.attr_username);
+                return Ok(None);
+            }
+        };
+        let claims = Claims {
+            subject: entry.dn.to_ascii_lowercase(),
+            email: Self::attr(&entry, &self.attr_email),
+            // the directory is run by the administrator, so its addresses are trusted
+            email_verified: true,
+            preferred_username: Some(username),
+            name: Self::attr(&entry, &self.attr_name),
+        };
+        crate::auth::sync_directory_account(db, LDAP_PROVIDER, &claims).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ldap(group_filter: Option<&str>) -> Ldap {
+        Ldap {
+            url: "ldap://localhost".into(),
+            starttls: false,
+            bind_dn: None,
+            bind_password: String::new(),
+            base_dn: "dc=example,dc=com".into(),
+            user_filter: "(uid={login})".into(),
+            group_filter: group_filter.map(String::from),
+            attr_username: "uid".into(),
+            attr_email: "mail".into(),
+            attr_name: "cn".into(),
+        }
+    }
+
+    #[test]
+    fn filter_escapes_login_and_adds_group() {
+        assert_eq!(ldap(None).filter("a*)(uid=*"), "(uid=a\\2a\\29\\28uid=\\2a)");
+        assert_eq!(
+            ldap(Some("(memberOf=cn=git,dc=example,dc=com)")).filter("alice"),
+            "(&(uid=alice)(memberOf=cn=git,dc=example,dc=com))"
+        );
+    }
+}
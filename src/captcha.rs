@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use maud::{html, Markup, PreEscaped};
+use rand::{Rng, RngCore};
+use reqwest::Client;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::models::{CaptchaInfo, CaptchaResponse};
+
+/// Offline challenges must be solved within this many seconds.
+const CHALLENGE_TTL_SECS: i64 = 10 * 60;
+const DEFAULT_POW_DIFFICULTY: u32 = 18;
+
+/// Guards registration against bots.
+#[async_trait]
+pub trait Captcha: Send + Sync {
+    /// Scripts the widget needs in the page head.
+    fn head(&self) -> Markup {
+        html! {}
+    }
+    /// Form fields of the widget; offline providers issue a fresh challenge every time.
+    fn widget(&self) -> Markup;
+    /// What API clients need to solve the captcha.
+    fn info(&self) -> CaptchaInfo;
+    async fn verify(&self, response: &CaptchaResponse, remote_ip: Option<&str>) -> bool;
+}
+
+/// Picks the provider from `CAPTCHA_PROVIDER`: `recaptcha`, `hcaptcha`, `turnstile`,
+/// `pow`, `arithmetic` or `none` (the default). `RECAPTCHA_ENABLED=true` still selects reCAPTCHA.
+pub fn from_env() -> Arc<dyn Captcha> {
+    let provider = match env::var("CAPTCHA_PROVIDER") {
+        Ok(p) if !p.trim().is_empty() => p.trim().to_ascii_lowercase(),
+        _ if env::var("RECAPTCHA_ENABLED").unwrap_or_default() == "true" => "recaptcha".to_string(),
+        _ => "none".to_string(),
+    };
+    match provider.as_str() {
+        "recaptcha" => Arc::new(HostedCaptcha::from_env(Hosted::Recaptcha)),
+        "hcaptcha" => Arc::new(HostedCaptcha::from_env(Hosted::Hcaptcha)),
+        "turnstile" => Arc::new(HostedCaptcha::from_env(Hosted::Turnstile)),
+        "pow" => {
+            let difficulty = env::var("CAPTCHA_POW_DIFFICULTY")
+                .ok()
+                .and_then(|d| d.parse::<u32>().ok())
+                .filter(|d| (1..=32).contains(d))
+                .unwrap_or(DEFAULT_POW_DIFFICULTY);
+            Arc::new(ProofOfWork { signer: Signer::from_env(), difficulty })
+        }
+        "arithmetic" => Arc::new(Arithmetic { signer: Signer::from_env() }),
+        "none" => Arc::new(NoCaptcha),
+        other => panic!("unknown CAPTCHA_PROVIDER '{}'", other),
+    }
+}
+
+/// Accepts every submission.
+pub struct NoCaptcha;
+
+#[async_trait]
+impl Captcha for NoCaptcha {
+    fn widget(&self) -> Markup {
+        html! {}
+    }
+
+    fn info(&self) -> CaptchaInfo {
+        CaptchaInfo { provider: "none".into(), ..Default::default() }
+    }
+
+    async fn verify(&self, _response: &CaptchaResponse, _remote_ip: Option<&str>) -> bool {
+        true
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hosted {
+    Recaptcha,
+    Hcaptcha,
+    Turnstile,
+}
+
+impl Hosted {
+    fn name(self) -> &'static str {
+        match self {
+            Hosted::Recaptcha => "recaptcha",
+            Hosted::Hcaptcha => "hcaptcha",
+            Hosted::Turnstile => "turnstile",
+        }
+    }
+
+    fn script(self) -> &'static str {
+        match self {
+            Hosted::Recaptcha => "https://www.google.com/recaptcha/api.js",
+            Hosted::Hcaptcha => "https://js.hcaptcha.com/1/api.js",
+            Hosted::Turnstile => "https://challenges.cloudflare.com/turnstile/v0/api.js",
+        }
+    }
+
+    fn widget_class(self) -> &'static str {
+        match self {
+            Hosted::Recaptcha => "g-recaptcha",
+            Hosted::Hcaptcha => "h-captcha",
+            Hosted::Turnstile => "cf-turnstile",
+        }
+    }
+
+    fn verify_url(self) -> &'static str {
+        match self {
+            Hosted::Recaptcha => "https://www.google.com/recaptcha/api/siteverify",
+            Hosted::Hcaptcha => "https://api.hcaptcha.com/siteverify",
+            Hosted::Turnstile => "https://challenges.cloudflare.com/turnstile/v0/siteverify",
+        }
+    }
+
+    fn response(self, response: &CaptchaResponse) -> Option<&str> {
+        match self {
+            Hosted::Recaptcha => response.recaptcha.as_deref(),
+            Hosted::Hcaptcha => response.hcaptcha.as_deref(),
+            Hosted::Turnstile => response.turnstile.as_deref(),
+        }
+    }
+}
+
+/// reCAPTCHA, hCaptcha and Turnstile share the widget and `siteverify` protocol.
+/// Keys come from `CAPTCHA_SITE_KEY` and `CAPTCHA_SECRET`, or the older `RECAPTCHA_*` names.
+pub struct HostedCaptcha {
+    kind: Hosted,
+    site_key: String,
+    secret: String,
+    client: Client,
+}
+
+impl HostedCaptcha {
+    pub fn from_env(kind: Hosted) -> Self {
+        let var = |name: &str| {
+            env::var(format!("CAPTCHA_{}", name))
+                .or_else(|_| env::var(format!("RECAPTCHA_{}", name)))
+                .unwrap_or_else(|_| panic!("CAPTCHA_{} must be set for {}", name, kind.name()))
+        };
+        HostedCaptcha { kind, site_key: var("SITE_KEY"), secret: var("SECRET"), client: Client::new() }
+    }
+}
+
+#[async_trait]
+impl Captcha for HostedCaptcha {
+    fn head(&self) -> Markup {
+        html! { script src=(self.kind.script()) async defer {} }
+    }
+
+    fn widget(&self) -> Markup {
+        html! {
+            div class="form-group" {
+                div class=(self.kind.widget_class()) data-sitekey=(self.site_key) {}
+            }
+        }
+    }
+
+    fn info(&self) -> CaptchaInfo {
+        CaptchaInfo {
+            provider: self.kind.name().into(),
+            site_key: Some(self.site_key.clone()),
+            ..Default::default()
+        }
+    }
+
+    async fn verify(&self, response: &CaptchaResponse, remote_ip: Option<&str>) -> bool {
+        let token = match self.kind.response(response) {
+            Some(t) if !t.is_empty() => t,
+            _ => return false,
+        };
+
+        #[derive(serde::Deserialize)]
+        struct VerifyResp {
+            success: bool,
+            #[serde(default, rename = "error-codes")]
+            error_codes: Vec<String>,
+        }
+
+        let mut params = vec![("secret", self.secret.as_str()), ("response", token)];
+        if let Some(ip) = remote_ip {
+            params.push(("remoteip", ip));
+        }
+        let resp = match self.client.post(self.kind.verify_url()).form(&params).send().await {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("{} verification request failed: {}", self.kind.name(), e);
+                return false;
+            }
+        };
+        match resp.json::<VerifyResp>().await {
+            Ok(v) => {
+                if !v.success && !v.error_codes.is_empty() {
+                    warn!("{} rejected a response: {}", self.kind.name(), v.error_codes.join(", "));
+                }
+                v.success
+            }
+            Err(e) => {
+                warn!("{} returned an invalid response: {}", self.kind.name(), e);
+                false
+            }
+        }
+    }
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs() as i64
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Issues stateless `expires.nonce.mac` challenges and accepts each one once.
+/// The key comes from `CAPTCHA_SECRET`; without it a random key is used, so
+/// challenges issued before a restart become invalid.
+struct Signer {
+    key: Vec<u8>,
+    /// MACs of solved challenges with their expiry, to refuse replays.
+    spent: Mutex<HashMap<String, i64>>,
+}
+
+impl Signer {
+    fn from_env() -> Self {
+        let key = match env::var("CAPTCHA_SECRET") {
+            Ok(s) if !s.is_empty() => s.into_bytes(),
+            _ => {
+                let mut key = vec![0u8; 32];
+                rand::thread_rng().fill_bytes(&mut key);
+                key
+            }
+        };
+        Signer { key, spent: Mutex::new(HashMap::new()) }
+    }
+
+    fn mac(&self, kind: &str, expires: i64, nonce: &str, bound: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(format!("{}|{}|{}|{}", kind, expires, nonce, bound).as_bytes());
+        mac
+    }
+
+    /// A challenge of `kind` tied to `bound`, which the solver must reproduce.
+    fn issue(&self, kind: &str, bound: &str) -> String {
+        let expires = now_secs() + CHALLENGE_TTL_SECS;
+        let mut nonce = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let nonce = hex(&nonce);
+        let mac = hex(&self.mac(kind, expires, &nonce, bound).finalize().into_bytes());
+        format!("{}.{}.{}", expires, nonce, mac)
+    }
+
+    /// Whether `challenge` was issued for `kind` and `bound`, has not expired and
+    /// has not been used before; marks it used.
+    fn redeem(&self, kind: &str, challenge: &str, bound: &str) -> bool {
+        let parts: Vec<&str> = challenge.split('.').collect();
+        let (expires, nonce, mac_hex) = match parts.as_slice() {
+            [e, n, m] => match e.parse::<i64>() {
+                Ok(e) => (e, *n, *m),
+                Err(_) => return false,
+            },
+            _ => return false,
+        };
+        let now = now_secs();
+        if expires < now {
+            return false;
+        }
+        let mac_bytes = match data_encoding::HEXLOWER.decode(mac_hex.as_bytes()) {
+            Ok(b) => b,
+            Err(_) => return false,
+        };
+        if self.mac(kind, expires, nonce, bound).verify_slice(&mac_bytes).is_err() {
+            return false;
+        }
+
+        let mut spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+        spent.retain(|_, exp| *exp >= now);
+        spent.insert(mac_hex.to_string(), expires).is_none()
+    }
+}
+
+/// Number of leading zero bits of `SHA-256(challenge:answer)`.
+fn work_bits(challenge: &str, answer: &str) -> u32 {
+    let digest = Sha256::digest(format!("{}:{}", challenge, answer).as_bytes());
+    let mut bits = 0;
+    for byte in digest.iter() {
+        if *byte == 0 {
+            bits += 8;
+            continue;
+        }
+        bits += byte.leading_zeros();
+        break;
+    }
+    bits
+}
+
+/// Solves the challenge in the browser: finds a nonce whose SHA-256 with the
+/// challenge starts with enough zero bits. Plain JS so it also works without HTTPS.
+const POW_SCRIPT: &str = r#"(function () {
+  var box = document.currentScript.parentNode;
+  var challenge = box.getAttribute('data-challenge');
+  var bits = parseInt(box.getAttribute('data-difficulty'), 10);
+  var answer = box.querySelector('input[name=captcha_answer]');
+  var status = box.querySelector('.input-help');
+  var button = box.closest('form').querySelector('button[type=submit]');
+  var K = [], H = [], n = 0, p = 2;
+  while (n < 64) {
+    var prime = true;
+    for (var d = 2; d * d <= p; d++) if (p % d === 0) { prime = false; break; }
+    if (prime) {
+      if (n < 8) H[n] = (Math.pow(p, 1 / 2) * 4294967296) | 0;
+      K[n++] = (Math.pow(p, 1 / 3) * 4294967296) | 0;
+    }
+    p++;
+  }
+  function rotr(x, r) { return (x >>> r) | (x << (32 - r)); }
+  function sha256(s) {
+    var words = [], total = (((s.length + 8) >> 6) << 4) + 16, w = [], h = H.slice(), i, j;
+    for (i = 0; i < s.length; i++) words[i >> 2] |= s.charCodeAt(i) << ((3 - (i & 3)) * 8);
+    words[s.length >> 2] |= 0x80 << ((3 - (s.length & 3)) * 8);
+    words[total - 1] = s.length * 8;
+    for (i = 0; i < total; i += 16) {
+      var a = h[0], b = h[1], c = h[2], dd = h[3], e = h[4], f = h[5], g = h[6], k = h[7];
+      for (j = 0; j < 64; j++) {
+        if (j < 16) {
+          w[j] = words[i + j] | 0;
+        } else {
+          var x = w[j - 15], y = w[j - 2];
+          w[j] = (w[j - 16] + (rotr(x, 7) ^ rotr(x, 18) ^ (x >>> 3)) + w[j - 7] + (rotr(y, 17) ^ rotr(y, 19) ^ (y >>> 10))) | 0;
+        }
+        var t1 = (k + (rotr(e, 6) ^ rotr(e, 11) ^ rotr(e, 25)) + ((e & f) ^ (~e & g)) + K[j] + w[j]) | 0;
+        var t2 = ((rotr(a, 2) ^ rotr(a, 13) ^ rotr(a, 22)) + ((a & b) ^ (a & c) ^ (b & c))) | 0;
+        k = g; g = f; f = e; e = (dd + t1) | 0; dd = c; c = b; b = a; a = (t1 + t2) | 0;
+      }
+      h[0] = (h[0] + a) | 0; h[1] = (h[1] + b) | 0; h[2] = (h[2] + c) | 0; h[3] = (h[3] + dd) | 0;
+      h[4] = (h[4] + e) | 0; h[5] = (h[5] + f) | 0; h[6] = (h[6] + g) | 0; h[7] = (h[7] + k) | 0;
+    }
+    return h;
+  }
+  function zeros(h) {
+    for (var i = 0, z = 0; i < 8; i++, z += 32) if (h[i] !== 0) return z + Math.clz32(h[i]);
+    return 256;
+  }
+  var nonce = 0;
+  function work() {
+    for (var end = nonce + 5000; nonce < end; nonce++) {
+      if (zeros(sha256(challenge + ':' + nonce)) >= bits) {
+        answer.value = String(nonce);
+        status.textContent = 'Verified.';
+        button.disabled = false;
+        return;
+      }
+    }
+    setTimeout(work, 0);
+  }
+  button.disabled = true;
+  status.textContent = 'Verifying your browser, this takes a few seconds…';
+  setTimeout(work, 0);
+})();"#;
+
+/// Self-hosted proof of work: the browser spends a moment of CPU time per registration.
+/// The cost grows with `CAPTCHA_POW_DIFFICULTY`, the number of leading zero bits required.
+struct ProofOfWork {
+    signer: Signer,
+    difficulty: u32,
+}
+
+#[async_trait]
+impl Captcha for ProofOfWork {
+    fn widget(&self) -> Markup {
+        let challenge = self.signer.issue("pow", &self.difficulty.to_string());
+        html! {
+            div class="form-group" data-challenge=(challenge) data-difficulty=(self.difficulty) {
+                input type="hidden" name="captcha_challenge" value=(challenge) {}
+                input type="hidden" name="captcha_answer" {}
+                div class="input-help" { "JavaScript is required to verify your browser." }
+                script { (PreEscaped(POW_SCRIPT)) }
+            }
+        }
+    }
+
+    fn info(&self) -> CaptchaInfo {
+        CaptchaInfo {
+            provider: "pow".into(),
+            challenge: Some(self.signer.issue("pow", &self.difficulty.to_string())),
+            difficulty: Some(self.difficulty),
+            ..Default::default()
+        }
+    }
+
+    async fn verify(&self, response: &CaptchaResponse, _remote_ip: Option<&str>) -> bool {
+        let (challenge, answer) = match (&response.captcha_challenge, &response.captcha_answer) {
+            (Some(c), Some(a)) if !a.is_empty() && a.len() <= 20 => (c, a),
+            _ => return false,
+        };
+        work_bits(challenge, answer) >= self.difficulty
+            && self.signer.redeem("pow", challenge, &self.difficulty.to_string())
+    }
+}
+
+/// Self-hosted "what is a + b" question; stops only the simplest bots, but needs no JavaScript.
+struct Arithmetic {
+    signer: Signer,
+}
+
+impl Arithmetic {
+    fn question(&self) -> (String, String) {
+        let mut rng = rand::thread_rng();
+        let (a, b) = (rng.gen_range(1..=20), rng.gen_range(1..=20));
+        let challenge = self.signer.issue("arithmetic", &(a + b).to_string());
+        (format!("What is {} + {}?", a, b), challenge)
+    }
+}
+
+#[async_trait]
+impl Captcha for Arithmetic {
+    fn widget(&self) -> Markup {
+        let (question, challenge) = self.question();
+        html! {
+            div class="form-group" {
+                label for="captcha-answer" { (question) }
+                input type="hidden" name="captcha_challenge" value=(challenge) {}
+                input type="text" id="captcha-answer" name="captcha_answer" inputmode="numeric" autocomplete="off" required {}
+            }
+        }
+    }
+
+    fn info(&self) -> CaptchaInfo {
+        let (question, challenge) = self.question();
+        CaptchaInfo {
+            provider: "arithmetic".into(),
+            challenge: Some(challenge),
+            question: Some(question),
+            ..Default::default()
+        }
+    }
+
+    async fn verify(&self, response: &CaptchaResponse, _remote_ip: Option<&str>) -> bool {
+        let (challenge, answer) = match (&response.captcha_challenge, &response.captcha_answer) {
+            (Some(c), Some(a)) => (c, a),
+            _ => return false,
+        };
+        match answer.trim().parse::<u32>() {
+            Ok(sum) => self.signer.redeem("arithmetic", challenge, &sum.to_string()),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer() -> Signer {
+        Signer { key: b"test".to_vec(), spent: Mutex::new(HashMap::new()) }
+    }
+
+    #[test]
+    fn challenges_are_accepted_once() {
+        let s = signer();
+        let c = s.issue("arithmetic", "7");
+        assert!(s.redeem("arithmetic", &c, "7"));
+        assert!(!s.redeem("arithmetic", &c, "7"));
+    }
+
+    #[test]
+    fn challenges_are_bound_to_kind_and_answer() {
+        let s = signer();
+        let c = s.issue("arithmetic", "7");
+        assert!(!s.redeem("arithmetic", &c, "8"));
+        assert!(!s.redeem("pow", &c, "7"));
+        assert!(!s.redeem("arithmetic", "1.2.3", "7"));
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::HashSet;
+use std::io::BufRead;
+
+use clap::{Args, Parser, Subcommand};
+use mongodb::bson::doc;
+use tokio::process::Command as GitCommand;
+
+use crate::auth;
+use crate::db::{Database, User};
+use crate::errors::AuthError;
+use crate::repo::repo_path;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Cli {
+    /// Defaults to `serve`
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the web, git and SSH servers
+    Serve(ServeArgs),
+    /// Manage user accounts
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Inspect repositories
+    #[command(subcommand)]
+    Repo(RepoCommand),
+    /// Manage access tokens
+    #[command(subcommand)]
+    Token(TokenCommand),
+}
+
+#[derive(Args, Debug, Default)]
+pub struct ServeArgs {
+    /// Port of the web server; defaults to `PORT`, then 8080
+    #[arg(short, long)]
+    pub port: Option<u16>,
+    /// Address the web server binds to; defaults to localhost
+    #[arg(short, long)]
+    pub addr: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UserCommand {
+    /// Create an account with a verified email address, whatever the registration mode
+    Create {
+        username: String,
+        email: String,
+        /// Read from standard input when omitted
+        #[arg(long)]
+        password: Option<String>,
+        /// Make the account a site administrator
+        #[arg(long)]
+        admin: bool,
+    },
+    /// Set a new password, sign the user out everywhere and revoke their access tokens
+    ResetPassword {
+        username: String,
+        /// Read from standard input when omitted
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Make a user a site administrator
+    PromoteAdmin {
+        username: String,
+        /// Take the administrator role away instead
+        #[arg(long)]
+        revoke: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RepoCommand {
+    /// List repositories as `owner/name`, visibility and id
+    List {
+        /// Only repositories of this user or organization
+        #[arg(long)]
+        owner: Option<String>,
+    },
+    /// Check that every repository exists on disk and report directories without one
+    Fsck {
+        /// Also run `git fsck` on each repository
+        #[arg(long)]
+        full: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TokenCommand {
+    /// Revoke a session, personal access or deploy token by its value, or every token of a user
+    Revoke {
+        #[arg(required_unless_present = "user", conflicts_with = "user")]
+        token: Option<String>,
+        /// Revoke all sessions and personal access tokens of this user
+        #[arg(long)]
+        user: Option<String>,
+    },
+}
+
+/// Runs an administration command directly against the database and `repos/`.
+pub async fn run(db: &Database, command: Command) -> Result<(), String> {
+    match command {
+        Command::Serve(_) => Ok(()),
+        Command::User(cmd) => user(db, cmd).await,
+        Command::Repo(RepoCommand::List { owner }) => repo_list(db, owner).await,
+        Command::Repo(RepoCommand::Fsck { full }) => repo_fsck(db, full).await,
+        Command::Token(TokenCommand::Revoke { token, user }) => token_revoke(db, token, user).await,
+    }
+}
+
+fn read_password(password: Option<String>) -> Result<String, String> {
+    if let Some(p) = password {
+        return Ok(p);
+    }
+    eprint!("Password: ");
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line).map_err(|e| e.to_string())?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn find_user(db: &Database, username: &str) -> Result<User, String> {
+    db.find_user_by_login(username)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("user '{}' not found", username))
+}
+
+async fn user(db: &Database, command: UserCommand) -> Result<(), String> {
+    match command {
+        UserCommand::Create { username, email, password, admin } => {
+            if !auth::is_valid_name(&username) {
+                return Err(format!("'{}' is not a valid username or is reserved", username));
+            }
+            if !auth::is_valid_email(email.trim()) {
+                return Err(format!("'{}' is not a valid email address", email));
+            }
+            let password = read_password(password)?;
+            if !auth::is_valid_password(&password) {
+                return Err(AuthError::InvalidPassword.to_string());
+            }
+            let user = auth::create_account(db, username, email, password, admin)
+                .await
+                .map_err(|e| match e {
+                    AuthError::InvalidCredentials => "username or email address is already taken".to_string(),
+                    e => e.to_string(),
+                })?;
+            println!("Created user {} ({})", user.username, user._id.to_hex());
+        }
+        UserCommand::ResetPassword { username, password } => {
+            let user = find_user(db, &username).await?;
+            let password = read_password(password)?;
+            auth::set_password(db, &user._id, &password).await.map_err(|e| e.to_string())?;
+            println!("Password of {} changed", user.username);
+        }
+        UserCommand::PromoteAdmin { username, revoke } => {
+            let user = find_user(db, &username).await?;
+            db.set_user_admin(&user._id, !revoke).await.map_err(|e| e.to_string())?;
+            match revoke {
+                true => println!("{} is no longer a site administrator", user.username),
+                false => println!("{} is now a site administrator", user.username),
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn repo_list(db: &Database, owner: Option<String>) -> Result<(), String> {
+    let filter = match &owner {
+        Some(name) => {
+            let owner = db
+                .find_owner_by_name(name)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("owner '{}' not found", name))?;
+            doc! { "user": owner.id() }
+        }
+        None => doc! {},
+    };
+    let repos = db.search_repos(filter, 0, 0).await.map_err(|e| e.to_string())?;
+    for r in repos {
+        let owner = db
+            .find_owner_by_id(&r.user)
+            .await
+            .map_err(|e| e.to_string())?
+            .map(|o| o.name().to_string())
+            .unwrap_or_else(|| format!("<{}>", r.user.to_hex()));
+        let visibility = if r.is_private { "private" } else { "public" };
+        println!("{}/{}\t{}\t{}", owner, r.name, visibility, r._id.to_hex());
+    }
+    Ok(())
+}
+
+async fn repo_fsck(db: &Database, full: bool) -> Result<(), String> {
+    let repos = db.search_repos(doc! {}, 0, 0).await.map_err(|e| e.to_string())?;
+    let mut problems = 0;
+    let mut known = HashSet::new();
+
+    for r in &repos {
+        known.insert((r.user.to_hex(), r._id.to_hex()));
+        let owner = db.find_owner_by_id(&r.user).await.map_err(|e| e.to_string())?;
+        let name = match &owner {
+            Some(o) => format!("{}/{}", o.name(), r.name),
+            None => format!("<{}>/{}", r.user.to_hex(), r.name),
+        };
+        if owner.is_none() {
+            println!("{}: owner does not exist", name);
+            problems += 1;
+        }
+
+        let path = repo_path(&r.user, &r._id);
+        if !path.is_dir() {
+            println!("{}: missing on disk at {}", name, path.display());
+            problems += 1;
+            continue;
+        }
+        if let Err(e) = git2::Repository::open_bare(&path) {
+            println!("{}: not a git repository: {}", name, e.message());
+            problems += 1;
+            continue;
+        }
+        if full {
+            let out = GitCommand::new("git")
+                .arg("--git-dir")
+                .arg(&path)
+                .args(["fsck", "--no-progress"])
+                .output()
+                .await
+                .map_err(|e| format!("could not run git: {}", e))?;
+            if !out.status.success() {
+                println!("{}: git fsck failed", name);
+                for line in String::from_utf8_lossy(&out.stderr).lines().chain(String::from_utf8_lossy(&out.stdout).lines()) {
+                    println!("    {}", line);
+                }
+                problems += 1;
+            }
+        }
+    }
+
+    // directories no repository points at, e.g. left behind by a failed delete
+    if let Ok(owners) = std::fs::read_dir("repos") {
+        for owner_dir in owners.flatten().filter(|e| e.path().is_dir()) {
+            let owner = owner_dir.file_name().to_string_lossy().to_string();
+            let Ok(entries) = std::fs::read_dir(owner_dir.path()) else { continue };
+            for entry in entries.flatten() {
+                let id = entry.file_name().to_string_lossy().to_string();
+                if !known.contains(&(owner.clone(), id)) {
+                    println!("{}: not referenced by any repository", entry.path().display());
+                    problems += 1;
+                }
+            }
+        }
+    }
+
+    println!("{} repositories checked, {} problems found", repos.len(), problems);
+    match problems {
+        0 => Ok(()),
+        n => Err(format!("{} problems found", n)),
+    }
+}
+
+async fn token_revoke(db: &Database, token: Option<String>, user: Option<String>) -> Result<(), String> {
+    if let Some(username) = user {
+        let user = find_user(db, &username).await?;
+        let n = db.delete_all_user_tokens(&user._id).await.map_err(|e| e.to_string())?;
+        println!("Revoked {} tokens of {}", n, user.username);
+        return Ok(());
+    }
+
+    let value = token.unwrap_or_default();
+    let value = value.trim();
+    if auth::is_deploy_token(value) {
+        let token = db
+            .find_deploy_token(&auth::token_prefix(value), &auth::hash_token(value))
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("token not found")?;
+        db.delete_deploy_token(&token._id).await.map_err(|e| e.to_string())?;
+        println!("Revoked deploy token '{}'", token.name);
+    } else {
+        auth::logout(db, value.to_string()).await.map_err(|e| match e {
+            AuthError::InvalidCredentials => "token not found".to_string(),
+            e => e.to_string(),
+        })?;
+        println!("Revoked token");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn parses_subcommands() {
+        Cli::command().debug_assert();
+
+        assert!(Cli::try_parse_from(["gitlit"]).unwrap().command.is_none());
+        let cli = Cli::try_parse_from(["gitlit", "user", "promote-admin", "alice", "--revoke"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::User(UserCommand::PromoteAdmin { revoke: true, .. }))));
+        let cli = Cli::try_parse_from(["gitlit", "serve", "--port", "3000"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Serve(ServeArgs { port: Some(3000), .. }))));
+
+        assert!(Cli::try_parse_from(["gitlit", "token", "revoke"]).is_err());
+        assert!(Cli::try_parse_from(["gitlit", "token", "revoke", "glt_x", "--user", "alice"]).is_err());
+    }
+}
@@ -0,0 +1,350 @@
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, DateTime};
+use mongodb::bson::oid::ObjectId;
+use mongodb::options::ReturnDocument;
+use mongodb::results::InsertOneResult;
+use crate::db;
+
+impl super::Database {
+    /// Stores a fresh, not yet confirmed TOTP secret and drops any previous enrollment.
+    pub async fn set_pending_totp(&self, user_id: &ObjectId, secret: &str) -> mongodb::error::Result<()> {
+        self.users
+            .update_one(
+                doc! { "_id": user_id },
+                doc! { "$set": { "totp_secret": secret, "totp_enabled": false, "recovery_codes": [] } },
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn enable_totp(&self, user_id: &ObjectId, recovery_hashes: Vec<String>) -> mongodb::error::Result<()> {
+        self.users
+            .update_one(
+                doc! { "_id": user_id },
+                doc! { "$set": { "totp_enabled": true, "recovery_codes": recovery_hashes } },
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn disable_totp(&self, user_id: &ObjectId) -> mongodb::error::Result<()> {
+        self.users
+            .update_one(
+                doc! { "_id": user_id },
+                doc! { "$set": { "totp_enabled": false, "recovery_codes": [] }, "$unset": { "totp_secret": "" } },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Removes a recovery code hash; returns `true` if it was present (and is now spent).
+    pub async fn consume_recovery_code(&self, user_id: &ObjectId, code_hash: &str) -> mongodb::error::Result<bool> {
+        let res = self
+            .users
+            .update_one(
+                doc! { "_id": user_id, "recovery_codes": code_hash },
+                doc! { "$pull": { "recovery_codes": code_hash } },
+            )
+            .await?;
+        Ok(res.modified_count == 1)
+    }
+
+    pub async fn update_password(&self, user_id: &ObjectId, password_hash: &str) -> mongodb::error::Result<()> {
+        self.users
+            .update_one(doc! { "_id": user_id }, doc! { "$set": { "password": password_hash } })
+            .await?;
+        Ok(())
+    }
+
+    /// Changes the address; it stays unverified until the new one is confirmed.
+    pub async fn update_email(&self, user_id: &ObjectId, email: &str) -> mongodb::error::Result<()> {
+        self.users
+            .update_one(doc! { "_id": user_id }, doc! { "$set": { "email": email, "email_verified": false } })
+            .await?;
+        Ok(())
+    }
+
+    /// Copies the profile from an external directory, which vouches for the address.
+    pub async fn sync_profile(&self, user_id: &ObjectId, email: &str, display_name: &str) -> mongodb::error::Result<()> {
+        self.users
+            .update_one(
+                doc! { "_id": user_id },
+                doc! { "$set": { "email": email, "email_verified": true, "display_name": display_name } },
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn update_username(&self, user_id: &ObjectId, username: &str) -> mongodb::error::Result<()> {
+        self.users
+            .update_one(doc! { "_id": user_id }, doc! { "$set": { "username": username } })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create_login_challenge(&self, challenge: db::LoginChallenge) -> mongodb::error::Result<InsertOneResult> {
+        self.login_challenges.insert_one(challenge).await
+    }
+
+    pub async fn find_login_challenge(&self, id: &ObjectId) -> mongodb::error::Result<Option<db::LoginChallenge>> {
+        self.login_challenges.find_one(doc! { "_id": id }).await
+    }
+
+    pub async fn delete_login_challenge(&self, id: &ObjectId) -> mongodb::error::Result<u64> {
+        let res = self.login_challenges.delete_one(doc! { "_id": id }).await?;
+        Ok(res.deleted_count)
+    }
+
+    pub async fn list_personal_tokens(&self, user_id: &ObjectId) -> mongodb::error::Result<Vec<db::Token>> {
+        let cursor = self
+            .tokens
+            .find(doc! { "user": user_id, "kind": "personal" })
+            .sort(doc! { "created_at": -1 })
+            .await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn delete_personal_token(&self, user_id: &ObjectId, token_id: &ObjectId) -> mongodb::error::Result<u64> {
+        let res = self
+            .tokens
+            .delete_one(doc! { "_id": token_id, "user": user_id, "kind": "personal" })
+            .await?;
+        Ok(res.deleted_count)
+    }
+
+    pub async fn touch_token(&self, token_id: &ObjectId) -> mongodb::error::Result<()> {
+        self.tokens
+            .update_one(doc! { "_id": token_id }, doc! { "$set": { "last_used_at": DateTime::now() } })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_token_expiry(&self, token_id: &ObjectId, expires_at: DateTime) -> mongodb::error::Result<()> {
+        self.tokens
+            .update_one(doc! { "_id": token_id }, doc! { "$set": { "expires_at": expires_at } })
+            .await?;
+        Ok(())
+    }
+
+    /// Every unexpired session and personal token of a user, most recently created first.
+    pub async fn list_user_tokens(&self, user_id: &ObjectId) -> mongodb::error::Result<Vec<db::Token>> {
+        let now = DateTime::now();
+        let cursor = self
+            .tokens
+            .find(doc! {
+                "user": user_id,
+                "$or": [ { "expires_at": null }, { "expires_at": { "$gt": now } } ],
+            })
+            .sort(doc! { "created_at": -1 })
+            .await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn delete_user_token(&self, user_id: &ObjectId, token_id: &ObjectId) -> mongodb::error::Result<u64> {
+        let res = self.tokens.delete_one(doc! { "_id": token_id, "user": user_id }).await?;
+        Ok(res.deleted_count)
+    }
+
+    /// Revokes every personal access token of a user.
+    pub async fn delete_personal_tokens(&self, user_id: &ObjectId) -> mongodb::error::Result<u64> {
+        let res = self.tokens.delete_many(doc! { "user": user_id, "kind": "personal" }).await?;
+        Ok(res.deleted_count)
+    }
+
+    /// Deletes all login sessions of a user, optionally keeping one (the caller's).
+    pub async fn delete_user_sessions(&self, user_id: &ObjectId, keep: Option<&ObjectId>) -> mongodb::error::Result<u64> {
+        let mut filter = doc! { "user": user_id, "kind": { "$ne": "personal" } };
+        if let Some(id) = keep {
+            filter.insert("_id", doc! { "$ne": id });
+        }
+        let res = self.tokens.delete_many(filter).await?;
+        Ok(res.deleted_count)
+    }
+
+    /// Stores a new reset link for a user, replacing any earlier one.
+    pub async fn replace_password_reset(&self, reset: db::PasswordReset) -> mongodb::error::Result<()> {
+        self.password_resets.delete_many(doc! { "user": reset.user }).await?;
+        self.password_resets.insert_one(reset).await?;
+        Ok(())
+    }
+
+    /// Removes and returns the reset with this token hash, so each link works only once.
+    pub async fn take_password_reset(&self, token_hash: &str) -> mongodb::error::Result<Option<db::PasswordReset>> {
+        self.password_resets.find_one_and_delete(doc! { "token_hash": token_hash }).await
+    }
+
+    /// Stores a new verification link for a user, replacing any earlier one.
+    pub async fn replace_email_verification(&self, verification: db::EmailVerification) -> mongodb::error::Result<()> {
+        self.email_verifications.delete_many(doc! { "user": verification.user }).await?;
+        self.email_verifications.insert_one(verification).await?;
+        Ok(())
+    }
+
+    pub async fn take_email_verification(&self, token_hash: &str) -> mongodb::error::Result<Option<db::EmailVerification>> {
+        self.email_verifications.find_one_and_delete(doc! { "token_hash": token_hash }).await
+    }
+
+    /// Marks `email` as verified, unless the user has changed their address since.
+    pub async fn set_email_verified(&self, user_id: &ObjectId, email: &str) -> mongodb::error::Result<bool> {
+        let res = self
+            .users
+            .update_one(
+                doc! { "_id": user_id, "email": email },
+                doc! { "$set": { "email_verified": true } },
+            )
+            .await?;
+        Ok(res.matched_count == 1)
+    }
+
+    pub async fn find_login_attempts(&self, keys: &[String]) -> mongodb::error::Result<Vec<db::LoginAttempt>> {
+        let cursor = self.login_attempts.find(doc! { "key": { "$in": keys } }).await?;
+        cursor.try_collect().await
+    }
+
+    /// Increments the failure counter for `key` and returns the updated record.
+    pub async fn record_login_failure(&self, key: &str, expires_at: DateTime) -> mongodb::error::Result<db::LoginAttempt> {
+        let attempt = self
+            .login_attempts
+            .find_one_and_update(
+                doc! { "key": key },
+                doc! {
+                    "$inc": { "failures": 1 },
+                    "$set": { "last_failure": DateTime::now(), "expires_at": expires_at },
+                    "$setOnInsert": { "_id": ObjectId::new(), "locked_until": null },
+                },
+            )
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .await?;
+        attempt.ok_or_else(|| mongodb::error::Error::custom("upsert returned no document"))
+    }
+
+    pub async fn lock_login(&self, key: &str, until: DateTime) -> mongodb::error::Result<()> {
+        self.login_attempts
+            .update_one(doc! { "key": key }, doc! { "$set": { "locked_until": until } })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn clear_login_attempts(&self, key: &str) -> mongodb::error::Result<u64> {
+        let res = self.login_attempts.delete_one(doc! { "key": key }).await?;
+        Ok(res.deleted_count)
+    }
+
+    pub async fn create_invite(&self, invite: db::Invite) -> mongodb::error::Result<InsertOneResult> {
+        self.invites.insert_one(invite).await
+    }
+
+    /// Unexpired invites, newest first; all of them when `created_by` is `None`.
+    pub async fn list_invites(&self, created_by: Option<&ObjectId>) -> mongodb::error::Result<Vec<db::Invite>> {
+        let mut filter = doc! {
+            "$or": [ { "expires_at": null }, { "expires_at": { "$gt": DateTime::now() } } ],
+        };
+        if let Some(user) = created_by {
+            filter.insert("created_by", user);
+        }
+        let cursor = self.invites.find(filter).sort(doc! { "created_at": -1 }).await?;
+        cursor.try_collect().await
+    }
+
+    /// Deletes an invite; restricted to the creator's own invites unless `created_by` is `None`.
+    pub async fn delete_invite(&self, id: &ObjectId, created_by: Option<&ObjectId>) -> mongodb::error::Result<u64> {
+        let mut filter = doc! { "_id": id };
+        if let Some(user) = created_by {
+            filter.insert("created_by", user);
+        }
+        let res = self.invites.delete_one(filter).await?;
+        Ok(res.deleted_count)
+    }
+
+    /// Uses up one slot of a valid invite for `user`; `None` when the code is unknown,
+    /// expired or exhausted. The check and the increment happen in one update.
+    pub async fn claim_invite(&self, code_hash: &str, user: &ObjectId) -> mongodb::error::Result<Option<db::Invite>> {
+        self.invites
+            .find_one_and_update(
+                doc! {
+                    "code_hash": code_hash,
+                    "$expr": { "$lt": ["$uses", "$max_uses"] },
+                    "$or": [ { "expires_at": null }, { "expires_at": { "$gt": DateTime::now() } } ],
+                },
+                doc! { "$inc": { "uses": 1 }, "$push": { "used_by": user } },
+            )
+            .return_document(ReturnDocument::After)
+            .await
+    }
+
+    /// Gives back a slot taken by [`claim_invite`](Self::claim_invite) when registration fails afterwards.
+    pub async fn release_invite(&self, id: &ObjectId, user: &ObjectId) -> mongodb::error::Result<()> {
+        self.invites
+            .update_one(doc! { "_id": id }, doc! { "$inc": { "uses": -1 }, "$pull": { "used_by": user } })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create_oidc_login(&self, login: db::OidcLogin) -> mongodb::error::Result<InsertOneResult> {
+        self.oidc_logins.insert_one(login).await
+    }
+
+    /// Removes and returns the pending sign-in with this state, so each callback works only once.
+    pub async fn take_oidc_login(&self, state: &str) -> mongodb::error::Result<Option<db::OidcLogin>> {
+        self.oidc_logins.find_one_and_delete(doc! { "state": state }).await
+    }
+
+    pub async fn find_identity(&self, provider: &str, subject: &str) -> mongodb::error::Result<Option<db::Identity>> {
+        self.identities.find_one(doc! { "provider": provider, "subject": subject }).await
+    }
+
+    pub async fn create_identity(&self, identity: db::Identity) -> mongodb::error::Result<InsertOneResult> {
+        self.identities.insert_one(identity).await
+    }
+
+    pub async fn list_identities(&self, user_id: &ObjectId) -> mongodb::error::Result<Vec<db::Identity>> {
+        let cursor = self
+            .identities
+            .find(doc! { "user": user_id })
+            .sort(doc! { "created_at": 1 })
+            .await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn delete_identity(&self, user_id: &ObjectId, id: &ObjectId) -> mongodb::error::Result<u64> {
+        let res = self.identities.delete_one(doc! { "_id": id, "user": user_id }).await?;
+        Ok(res.deleted_count)
+    }
+
+    pub async fn touch_identity(&self, id: &ObjectId, email: Option<&str>) -> mongodb::error::Result<()> {
+        self.identities
+            .update_one(doc! { "_id": id }, doc! { "$set": { "last_used_at": DateTime::now(), "email": email } })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create_ssh_key(&self, key: db::SshKey) -> mongodb::error::Result<InsertOneResult> {
+        self.ssh_keys.insert_one(key).await
+    }
+
+    pub async fn list_ssh_keys(&self, user_id: &ObjectId) -> mongodb::error::Result<Vec<db::SshKey>> {
+        let cursor = self
+            .ssh_keys
+            .find(doc! { "user": user_id })
+            .sort(doc! { "created_at": 1 })
+            .await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn find_ssh_key(&self, fingerprint: &str) -> mongodb::error::Result<Option<db::SshKey>> {
+        self.ssh_keys.find_one(doc! { "fingerprint": fingerprint }).await
+    }
+
+    pub async fn delete_ssh_key(&self, user_id: &ObjectId, id: &ObjectId) -> mongodb::error::Result<u64> {
+        let res = self.ssh_keys.delete_one(doc! { "_id": id, "user": user_id }).await?;
+        Ok(res.deleted_count)
+    }
+
+    pub async fn touch_ssh_key(&self, id: &ObjectId) -> mongodb::error::Result<()> {
+        self.ssh_keys
+            .update_one(doc! { "_id": id }, doc! { "$set": { "last_used_at": DateTime::now() } })
+            .await?;
+        Ok(())
+    }
+}
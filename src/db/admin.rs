@@ -0,0 +1,92 @@
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::bson::oid::ObjectId;
+use crate::db;
+
+impl super::Database {
+    pub async fn search_users(&self, filter: Document, skip: u64, limit: i64) -> mongodb::error::Result<Vec<db::User>> {
+        let cursor = self
+            .users
+            .find(filter)
+            .sort(doc! { "username": 1 })
+            .skip(skip)
+            .limit(limit)
+            .await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn search_repos(&self, filter: Document, skip: u64, limit: i64) -> mongodb::error::Result<Vec<db::Repository>> {
+        let cursor = self
+            .repositories
+            .find(filter)
+            .sort(doc! { "created_at": -1 })
+            .skip(skip)
+            .limit(limit)
+            .await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn count_users(&self, filter: Document) -> mongodb::error::Result<u64> {
+        self.users.count_documents(filter).await
+    }
+
+    pub async fn count_repos(&self, filter: Document) -> mongodb::error::Result<u64> {
+        self.repositories.count_documents(filter).await
+    }
+
+    pub async fn count_orgs(&self) -> mongodb::error::Result<u64> {
+        self.organizations.count_documents(doc! {}).await
+    }
+
+    pub async fn set_user_suspended(&self, user_id: &ObjectId, suspended: bool) -> mongodb::error::Result<()> {
+        self.users
+            .update_one(doc! { "_id": user_id }, doc! { "$set": { "suspended": suspended } })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_user_admin(&self, user_id: &ObjectId, admin: bool) -> mongodb::error::Result<()> {
+        self.users
+            .update_one(doc! { "_id": user_id }, doc! { "$set": { "admin": admin } })
+            .await?;
+        Ok(())
+    }
+
+    /// Signs a user out everywhere: sessions and personal access tokens alike.
+    pub async fn delete_all_user_tokens(&self, user_id: &ObjectId) -> mongodb::error::Result<u64> {
+        let res = self.tokens.delete_many(doc! { "user": user_id }).await?;
+        Ok(res.deleted_count)
+    }
+
+    /// Moves a repository to another user or organization.
+    pub async fn set_repo_owner(&self, repo_id: &ObjectId, owner: &ObjectId) -> mongodb::error::Result<()> {
+        self.repositories
+            .update_one(doc! { "_id": repo_id }, doc! { "$set": { "user": owner, "updated_at": bson::DateTime::now() } })
+            .await?;
+        Ok(())
+    }
+
+    /// Removes an account with its credentials and memberships, the registration invites
+    /// it issued and the deploy keys and tokens it created on other owners' repositories.
+    /// Its repositories must be deleted first.
+    pub async fn delete_user_account(&self, user_id: &ObjectId) -> mongodb::error::Result<u64> {
+        self.tokens.delete_many(doc! { "user": user_id }).await?;
+        self.invites.delete_many(doc! { "created_by": user_id }).await?;
+        self.deploy_keys.delete_many(doc! { "created_by": user_id }).await?;
+        self.deploy_tokens.delete_many(doc! { "created_by": user_id }).await?;
+        self.ssh_keys.delete_many(doc! { "user": user_id }).await?;
+        self.identities.delete_many(doc! { "user": user_id }).await?;
+        self.login_challenges.delete_many(doc! { "user": user_id }).await?;
+        self.password_resets.delete_many(doc! { "user": user_id }).await?;
+        self.email_verifications.delete_many(doc! { "user": user_id }).await?;
+        self.collaborators.delete_many(doc! { "user": user_id }).await?;
+        self.org_members.delete_many(doc! { "user": user_id }).await?;
+        self.teams
+            .update_many(doc! { "members": user_id }, doc! { "$pull": { "members": user_id } })
+            .await?;
+        self.name_redirects.delete_many(doc! { "owner": user_id }).await?;
+        self.repo_redirects.delete_many(doc! { "owner": user_id }).await?;
+        let res = self.users.delete_one(doc! { "_id": user_id }).await?;
+        Ok(res.deleted_count)
+    }
+}
@@ -0,0 +1,80 @@
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, DateTime};
+use mongodb::bson::oid::ObjectId;
+use mongodb::results::InsertOneResult;
+use crate::db;
+
+impl super::Database {
+    pub async fn create_deploy_key(&self, key: db::DeployKey) -> mongodb::error::Result<InsertOneResult> {
+        self.deploy_keys.insert_one(key).await
+    }
+
+    pub async fn list_deploy_keys(&self, repo_id: &ObjectId) -> mongodb::error::Result<Vec<db::DeployKey>> {
+        let cursor = self
+            .deploy_keys
+            .find(doc! { "repo": repo_id })
+            .sort(doc! { "created_at": 1 })
+            .await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn find_deploy_key(&self, fingerprint: &str) -> mongodb::error::Result<Option<db::DeployKey>> {
+        self.deploy_keys.find_one(doc! { "fingerprint": fingerprint }).await
+    }
+
+    pub async fn find_deploy_key_by_id(&self, id: &ObjectId) -> mongodb::error::Result<Option<db::DeployKey>> {
+        self.deploy_keys.find_one(doc! { "_id": id }).await
+    }
+
+    pub async fn delete_deploy_key(&self, id: &ObjectId) -> mongodb::error::Result<u64> {
+        let res = self.deploy_keys.delete_one(doc! { "_id": id }).await?;
+        Ok(res.deleted_count)
+    }
+
+    pub async fn touch_deploy_key(&self, id: &ObjectId) -> mongodb::error::Result<()> {
+        self.deploy_keys
+            .update_one(doc! { "_id": id }, doc! { "$set": { "last_used_at": DateTime::now() } })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create_deploy_token(&self, token: db::DeployToken) -> mongodb::error::Result<InsertOneResult> {
+        self.deploy_tokens.insert_one(token).await
+    }
+
+    pub async fn list_deploy_tokens(&self, repo_id: &ObjectId) -> mongodb::error::Result<Vec<db::DeployToken>> {
+        let cursor = self
+            .deploy_tokens
+            .find(doc! { "repo": repo_id })
+            .sort(doc! { "created_at": 1 })
+            .await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn find_deploy_token(&self, prefix: &str, token_hash: &str) -> mongodb::error::Result<Option<db::DeployToken>> {
+        self.deploy_tokens.find_one(doc! { "prefix": prefix, "token_hash": token_hash }).await
+    }
+
+    pub async fn find_deploy_token_by_id(&self, id: &ObjectId) -> mongodb::error::Result<Option<db::DeployToken>> {
+        self.deploy_tokens.find_one(doc! { "_id": id }).await
+    }
+
+    pub async fn delete_deploy_token(&self, id: &ObjectId) -> mongodb::error::Result<u64> {
+        let res = self.deploy_tokens.delete_one(doc! { "_id": id }).await?;
+        Ok(res.deleted_count)
+    }
+
+    pub async fn touch_deploy_token(&self, id: &ObjectId) -> mongodb::error::Result<()> {
+        self.deploy_tokens
+            .update_one(doc! { "_id": id }, doc! { "$set": { "last_used_at": DateTime::now() } })
+            .await?;
+        Ok(())
+    }
+
+    /// Removes the deploy keys and tokens of a deleted repository.
+    pub async fn delete_deploy_credentials_for_repo(&self, repo_id: &ObjectId) -> mongodb::error::Result<()> {
+        self.deploy_keys.delete_many(doc! { "repo": repo_id }).await?;
+        self.deploy_tokens.delete_many(doc! { "repo": repo_id }).await?;
+        Ok(())
+    }
+}
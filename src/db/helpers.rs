@@ -66,13 +66,87 @@ impl super::Database {
         Ok(res)
     }
 
-    pub async fn find_token(&self, token_value: &str) -> mongodb::error::Result<Option<db::Token>> {
-        let res = self.tokens.find_one(doc! { "token": token_value }).await?;
+    /// Stores the editable attributes of a repository and bumps `updated_at`.
+    pub async fn update_repo_settings(&self, repo: &db::Repository) -> mongodb::error::Result<()> {
+        self.repositories
+            .update_one(
+                doc! { "_id": repo._id },
+                doc! {
+                    "$set": {
+                        "description": &repo.description,
+                        "is_private": repo.is_private,
+                        "website": &repo.website,
+                        "topics": &repo.topics,
+                        "updated_at": bson::DateTime::now(),
+                    }
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Repositories forked from `parent`, oldest first.
+    pub async fn find_forks(&self, parent: &bson::oid::ObjectId) -> mongodb::error::Result<Vec<db::Repository>> {
+        self.find_repos_with_filter_sort(doc! { "forked_from": parent.to_hex() }, doc! { "created_at": 1 }).await
+    }
+
+    /// Detaches the forks of a deleted repository from it.
+    pub async fn clear_forked_from(&self, parent: &bson::oid::ObjectId) -> mongodb::error::Result<()> {
+        self.repositories
+            .update_many(doc! { "forked_from": parent.to_hex() }, doc! { "$set": { "forked_from": bson::Bson::Null } })
+            .await?;
+        Ok(())
+    }
+
+    /// Updates a repository's name and bumps `updated_at`.
+    pub async fn rename_repository(&self, id: &bson::oid::ObjectId, name: &str) -> mongodb::error::Result<()> {
+        self.repositories
+            .update_one(doc! { "_id": id }, doc! { "$set": { "name": name, "updated_at": bson::DateTime::now() } })
+            .await?;
+        Ok(())
+    }
+
+    /// Follows a former `owner/name` path to the repository that was moved away from it.
+    pub async fn find_renamed_repo(&self, owner: &bson::oid::ObjectId, old_name: &str) -> mongodb::error::Result<Option<db::Repository>> {
+        match self.repo_redirects.find_one(doc! { "owner": owner, "name": old_name }).await? {
+            Some(r) => self.find_repo(&r.repo).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Points `owner/old_name` at `repo`, replacing any earlier redirect for that path.
+    pub async fn set_repo_redirect(&self, owner: &bson::oid::ObjectId, old_name: &str, repo: &bson::oid::ObjectId) -> mongodb::error::Result<()> {
+        self.repo_redirects
+            .update_one(
+                doc! { "owner": owner, "name": old_name },
+                doc! {
+                    "$set": { "repo": repo },
+                    "$setOnInsert": { "_id": bson::oid::ObjectId::new(), "created_at": bson::DateTime::now() },
+                },
+            )
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    /// Drops the redirect for a path that a repository now occupies.
+    pub async fn delete_repo_redirect(&self, owner: &bson::oid::ObjectId, name: &str) -> mongodb::error::Result<u64> {
+        let res = self.repo_redirects.delete_one(doc! { "owner": owner, "name": name }).await?;
+        Ok(res.deleted_count)
+    }
+
+    pub async fn delete_repo_redirects_for_repo(&self, repo: &bson::oid::ObjectId) -> mongodb::error::Result<u64> {
+        let res = self.repo_redirects.delete_many(doc! { "repo": repo }).await?;
+        Ok(res.deleted_count)
+    }
+
+    pub async fn find_token(&self, prefix: &str, token_hash: &str) -> mongodb::error::Result<Option<db::Token>> {
+        let res = self.tokens.find_one(doc! { "prefix": prefix, "token_hash": token_hash }).await?;
         Ok(res)
     }
 
-    pub async fn delete_token(&self, token_value: &str) -> mongodb::error::Result<u64> {
-        let res = self.tokens.delete_one(doc! { "token": token_value }).await?;
+    pub async fn delete_token(&self, prefix: &str, token_hash: &str) -> mongodb::error::Result<u64> {
+        let res = self.tokens.delete_one(doc! { "prefix": prefix, "token_hash": token_hash }).await?;
         Ok(res.deleted_count)
     }
     pub async fn delete_repository_by_id(&self, id: &bson::oid::ObjectId) -> mongodb::error::Result<u64> {
@@ -90,4 +164,84 @@ impl super::Database {
         let repos: Vec<super::models::Repository> = cursor.try_collect().await?;
         Ok(repos)
     }
+
+    pub async fn find_collaborator(
+        &self,
+        repo_id: &bson::oid::ObjectId,
+        user_id: &bson::oid::ObjectId,
+    ) -> mongodb::error::Result<Option<db::Collaborator>> {
+        let res = self.collaborators.find_one(doc! { "repo": repo_id, "user": user_id }).await?;
+        Ok(res)
+    }
+
+    pub async fn list_collaborators(&self, repo_id: &bson::oid::ObjectId) -> mongodb::error::Result<Vec<db::Collaborator>> {
+        use futures_util::TryStreamExt;
+        let cursor = self.collaborators.find(doc! { "repo": repo_id }).sort(doc! { "created_at": 1 }).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn set_collaborator(
+        &self,
+        repo_id: &bson::oid::ObjectId,
+        user_id: &bson::oid::ObjectId,
+        role: db::Role,
+    ) -> mongodb::error::Result<()> {
+        let res = self
+            .collaborators
+            .update_one(
+                doc! { "repo": repo_id, "user": user_id },
+                doc! {
+                    "$set": { "role": role.as_str() },
+                    "$setOnInsert": { "_id": bson::oid::ObjectId::new(), "created_at": bson::DateTime::now() },
+                },
+            )
+            .upsert(true)
+            .await?;
+        info!("Set collaborator role: {:?}", res);
+        Ok(())
+    }
+
+    pub async fn delete_collaborator(
+        &self,
+        repo_id: &bson::oid::ObjectId,
+        user_id: &bson::oid::ObjectId,
+    ) -> mongodb::error::Result<u64> {
+        let res = self.collaborators.delete_one(doc! { "repo": repo_id, "user": user_id }).await?;
+        Ok(res.deleted_count)
+    }
+
+    pub async fn delete_collaborators_for_repo(&self, repo_id: &bson::oid::ObjectId) -> mongodb::error::Result<u64> {
+        let res = self.collaborators.delete_many(doc! { "repo": repo_id }).await?;
+        Ok(res.deleted_count)
+    }
+
+    pub async fn collaborator_repo_ids(&self, user_id: &bson::oid::ObjectId) -> mongodb::error::Result<Vec<bson::oid::ObjectId>> {
+        use futures_util::TryStreamExt;
+        let cursor = self.collaborators.find(doc! { "user": user_id }).await?;
+        let entries: Vec<db::Collaborator> = cursor.try_collect().await?;
+        Ok(entries.into_iter().map(|c| c.repo).collect())
+    }
+
+    /// Effective role of a user on a repository: the highest of ownership,
+    /// organization ownership, team grants and direct collaboration.
+    pub async fn find_repo_role(
+        &self,
+        user_id: &bson::oid::ObjectId,
+        repo: &db::Repository,
+    ) -> mongodb::error::Result<Option<db::Role>> {
+        if repo.user == *user_id {
+            return Ok(Some(db::Role::Admin));
+        }
+        if let Some(member) = self.find_org_member(&repo.user, user_id).await? {
+            if member.role == db::OrgRole::Owner {
+                return Ok(Some(db::Role::Admin));
+            }
+        }
+
+        let mut role = self.find_collaborator(&repo._id, user_id).await?.map(|c| c.role);
+        for team in self.teams_for_user_and_repo(user_id, &repo._id).await? {
+            role = role.max(Some(team.permission));
+        }
+        Ok(role)
+    }
 }
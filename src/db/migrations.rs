@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::options::IndexOptions;
+use mongodb::IndexModel;
+use tracing::{info, warn};
+
+impl super::Database {
+    /// Indexes the server relies on; creating an existing index is a no-op.
+    pub(super) async fn ensure_indexes(&self) -> mongodb::error::Result<()> {
+        // documents are removed once `expires_at` has passed; tokens without it never expire
+        let ttl = || {
+            IndexModel::builder()
+                .keys(doc! { "expires_at": 1 })
+                .options(IndexOptions::builder().expire_after(Duration::from_secs(0)).build())
+                .build()
+        };
+        self.tokens.create_index(ttl()).await?;
+        self.login_challenges.create_index(ttl()).await?;
+        self.password_resets.create_index(ttl()).await?;
+        self.email_verifications.create_index(ttl()).await?;
+        self.login_attempts.create_index(ttl()).await?;
+        self.invites.create_index(ttl()).await?;
+        self.oidc_logins.create_index(ttl()).await?;
+        self.deploy_tokens.create_index(ttl()).await?;
+
+        self.tokens
+            .create_index(IndexModel::builder().keys(doc! { "prefix": 1 }).build())
+            .await?;
+        self.password_resets
+            .create_index(IndexModel::builder().keys(doc! { "token_hash": 1 }).build())
+            .await?;
+        self.email_verifications
+            .create_index(IndexModel::builder().keys(doc! { "token_hash": 1 }).build())
+            .await?;
+        self.login_attempts
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "key": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await?;
+        self.invites
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "code_hash": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await?;
+        self.oidc_logins
+            .create_index(IndexModel::builder().keys(doc! { "state": 1 }).build())
+            .await?;
+        self.identities
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "provider": 1, "subject": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await?;
+        self.identities
+            .create_index(IndexModel::builder().keys(doc! { "user": 1 }).build())
+            .await?;
+        self.ssh_keys
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "fingerprint": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await?;
+        self.ssh_keys
+            .create_index(IndexModel::builder().keys(doc! { "user": 1 }).build())
+            .await?;
+        self.deploy_keys
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "fingerprint": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await?;
+        self.deploy_keys
+            .create_index(IndexModel::builder().keys(doc! { "repo": 1 }).build())
+            .await?;
+        self.deploy_tokens
+            .create_index(IndexModel::builder().keys(doc! { "prefix": 1 }).build())
+            .await?;
+        self.deploy_tokens
+            .create_index(IndexModel::builder().keys(doc! { "repo": 1 }).build())
+            .await?;
+        self.name_redirects
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "name": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await?;
+        self.repo_redirects
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "owner": 1, "name": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await?;
+        self.repo_redirects
+            .create_index(IndexModel::builder().keys(doc! { "repo": 1 }).build())
+            .await?;
+        self.repositories
+            .create_index(IndexModel::builder().keys(doc! { "forked_from": 1 }).build())
+            .await?;
+        Ok(())
+    }
+
+    /// Replaces plaintext `token` fields left by older versions with `prefix` and `token_hash`.
+    pub(super) async fn migrate_plaintext_tokens(&self) -> mongodb::error::Result<()> {
+        let raw = self.tokens.clone_with_type::<Document>();
+        let legacy: Vec<Document> = raw
+            .find(doc! { "token": { "$exists": true } })
+            .await?
+            .try_collect()
+            .await?;
+        if legacy.is_empty() {
+            return Ok(());
+        }
+
+        info!("Migrating {} plaintext tokens to hashed storage", legacy.len());
+        for d in legacy {
+            let (id, value) = match (d.get_object_id("_id"), d.get_str("token")) {
+                (Ok(id), Ok(value)) => (id, value),
+                _ => {
+                    warn!("Skipping malformed token document: {:?}", d.get("_id"));
+                    continue;
+                }
+            };
+            raw.update_one(
+                doc! { "_id": id },
+                doc! {
+                    "$set": {
+                        "prefix": crate::auth::token_prefix(value),
+                        "token_hash": crate::auth::hash_token(value),
+                    },
+                    "$unset": { "token": "" },
+                },
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,10 @@
+mod account;
+mod admin;
+mod deploy;
 mod helpers;
+mod migrations;
 mod models;
+mod orgs;
 
 pub use models::*;
 
@@ -12,6 +17,22 @@ pub struct Database {
     users: Collection<User>,
     repositories: Collection<Repository>,
     tokens: Collection<Token>,
+    collaborators: Collection<Collaborator>,
+    organizations: Collection<Organization>,
+    org_members: Collection<OrgMember>,
+    teams: Collection<Team>,
+    login_challenges: Collection<LoginChallenge>,
+    name_redirects: Collection<NameRedirect>,
+    repo_redirects: Collection<RepoRedirect>,
+    password_resets: Collection<PasswordReset>,
+    email_verifications: Collection<EmailVerification>,
+    login_attempts: Collection<LoginAttempt>,
+    invites: Collection<Invite>,
+    identities: Collection<Identity>,
+    oidc_logins: Collection<OidcLogin>,
+    ssh_keys: Collection<SshKey>,
+    deploy_keys: Collection<DeployKey>,
+    deploy_tokens: Collection<DeployToken>,
 }
 
 impl Database {
@@ -38,7 +59,47 @@ impl Database {
         let users: Collection<User> = db.collection("users");
         let repositories: Collection<Repository> = db.collection("repositories");
         let tokens: Collection<Token> = db.collection("tokens");
+        let collaborators: Collection<Collaborator> = db.collection("collaborators");
+        let organizations: Collection<Organization> = db.collection("organizations");
+        let org_members: Collection<OrgMember> = db.collection("org_members");
+        let teams: Collection<Team> = db.collection("teams");
+        let login_challenges: Collection<LoginChallenge> = db.collection("login_challenges");
+        let name_redirects: Collection<NameRedirect> = db.collection("name_redirects");
+        let repo_redirects: Collection<RepoRedirect> = db.collection("repo_redirects");
+        let password_resets: Collection<PasswordReset> = db.collection("password_resets");
+        let email_verifications: Collection<EmailVerification> = db.collection("email_verifications");
+        let login_attempts: Collection<LoginAttempt> = db.collection("login_attempts");
+        let invites: Collection<Invite> = db.collection("invites");
+        let identities: Collection<Identity> = db.collection("identities");
+        let oidc_logins: Collection<OidcLogin> = db.collection("oidc_logins");
+        let ssh_keys: Collection<SshKey> = db.collection("ssh_keys");
+        let deploy_keys: Collection<DeployKey> = db.collection("deploy_keys");
+        let deploy_tokens: Collection<DeployToken> = db.collection("deploy_tokens");
 
-        Database { users, repositories, tokens }
+        let database = Database {
+            users,
+            repositories,
+            tokens,
+            collaborators,
+            organizations,
+            org_members,
+            teams,
+            login_challenges,
+            name_redirects,
+            repo_redirects,
+            password_resets,
+            email_verifications,
+            login_attempts,
+            invites,
+            identities,
+            oidc_logins,
+            ssh_keys,
+            deploy_keys,
+            deploy_tokens,
+        };
+
+        database.migrate_plaintext_tokens().await.unwrap();
+        database.ensure_indexes().await.unwrap();
+        database
     }
 }
@@ -11,6 +11,12 @@ pub struct Repository {
     pub name: String,
     pub description: String,
     pub is_private: bool,
+    /// Project homepage shown next to the description
+    #[serde(default)]
+    pub website: Option<String>,
+    #[serde(default)]
+    pub topics: Vec<String>,
+    /// Id of the repository this one was forked from
     #[schema(value_type = Option<String>)]
     pub forked_from: Option<String>,
     #[schema(value_type = String, format = DateTime, example = "2024-01-01T12:00:00Z")]
@@ -19,31 +25,417 @@ pub struct Repository {
     pub updated_at: DateTime,
 }
 
-#[derive(Serialize, Deserialize, Debug, utoipa::ToSchema)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenKind {
+    /// Created by logging in; carries every scope.
+    #[default]
+    Session,
+    /// Named personal access token limited to its scopes.
+    Personal,
+}
+
+/// Permission granted to a personal access token. Each scope implies the ones below it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, utoipa::ToSchema)]
+pub enum Scope {
+    #[serde(rename = "repo:read")]
+    RepoRead,
+    #[serde(rename = "repo:write")]
+    RepoWrite,
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::RepoRead, Scope::RepoWrite, Scope::Admin];
+
+    pub fn parse(s: &str) -> Option<Scope> {
+        match s {
+            "repo:read" => Some(Scope::RepoRead),
+            "repo:write" => Some(Scope::RepoWrite),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::RepoRead => "repo:read",
+            Scope::RepoWrite => "repo:write",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, utoipa::ToSchema)]
 pub struct Token {
     #[schema(value_type = String)]
     pub _id: ObjectId,
     #[schema(value_type = String)]
     pub user: ObjectId,
-    pub token: String,
+    /// Leading characters of the token, stored in clear to find candidates.
+    pub prefix: String,
+    /// SHA-256 of the full token; the token itself is never stored.
+    pub token_hash: String,
     #[schema(value_type = String, format = DateTime)]
     pub created_at: DateTime,
+    /// Expired tokens are removed by a TTL index.
     #[schema(value_type = Option<String>, format = DateTime)]
     pub expires_at: Option<DateTime>,
+    #[serde(default)]
+    pub kind: TokenKind,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<Scope>,
+    #[serde(default)]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub last_used_at: Option<DateTime>,
+    /// Client address and user agent the token was created from.
+    #[serde(default)]
+    pub ip: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Session created with "remember me"; lives for the longer remembered TTL.
+    #[serde(default)]
+    pub remember: bool,
+}
+
+impl Token {
+    /// Sessions may do anything the user can; personal tokens only what their scopes allow.
+    pub fn allows(&self, scope: Scope) -> bool {
+        match self.kind {
+            TokenKind::Session => true,
+            TokenKind::Personal => self.scopes.iter().any(|s| *s >= scope),
+        }
+    }
+}
+
+fn verified_by_default() -> bool {
+    true
 }
 
-#[derive(Serialize, Deserialize, Debug, utoipa::ToSchema)]
+#[derive(Serialize, Deserialize, Debug, Clone, utoipa::ToSchema)]
 pub struct User {
     #[schema(value_type = String)]
     pub _id: ObjectId,
     pub username: String,
     pub email: String,
+    /// Accounts created before verification existed count as verified.
+    #[serde(default = "verified_by_default")]
+    pub email_verified: bool,
     pub password: String,
     pub display_name: String,
     pub avatar_url: Option<String>,
     #[schema(value_type = String, format = DateTime)]
     pub created_at: DateTime,
+    /// Base32 TOTP secret; set during enrollment and only trusted once `totp_enabled`.
+    #[serde(default)]
+    #[schema(ignore)]
+    pub totp_secret: Option<String>,
+    #[serde(default)]
+    pub totp_enabled: bool,
+    /// SHA-256 hashes of the unused recovery codes.
+    #[serde(default)]
+    #[schema(ignore)]
+    pub recovery_codes: Vec<String>,
+    /// Site administrator; `ADMIN_USERS` grants the same without this flag.
+    #[serde(default)]
+    pub admin: bool,
+    /// Suspended accounts cannot sign in or use git; their content stays in place.
+    #[serde(default)]
+    pub suspended: bool,
 }
 
+/// Single-use password reset link; only the hash of the emailed token is stored.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PasswordReset {
+    pub _id: ObjectId,
+    pub user: ObjectId,
+    pub token_hash: String,
+    pub created_at: DateTime,
+    pub expires_at: DateTime,
+}
+
+/// Pending confirmation that `email` belongs to `user`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EmailVerification {
+    pub _id: ObjectId,
+    pub user: ObjectId,
+    pub email: String,
+    pub token_hash: String,
+    pub created_at: DateTime,
+    pub expires_at: DateTime,
+}
 
+/// Failed sign-in counter for an account (`user:`/`login:` key) or a client address (`ip:` key).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoginAttempt {
+    pub _id: ObjectId,
+    pub key: String,
+    pub failures: i32,
+    pub locked_until: Option<DateTime>,
+    pub last_failure: DateTime,
+    pub expires_at: DateTime,
+}
+
+/// Registration invite; only the hash of the code is stored.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Invite {
+    pub _id: ObjectId,
+    pub code_hash: String,
+    pub created_by: ObjectId,
+    pub note: Option<String>,
+    pub max_uses: i32,
+    pub uses: i32,
+    /// Accounts registered with this invite.
+    #[serde(default)]
+    pub used_by: Vec<ObjectId>,
+    pub created_at: DateTime,
+    pub expires_at: Option<DateTime>,
+}
 
+/// Account at an OpenID Connect provider linked to a user.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Identity {
+    pub _id: ObjectId,
+    pub user: ObjectId,
+    /// Provider id from `OIDC_PROVIDERS`.
+    pub provider: String,
+    /// The provider's `sub` claim.
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: DateTime,
+    pub last_used_at: Option<DateTime>,
+}
+
+/// SSH public key a user authenticates git over SSH with.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SshKey {
+    pub _id: ObjectId,
+    pub user: ObjectId,
+    pub name: String,
+    /// `SHA256:...` fingerprint; unique, so a key always identifies one account.
+    pub fingerprint: String,
+    /// OpenSSH encoding without the comment.
+    pub public_key: String,
+    pub created_at: DateTime,
+    pub last_used_at: Option<DateTime>,
+}
+
+/// SSH key that grants git access to a single repository instead of an account.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeployKey {
+    pub _id: ObjectId,
+    pub repo: ObjectId,
+    pub name: String,
+    /// `SHA256:...` fingerprint; also never used by an account key.
+    pub fingerprint: String,
+    pub public_key: String,
+    /// Deploy keys are read-only unless this is set.
+    pub write: bool,
+    pub created_by: ObjectId,
+    pub created_at: DateTime,
+    pub last_used_at: Option<DateTime>,
+}
+
+/// Token that grants git access over HTTP to a single repository, e.g. for CI.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeployToken {
+    pub _id: ObjectId,
+    pub repo: ObjectId,
+    pub name: String,
+    pub prefix: String,
+    /// SHA-256 of the full token, like `Token::token_hash`.
+    pub token_hash: String,
+    /// Deploy tokens are read-only unless this is set.
+    pub write: bool,
+    pub created_by: ObjectId,
+    pub created_at: DateTime,
+    /// Expired tokens are removed by a TTL index.
+    pub expires_at: Option<DateTime>,
+    pub last_used_at: Option<DateTime>,
+}
+
+/// Single sign-on in progress, found again by its `state` on the callback.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OidcLogin {
+    pub _id: ObjectId,
+    pub state: String,
+    pub provider: String,
+    pub nonce: String,
+    pub verifier: String,
+    /// Set when a signed-in user is linking the identity instead of signing in.
+    pub link_user: Option<ObjectId>,
+    pub created_at: DateTime,
+    pub expires_at: DateTime,
+}
+
+/// Former `/{owner}` name kept after a rename so old links and clone URLs keep working.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NameRedirect {
+    pub _id: ObjectId,
+    pub name: String,
+    pub owner: ObjectId,
+    pub created_at: DateTime,
+}
+
+/// Former `/{owner}/{repo}` path kept after a repository is renamed or transferred, so
+/// old links and clone URLs keep resolving to it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RepoRedirect {
+    pub _id: ObjectId,
+    pub owner: ObjectId,
+    pub name: String,
+    pub repo: ObjectId,
+    pub created_at: DateTime,
+}
+
+/// A password-verified login waiting for its second factor.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoginChallenge {
+    pub _id: ObjectId,
+    pub user: ObjectId,
+    pub created_at: DateTime,
+    pub expires_at: DateTime,
+    #[serde(default)]
+    pub remember: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Read,
+    Write,
+    Admin,
+}
+
+impl Role {
+    pub fn parse(s: &str) -> Option<Role> {
+        match s {
+            "read" => Some(Role::Read),
+            "write" => Some(Role::Write),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Read => "read",
+            Role::Write => "write",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, utoipa::ToSchema)]
+pub struct Collaborator {
+    #[schema(value_type = String)]
+    pub _id: ObjectId,
+    #[schema(value_type = String)]
+    pub repo: ObjectId,
+    #[schema(value_type = String)]
+    pub user: ObjectId,
+    pub role: Role,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, utoipa::ToSchema)]
+pub struct Organization {
+    #[schema(value_type = String)]
+    pub _id: ObjectId,
+    pub name: String,
+    pub display_name: String,
+    pub description: String,
+    pub avatar_url: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OrgRole {
+    Member,
+    Owner,
+}
+
+impl OrgRole {
+    pub fn parse(s: &str) -> Option<OrgRole> {
+        match s {
+            "member" => Some(OrgRole::Member),
+            "owner" => Some(OrgRole::Owner),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrgRole::Member => "member",
+            OrgRole::Owner => "owner",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, utoipa::ToSchema)]
+pub struct OrgMember {
+    #[schema(value_type = String)]
+    pub _id: ObjectId,
+    #[schema(value_type = String)]
+    pub org: ObjectId,
+    #[schema(value_type = String)]
+    pub user: ObjectId,
+    pub role: OrgRole,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, utoipa::ToSchema)]
+pub struct Team {
+    #[schema(value_type = String)]
+    pub _id: ObjectId,
+    #[schema(value_type = String)]
+    pub org: ObjectId,
+    pub name: String,
+    pub description: String,
+    /// Role granted to members on every repository of the team
+    pub permission: Role,
+    #[schema(value_type = Vec<String>)]
+    pub members: Vec<ObjectId>,
+    #[schema(value_type = Vec<String>)]
+    pub repos: Vec<ObjectId>,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime,
+}
+
+/// Namespace a repository lives under: either a user or an organization.
+#[derive(Debug, Clone)]
+pub enum Owner {
+    User(User),
+    Org(Organization),
+}
+
+impl Owner {
+    pub fn id(&self) -> &ObjectId {
+        match self {
+            Owner::User(u) => &u._id,
+            Owner::Org(o) => &o._id,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Owner::User(u) => &u.username,
+            Owner::Org(o) => &o.name,
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        match self {
+            Owner::User(u) if !u.display_name.is_empty() => &u.display_name,
+            Owner::Org(o) if !o.display_name.is_empty() => &o.display_name,
+            _ => self.name(),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use futures_util::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::bson::oid::ObjectId;
+use mongodb::results::InsertOneResult;
+use tracing::info;
+use crate::db;
+
+impl super::Database {
+    pub async fn create_org(&self, org: db::Organization) -> mongodb::error::Result<InsertOneResult> {
+        let result = self.organizations.insert_one(org).await?;
+        info!("Created organization: {:?}", result);
+        Ok(result)
+    }
+
+    pub async fn find_org_by_name(&self, name: &str) -> mongodb::error::Result<Option<db::Organization>> {
+        self.organizations.find_one(doc! { "name": name }).await
+    }
+
+    pub async fn find_org_by_id(&self, id: &ObjectId) -> mongodb::error::Result<Option<db::Organization>> {
+        self.organizations.find_one(doc! { "_id": id }).await
+    }
+
+    /// Resolves a `/{owner}` namespace to a user or an organization.
+    pub async fn find_owner_by_name(&self, name: &str) -> mongodb::error::Result<Option<db::Owner>> {
+        if let Some(user) = self.find_user_by_login(name).await? {
+            return Ok(Some(db::Owner::User(user)));
+        }
+        Ok(self.find_org_by_name(name).await?.map(db::Owner::Org))
+    }
+
+    /// Follows a former name to the owner that now carries a different one.
+    pub async fn find_renamed_owner(&self, old_name: &str) -> mongodb::error::Result<Option<db::Owner>> {
+        match self.name_redirects.find_one(doc! { "name": old_name }).await? {
+            Some(r) => self.find_owner_by_id(&r.owner).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Points `old_name` at `owner`, replacing any earlier redirect for that name.
+    pub async fn set_name_redirect(&self, old_name: &str, owner: &ObjectId) -> mongodb::error::Result<()> {
+        self.name_redirects
+            .update_one(
+                doc! { "name": old_name },
+                doc! {
+                    "$set": { "owner": owner },
+                    "$setOnInsert": { "_id": ObjectId::new(), "created_at": bson::DateTime::now() },
+                },
+            )
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    /// Drops the redirect for a name that is being taken by a new owner.
+    pub async fn delete_name_redirect(&self, name: &str) -> mongodb::error::Result<u64> {
+        let res = self.name_redirects.delete_one(doc! { "name": name }).await?;
+        Ok(res.deleted_count)
+    }
+
+    pub async fn find_owner_by_id(&self, id: &ObjectId) -> mongodb::error::Result<Option<db::Owner>> {
+        if let Some(user) = self.find_user_by_id(id).await? {
+            return Ok(Some(db::Owner::User(user)));
+        }
+        Ok(self.find_org_by_id(id).await?.map(db::Owner::Org))
+    }
+
+    pub async fn find_org_member(&self, org_id: &ObjectId, user_id: &ObjectId) -> mongodb::error::Result<Option<db::OrgMember>> {
+        self.org_members.find_one(doc! { "org": org_id, "user": user_id }).await
+    }
+
+    pub async fn list_org_members(&self, org_id: &ObjectId) -> mongodb::error::Result<Vec<db::OrgMember>> {
+        let cursor = self.org_members.find(doc! { "org": org_id }).sort(doc! { "created_at": 1 }).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn set_org_member(&self, org_id: &ObjectId, user_id: &ObjectId, role: db::OrgRole) -> mongodb::error::Result<()> {
+        let res = self
+            .org_members
+            .update_one(
+                doc! { "org": org_id, "user": user_id },
+                doc! {
+                    "$set": { "role": role.as_str() },
+                    "$setOnInsert": { "_id": ObjectId::new(), "created_at": bson::DateTime::now() },
+                },
+            )
+            .upsert(true)
+            .await?;
+        info!("Set organization member: {:?}", res);
+        Ok(())
+    }
+
+    pub async fn delete_org_member(&self, org_id: &ObjectId, user_id: &ObjectId) -> mongodb::error::Result<u64> {
+        let res = self.org_members.delete_one(doc! { "org": org_id, "user": user_id }).await?;
+        self.teams
+            .update_many(doc! { "org": org_id }, doc! { "$pull": { "members": user_id } })
+            .await?;
+        Ok(res.deleted_count)
+    }
+
+    /// Organizations the user belongs to, with their role in each.
+    pub async fn orgs_for_user(&self, user_id: &ObjectId) -> mongodb::error::Result<Vec<(db::Organization, db::OrgRole)>> {
+        let cursor = self.org_members.find(doc! { "user": user_id }).await?;
+        let memberships: Vec<db::OrgMember> = cursor.try_collect().await?;
+        let mut out = Vec::new();
+        for m in memberships {
+            if let Some(org) = self.find_org_by_id(&m.org).await? {
+                out.push((org, m.role));
+            }
+        }
+        Ok(out)
+    }
+
+    pub async fn create_team(&self, team: db::Team) -> mongodb::error::Result<InsertOneResult> {
+        let result = self.teams.insert_one(team).await?;
+        info!("Created team: {:?}", result);
+        Ok(result)
+    }
+
+    pub async fn find_team(&self, org_id: &ObjectId, name: &str) -> mongodb::error::Result<Option<db::Team>> {
+        self.teams.find_one(doc! { "org": org_id, "name": name }).await
+    }
+
+    pub async fn list_teams(&self, org_id: &ObjectId) -> mongodb::error::Result<Vec<db::Team>> {
+        let cursor = self.teams.find(doc! { "org": org_id }).sort(doc! { "name": 1 }).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn delete_team(&self, team_id: &ObjectId) -> mongodb::error::Result<u64> {
+        let res = self.teams.delete_one(doc! { "_id": team_id }).await?;
+        Ok(res.deleted_count)
+    }
+
+    pub async fn team_add_member(&self, team_id: &ObjectId, user_id: &ObjectId) -> mongodb::error::Result<()> {
+        self.teams
+            .update_one(doc! { "_id": team_id }, doc! { "$addToSet": { "members": user_id } })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn team_remove_member(&self, team_id: &ObjectId, user_id: &ObjectId) -> mongodb::error::Result<u64> {
+        let res = self
+            .teams
+            .update_one(doc! { "_id": team_id }, doc! { "$pull": { "members": user_id } })
+            .await?;
+        Ok(res.modified_count)
+    }
+
+    pub async fn team_add_repo(&self, team_id: &ObjectId, repo_id: &ObjectId) -> mongodb::error::Result<()> {
+        self.teams
+            .update_one(doc! { "_id": team_id }, doc! { "$addToSet": { "repos": repo_id } })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn team_remove_repo(&self, team_id: &ObjectId, repo_id: &ObjectId) -> mongodb::error::Result<u64> {
+        let res = self
+            .teams
+            .update_one(doc! { "_id": team_id }, doc! { "$pull": { "repos": repo_id } })
+            .await?;
+        Ok(res.modified_count)
+    }
+
+    pub async fn pull_repo_from_teams(&self, repo_id: &ObjectId) -> mongodb::error::Result<()> {
+        self.teams
+            .update_many(doc! { "repos": repo_id }, doc! { "$pull": { "repos": repo_id } })
+            .await?;
+        Ok(())
+    }
+
+    /// Teams the user is a member of that grant access to the repository.
+    pub async fn teams_for_user_and_repo(&self, user_id: &ObjectId, repo_id: &ObjectId) -> mongodb::error::Result<Vec<db::Team>> {
+        let cursor = self.teams.find(doc! { "members": user_id, "repos": repo_id }).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn team_repo_ids(&self, user_id: &ObjectId) -> mongodb::error::Result<Vec<ObjectId>> {
+        let cursor = self.teams.find(doc! { "members": user_id }).await?;
+        let teams: Vec<db::Team> = cursor.try_collect().await?;
+        Ok(teams.into_iter().flat_map(|t| t.repos).collect())
+    }
+}
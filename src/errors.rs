@@ -9,6 +9,20 @@ pub enum AuthError {
     InvalidCredentials,
     #[error("Registration disabled, please contact the administrator.")]
     RegistrationDisabled,
+    #[error("Invalid or expired invite code")]
+    InvalidInvite,
+    #[error("Two-factor authentication code required")]
+    OtpRequired,
+    #[error("Two-factor authentication is already enabled")]
+    OtpAlreadyEnabled,
+    #[error("Password must be between 8 and 128 characters")]
+    InvalidPassword,
+    #[error("Single sign-on failed: {0}")]
+    Sso(String),
+    #[error("This account has been suspended")]
+    Suspended,
+    #[error("Too many failed login attempts, try again in {0} seconds")]
+    TooManyAttempts(i64),
     #[error("Internal Server Error: {0}")]
     Internal(String),
 }
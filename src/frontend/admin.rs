@@ -0,0 +1,446 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+use actix_web::http::{header::LOCATION, StatusCode};
+use bson::oid::ObjectId;
+use maud::{html, Markup};
+
+use crate::api::service;
+use crate::db::Database;
+use crate::frontend::errors::render_error_page;
+use crate::frontend::repo::utils;
+use crate::frontend::{components, url_encode};
+use crate::models::{AdminRepoInfo, AdminSearchQuery, AdminUserInfo, SuspendUserRequest, TransferRepoRequest};
+
+#[derive(serde::Deserialize)]
+pub struct ListQuery {
+    q: Option<String>,
+    page: Option<u64>,
+    error: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct MessageQuery {
+    error: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SuspendForm {
+    pub suspended: bool,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ConfirmForm {
+    pub confirm_name: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TransferForm {
+    pub owner: String,
+}
+
+#[derive(PartialEq, Eq)]
+enum Section {
+    Overview,
+    Users,
+    Repos,
+}
+
+fn admin_nav(active: Section) -> Markup {
+    html! {
+        nav class="settings-nav" {
+            a.active[active == Section::Overview] href="/admin" { "Overview" }
+            a.active[active == Section::Users] href="/admin/users" { "Users" }
+            a.active[active == Section::Repos] href="/admin/repos" { "Repositories" }
+        }
+    }
+}
+
+fn error_text(msg: &str) -> &str {
+    match msg {
+        "cannot modify your own account" => "You cannot suspend or delete your own account",
+        "already exists" => "The new owner already has a repository with this name",
+        "owner not found" => "No user or organization with this name",
+        "user not found" => "User not found",
+        "repository not found" => "Repository not found",
+        "organization must keep at least one owner" => "The user is the last owner of an organization; make someone else an owner first",
+        _ => "Something went wrong",
+    }
+}
+
+fn format_date(dt: &bson::DateTime) -> String {
+    utils::format_time(dt.timestamp_millis() / 1000).chars().take(10).collect()
+}
+
+fn redirect(location: String) -> HttpResponse {
+    HttpResponse::SeeOther().insert_header((LOCATION, location)).finish()
+}
+
+fn with_error(location: &str, msg: &str) -> HttpResponse {
+    redirect(format!("{}?error={}", location, url_encode(error_text(msg))))
+}
+
+/// The signed-in administrator and their display name; anyone else gets a login
+/// redirect or a 403 page.
+async fn require_admin(db: &Database, req: &HttpRequest) -> std::result::Result<(ObjectId, String), HttpResponse> {
+    let user = match utils::requester_id(db, req).await {
+        Some(uid) => db.find_user_by_id(&uid).await.ok().flatten(),
+        None => None,
+    };
+    match user {
+        Some(u) if crate::auth::is_admin(&u) => Ok((u._id, u.display_name)),
+        Some(_) => Err(render_error_page(
+            "403 - Forbidden",
+            "Forbidden",
+            "Only site administrators can open this page.",
+            StatusCode::FORBIDDEN,
+        )),
+        None => Err(redirect("/login".to_string())),
+    }
+}
+
+fn page(title: &str, active: Section, body: Markup, user_display: &str) -> HttpResponse {
+    let content = html! {
+        div class="container settings-layout" {
+            (admin_nav(active))
+            div class="settings-content" { (body) }
+        }
+    };
+    let page = utils::page_shell(&format!("{} - Site administration", title), content, Some(user_display));
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string())
+}
+
+fn search_form(action: &str, q: Option<&str>, placeholder: &str) -> Markup {
+    html! {
+        form class="settings-form" method="get" action=(action) {
+            div class="form-row" {
+                input type="text" name="q" value=(q.unwrap_or("")) placeholder=(placeholder) {}
+                button type="submit" class="action-btn" { "Search" }
+            }
+        }
+    }
+}
+
+/// Previous/next links; a full page suggests there may be more.
+fn pager(action: &str, q: Option<&str>, page: u64, count: usize) -> Markup {
+    let link = |p: u64| format!("{}?q={}&page={}", action, url_encode(q.unwrap_or("")), p);
+    html! {
+        p class="muted" {
+            @if page > 1 { a href=(link(page - 1)) { "Previous" } " " }
+            @if count >= 50 { a href=(link(page + 1)) { "Next" } }
+        }
+    }
+}
+
+#[get("/admin")]
+pub async fn overview(db: web::Data<Database>, req: HttpRequest) -> Result<HttpResponse> {
+    let (requester, user_display) = match require_admin(&db, &req).await {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    let stats = service::admin_stats(&db, requester)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    let body = html! {
+        h2 { "Site administration" }
+        ul class="settings-list" {
+            li { span class="grow" { "Users" } strong { (stats.users) } }
+            li { span class="grow" { "Suspended users" } strong { (stats.suspended_users) } }
+            li { span class="grow" { "Repositories" } strong { (stats.repositories) } }
+            li { span class="grow" { "Private repositories" } strong { (stats.private_repositories) } }
+            li { span class="grow" { "Organizations" } strong { (stats.organizations) } }
+            li { span class="grow" { "GitLit version" } strong { (stats.version) } }
+        }
+    };
+    Ok(page("Overview", Section::Overview, body, &user_display))
+}
+
+fn user_badges(u: &AdminUserInfo) -> Markup {
+    html! {
+        @if u.admin { span class="badge" { "admin" } " " }
+        @if u.suspended { span class="badge" { "suspended" } " " }
+        @if !u.email_verified { span class="badge" { "unverified" } " " }
+    }
+}
+
+#[get("/admin/users")]
+pub async fn user_list(db: web::Data<Database>, req: HttpRequest, query: web::Query<ListQuery>) -> Result<HttpResponse> {
+    let (requester, user_display) = match require_admin(&db, &req).await {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    let page_no = query.page.unwrap_or(1).max(1);
+    let search = AdminSearchQuery { q: query.q.clone(), page: Some(page_no) };
+    let list = service::admin_users(&db, requester, search)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    let body = html! {
+        h2 { "Users" }
+        @if let Some(err) = &query.error {
+            (components::alert(components::AlertKind::Error, err))
+        }
+        (search_form("/admin/users", query.q.as_deref(), "Username, name or email"))
+        @if list.is_empty() {
+            p class="muted" { "No users found." }
+        } @else {
+            ul class="settings-list" {
+                @for u in &list {
+                    li {
+                        span class="grow" {
+                            a href={(format!("/admin/users/{}", u.username))} { strong { (u.display_name) } " " span class="muted" { "@" (u.username) } }
+                            " " (user_badges(u))
+                            br;
+                            span class="muted" { (u.email) " · joined " (format_date(&u.created_at)) }
+                        }
+                    }
+                }
+            }
+        }
+        (pager("/admin/users", query.q.as_deref(), page_no, list.len()))
+    };
+    Ok(page("Users", Section::Users, body, &user_display))
+}
+
+#[get("/admin/users/{username}")]
+pub async fn user_detail(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<MessageQuery>,
+) -> Result<HttpResponse> {
+    let (requester, user_display) = match require_admin(&db, &req).await {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    let u = match service::admin_user(&db, requester, &path.into_inner()).await {
+        Ok(u) => u,
+        Err(msg) => return Ok(with_error("/admin/users", &msg)),
+    };
+    let base = format!("/admin/users/{}", u.username);
+    let is_self = u.id == requester.to_hex();
+
+    let body = html! {
+        h2 { (u.display_name) " " span class="muted" { "@" (u.username) } }
+        @if let Some(err) = &query.error {
+            (components::alert(components::AlertKind::Error, err))
+        }
+        p { (user_badges(&u)) }
+        ul class="settings-list" {
+            li { span class="grow" { "Email" } (u.email) }
+            li { span class="grow" { "Joined" } (format_date(&u.created_at)) }
+            li { span class="grow" { "Two-factor authentication" } @if u.totp_enabled { "Enabled" } @else { "Disabled" } }
+            li { span class="grow" { "Profile" } a href={(format!("/{}", u.username))} { "/" (u.username) } }
+        }
+        @if is_self {
+            p class="muted" { "You cannot suspend or delete your own account." }
+        } @else {
+            form class="settings-form" method="post" action={(format!("{}/suspend", base))} {
+                h3 { "Suspension" }
+                @if u.suspended {
+                    p class="help" { "The account is suspended: it cannot sign in, use tokens or access git." }
+                    input type="hidden" name="suspended" value="false" {}
+                    button type="submit" class="action-btn" { "Unsuspend" }
+                } @else {
+                    p class="help" { "Suspending signs the user out everywhere and blocks sign-in and git access. Their repositories stay in place." }
+                    input type="hidden" name="suspended" value="true" {}
+                    button type="submit" class="action-btn" { "Suspend" }
+                }
+            }
+            form class="settings-form danger-zone" method="post" action={(format!("{}/delete", base))} {
+                h3 { "Delete user" }
+                p class="help" { "Deletes the account and every repository it owns, and revokes the invites, deploy keys and deploy tokens it created. This cannot be undone. Type the username to confirm." }
+                input type="text" name="confirm_name" placeholder=(u.username) required {}
+                button type="submit" class="branch-delete-btn" { "Delete user" }
+            }
+        }
+    };
+    Ok(page(&u.username, Section::Users, body, &user_display))
+}
+
+#[post("/admin/users/{username}/suspend")]
+pub async fn suspend_user(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    form: web::Form<SuspendForm>,
+) -> Result<HttpResponse> {
+    let (requester, _) = match require_admin(&db, &req).await {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    let username = path.into_inner();
+    let back = format!("/admin/users/{}", username);
+    let payload = SuspendUserRequest { username, suspended: form.suspended };
+    match service::admin_set_suspended(&db, requester, payload).await {
+        Ok(()) => Ok(redirect(back)),
+        Err(msg) => Ok(with_error(&back, &msg)),
+    }
+}
+
+#[post("/admin/users/{username}/delete")]
+pub async fn delete_user(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    form: web::Form<ConfirmForm>,
+) -> Result<HttpResponse> {
+    let (requester, _) = match require_admin(&db, &req).await {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    let username = path.into_inner();
+    let back = format!("/admin/users/{}", username);
+    if form.confirm_name.trim() != username {
+        return Ok(redirect(format!("{}?error={}", back, url_encode("The username does not match"))));
+    }
+    match service::admin_delete_user(&db, requester, &username).await {
+        Ok(()) => Ok(redirect("/admin/users".to_string())),
+        Err(msg) => Ok(with_error(&back, &msg)),
+    }
+}
+
+fn repo_row(r: &AdminRepoInfo) -> Markup {
+    html! {
+        li {
+            span class="grow" {
+                a href={(format!("/admin/repos/{}", r.id))} { strong { (r.owner) "/" (r.name) } }
+                @if r.is_private { " " span class="badge" { "private" } }
+                br;
+                span class="muted" { "Created " (format_date(&r.created_at)) " · updated " (format_date(&r.updated_at)) }
+            }
+        }
+    }
+}
+
+#[get("/admin/repos")]
+pub async fn repo_list(db: web::Data<Database>, req: HttpRequest, query: web::Query<ListQuery>) -> Result<HttpResponse> {
+    let (requester, user_display) = match require_admin(&db, &req).await {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    let page_no = query.page.unwrap_or(1).max(1);
+    let search = AdminSearchQuery { q: query.q.clone(), page: Some(page_no) };
+    let list = service::admin_repos(&db, requester, search)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    let body = html! {
+        h2 { "Repositories" }
+        @if let Some(err) = &query.error {
+            (components::alert(components::AlertKind::Error, err))
+        }
+        (search_form("/admin/repos", query.q.as_deref(), "Name or description"))
+        @if list.is_empty() {
+            p class="muted" { "No repositories found." }
+        } @else {
+            ul class="settings-list" {
+                @for r in &list { (repo_row(r)) }
+            }
+        }
+        (pager("/admin/repos", query.q.as_deref(), page_no, list.len()))
+    };
+    Ok(page("Repositories", Section::Repos, body, &user_display))
+}
+
+#[get("/admin/repos/{id}")]
+pub async fn repo_detail(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<MessageQuery>,
+) -> Result<HttpResponse> {
+    let (_, user_display) = match require_admin(&db, &req).await {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    let repository = match db.find_repo_by_hex(&path.into_inner()).await {
+        Ok(Some(r)) => r,
+        _ => return Ok(with_error("/admin/repos", "repository not found")),
+    };
+    let owner = db
+        .find_owner_by_id(&repository.user)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .map(|o| o.name().to_string())
+        .unwrap_or_default();
+    let full_name = format!("{}/{}", owner, repository.name);
+    let base = format!("/admin/repos/{}", repository._id.to_hex());
+
+    let body = html! {
+        h2 { (full_name) @if repository.is_private { " " span class="badge" { "private" } } }
+        @if let Some(err) = &query.error {
+            (components::alert(components::AlertKind::Error, err))
+        }
+        @if !repository.description.is_empty() {
+            p class="muted" { (repository.description) }
+        }
+        p { a href={(format!("/{}", full_name))} { "View repository" } }
+        form class="settings-form" method="post" action={(format!("{}/transfer", base))} {
+            h3 { "Transfer" }
+            p class="help" { "Moves the repository to another user or organization. Collaborators and team access are removed." }
+            div class="form-row" {
+                input type="text" name="owner" placeholder="New owner" required {}
+                button type="submit" class="action-btn" { "Transfer" }
+            }
+        }
+        form class="settings-form danger-zone" method="post" action={(format!("{}/delete", base))} {
+            h3 { "Delete repository" }
+            p class="help" { "This cannot be undone. Type " code { (full_name) } " to confirm." }
+            input type="text" name="confirm_name" placeholder=(full_name) required {}
+            button type="submit" class="branch-delete-btn" { "Delete repository" }
+        }
+    };
+    Ok(page(&full_name, Section::Repos, body, &user_display))
+}
+
+#[post("/admin/repos/{id}/transfer")]
+pub async fn transfer_repo(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    form: web::Form<TransferForm>,
+) -> Result<HttpResponse> {
+    let (requester, _) = match require_admin(&db, &req).await {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    let id = path.into_inner();
+    let back = format!("/admin/repos/{}", id);
+    let payload = TransferRepoRequest { id, owner: form.owner.clone() };
+    match service::admin_transfer_repo(&db, requester, payload).await {
+        Ok(()) => Ok(redirect(back)),
+        Err(msg) => Ok(with_error(&back, &msg)),
+    }
+}
+
+#[post("/admin/repos/{id}/delete")]
+pub async fn delete_repo(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    form: web::Form<ConfirmForm>,
+) -> Result<HttpResponse> {
+    let (requester, _) = match require_admin(&db, &req).await {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    let id = path.into_inner();
+    let back = format!("/admin/repos/{}", id);
+    let repository = match db.find_repo_by_hex(&id).await {
+        Ok(Some(r)) => r,
+        _ => return Ok(with_error("/admin/repos", "repository not found")),
+    };
+    let owner = db
+        .find_owner_by_id(&repository.user)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .map(|o| o.name().to_string())
+        .unwrap_or_default();
+    if form.confirm_name.trim() != format!("{}/{}", owner, repository.name) {
+        return Ok(redirect(format!("{}?error={}", back, url_encode("The repository name does not match"))));
+    }
+    match service::admin_delete_repo(&db, requester, &id).await {
+        Ok(()) => Ok(redirect("/admin/repos".to_string())),
+        Err(msg) => Ok(with_error(&back, &msg)),
+    }
+}
@@ -1,22 +1,55 @@
 use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
 use actix_web::http::header::LOCATION;
 use actix_web::cookie::{Cookie, SameSite, time::Duration};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
 use maud::{DOCTYPE, html};
 use crate::db::Database;
 use crate::api::service;
 use crate::frontend::components;
-use crate::frontend::SERVE_PATH;
-use std::env;
+use crate::frontend::{url_encode, SERVE_PATH};
+use crate::auth_backend::AuthBackend;
+use crate::captcha::Captcha;
+use crate::oidc::Oidc;
+use crate::mail::Mailer;
+use crate::models::CaptchaResponse;
 use crate::errors::AuthError;
-use reqwest::Client;
+use crate::auth::{ClientInfo, LoginStep, RegistrationMode, SsoOutcome};
+use bson::oid::ObjectId;
 
 #[derive(serde::Deserialize)]
 struct ErrorQuery { error: Option<String> }
 
+#[derive(serde::Deserialize)]
+struct ResetQuery {
+    token: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ForgotForm {
+    pub login: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetForm {
+    pub token: String,
+    pub password: String,
+    pub confirm_password: String,
+}
+
 #[derive(serde::Deserialize)]
 pub struct LoginForm {
     pub login: String,
     pub password: String,
+    /// Checkbox; present ("on") when ticked
+    pub remember: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct OtpForm {
+    pub code: String,
 }
 
 #[derive(serde::Deserialize)]
@@ -24,8 +57,9 @@ pub struct RegisterForm {
     pub username: String,
     pub email: String,
     pub password: String,
-    #[serde(rename = "g-recaptcha-response")]
-    pub recaptcha_response: Option<String>,
+    pub invite: Option<String>,
+    #[serde(flatten)]
+    pub captcha: CaptchaResponse,
 }
 
 async fn redirect_if_logged(db: &Database, req: &HttpRequest) -> Option<HttpResponse> {
@@ -40,61 +74,8 @@ async fn redirect_if_logged(db: &Database, req: &HttpRequest) -> Option<HttpResp
     None
 }
 
-fn recaptcha_enabled() -> bool {
-    env::var("RECAPTCHA_ENABLED").unwrap_or_else(|_| "false".to_string()) == "true"
-}
-
-fn recaptcha_site_key() -> Option<String> {
-    env::var("RECAPTCHA_SITE_KEY").ok()
-}
-
-async fn verify_recaptcha_token(token: &str, remote_ip: Option<&str>) -> bool {
-    let secret = match env::var("RECAPTCHA_SECRET") {
-        Ok(s) => s,
-        Err(_) => return false,
-    };
-
-    #[derive(serde::Deserialize)]
-    struct VerifyResp {
-        success: bool,
-        #[allow(dead_code)]
-        #[serde(default)]
-        error_codes: Option<Vec<String>>,
-    }
-
-    #[derive(serde::Serialize)]
-    struct RecaptchaRequest {
-        secret: String,
-        response: String,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        remoteip: Option<String>,
-    }
-
-    let client = Client::new();
-    let req = RecaptchaRequest {
-        secret,
-        response: token.to_string(),
-        remoteip: remote_ip.map(|s| s.to_string()),
-    };
-
-    match client
-        .post("https://www.google.com/recaptcha/api/siteverify")
-        .json(&req)
-        .send()
-        .await
-    {
-        Ok(resp) => {
-            match resp.json::<VerifyResp>().await {
-                Ok(v) => v.success,
-                Err(_) => false,
-            }
-        }
-        Err(_) => false,
-    }
-}
-
 #[get("/login")]
-pub async fn get_login(db: web::Data<Database>, req: HttpRequest, query: web::Query<ErrorQuery>) -> Result<HttpResponse> {
+pub async fn get_login(db: web::Data<Database>, oidc: web::Data<Oidc>, req: HttpRequest, query: web::Query<ErrorQuery>) -> Result<HttpResponse> {
     if let Some(resp) = redirect_if_logged(&db, &req).await {
         return Ok(resp);
     }
@@ -132,6 +113,14 @@ pub async fn get_login(db: web::Data<Database>, req: HttpRequest, query: web::Qu
                             }
                             button type="submit" class="auth-btn" { "Sign in" }
                         }
+                        @if !oidc.providers().is_empty() {
+                            div class="auth-divider" { span { "or" } }
+                            div class="auth-sso" {
+                                @for p in oidc.providers() {
+                                    a class="auth-btn-secondary" href=(format!("/login/oidc/{}", p.id)) { "Sign in with " (p.name) }
+                                }
+                            }
+                        }
                         div class="auth-footer" {
                             p { "New to GitLit? " a href="/register" { "Create an account" } }
                         }
@@ -145,47 +134,310 @@ pub async fn get_login(db: web::Data<Database>, req: HttpRequest, query: web::Qu
     Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string()))
 }
 
+/// Remembered sessions get a persistent cookie; others end with the browser session.
+fn session_cookie(token: String, remember: bool) -> Cookie<'static> {
+    let mut cookie = Cookie::build("token", token)
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .path("/")
+        .finish();
+    if remember {
+        cookie.set_max_age(Duration::seconds(crate::auth::session_ttl_secs(true)));
+    }
+    cookie
+}
+
+/// Ties the browser to a login waiting for its second factor.
+fn challenge_cookie(challenge: ObjectId) -> Cookie<'static> {
+    Cookie::build("login_challenge", challenge.to_hex())
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .path("/login")
+        .max_age(Duration::minutes(5))
+        .finish()
+}
+
+/// Middleware that slides the expiry of the browser session on activity and
+/// refreshes the "remember me" cookie along with it.
+pub async fn renew_session(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>> {
+    let token = req.cookie("token").map(|c| c.value().to_string());
+    let db = req.app_data::<web::Data<Database>>().cloned();
+    let is_static = req.path().starts_with(SERVE_PATH.as_str());
+
+    let mut res = next.call(req).await?;
+
+    if let (Some(token), Some(db), false) = (token, db, is_static) {
+        if let Ok(Some(renewed)) = service::session_renew(&db, &token).await {
+            if renewed.remember {
+                let _ = res.response_mut().add_cookie(&session_cookie(token, true));
+            }
+        }
+    }
+    Ok(res)
+}
+
 #[post("/login")]
-pub async fn post_login(db: web::Data<Database>, form: web::Form<LoginForm>) -> Result<HttpResponse> {
-    match service::auth_login(&db, form.login.clone(), form.password.clone()).await {
-        Ok(token) => {
-            let cookie = Cookie::build("token", token)
+pub async fn post_login(
+    db: web::Data<Database>,
+    backend: web::Data<dyn AuthBackend>,
+    req: HttpRequest,
+    form: web::Form<LoginForm>,
+) -> Result<HttpResponse> {
+    let client = ClientInfo::from_request(&req);
+    let remember = form.remember.is_some();
+    match service::auth_begin_login(&db, backend.get_ref(), form.login.clone(), form.password.clone(), remember, client).await {
+        Ok(LoginStep::Token(token)) => {
+            Ok(HttpResponse::SeeOther()
+                .insert_header((LOCATION, "/"))
+                .cookie(session_cookie(token, remember))
+                .finish())
+        }
+        Ok(LoginStep::SecondFactor(challenge)) => {
+            Ok(HttpResponse::SeeOther()
+                .insert_header((LOCATION, "/login/2fa"))
+                .cookie(challenge_cookie(challenge))
+                .finish())
+        }
+        Err(e @ (AuthError::TooManyAttempts(_) | AuthError::Suspended)) => {
+            Ok(HttpResponse::SeeOther()
+                .insert_header((LOCATION, format!("/login?error={}", url_encode(&e.to_string()))))
+                .finish())
+        }
+        Err(_) => {
+            Ok(HttpResponse::SeeOther()
+                .insert_header((LOCATION, "/login?error=Invalid%20credentials"))
+                .finish())
+        }
+    }
+}
+
+#[get("/login/2fa")]
+pub async fn get_login_otp(req: HttpRequest, query: web::Query<ErrorQuery>) -> Result<HttpResponse> {
+    if req.cookie("login_challenge").is_none() {
+        return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish());
+    }
+    let page = html! {
+        (DOCTYPE)
+        html lang="en" {
+            (components::head("Two-factor authentication - GitLit", html! {
+                link rel="stylesheet" href=(SERVE_PATH.to_string() + "/auth.css") {}
+            }))
+            (components::body(html! {
+                main class="auth-container" {
+                    div class="auth-card" {
+                        div class="auth-header" {
+                            h1 { "Two-factor authentication" }
+                        }
+                        @if let Some(err) = &query.error {
+                            (components::alert(components::AlertKind::Error, err))
+                        }
+                        form class="auth-form" method="post" action="/login/2fa" {
+                            div class="form-group" {
+                                label for="code" { "Authentication code" }
+                                input type="text" id="code" name="code" inputmode="numeric" autocomplete="one-time-code" autofocus required {}
+                                div class="input-help" { "Enter the code from your authenticator app, or one of your recovery codes." }
+                            }
+                            button type="submit" class="auth-btn" { "Verify" }
+                        }
+                        div class="auth-footer" {
+                            p { a href="/login" { "Back to login" } }
+                        }
+                    }
+                }
+            }, None))
+        }
+    };
+
+    Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string()))
+}
+
+#[post("/login/2fa")]
+pub async fn post_login_otp(db: web::Data<Database>, req: HttpRequest, form: web::Form<OtpForm>) -> Result<HttpResponse> {
+    let challenge = match req.cookie("login_challenge") {
+        Some(c) => c.value().to_string(),
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+
+    match service::auth_finish_login(&db, &challenge, &form.code, ClientInfo::from_request(&req)).await {
+        Ok((token, remember)) => {
+            let removal = Cookie::build("login_challenge", "")
+                .path("/login")
                 .http_only(true)
-                .same_site(SameSite::Lax)
-                .path("/")
-                .max_age(Duration::seconds(24 * 60 * 60))
+                .max_age(Duration::seconds(0))
                 .finish();
 
             Ok(HttpResponse::SeeOther()
                 .insert_header((LOCATION, "/"))
-                .cookie(cookie)
+                .cookie(session_cookie(token, remember))
+                .cookie(removal)
+                .finish())
+        }
+        Err(e @ (AuthError::TooManyAttempts(_) | AuthError::Suspended)) => {
+            Ok(HttpResponse::SeeOther()
+                .insert_header((LOCATION, format!("/login/2fa?error={}", url_encode(&e.to_string()))))
                 .finish())
         }
         Err(_) => {
             Ok(HttpResponse::SeeOther()
-                .insert_header((LOCATION, "/login?error=Invalid%20credentials"))
+                .insert_header((LOCATION, "/login/2fa?error=Invalid%20code"))
                 .finish())
         }
     }
 }
 
+/// Standalone card page used by the password reset and email verification screens.
+fn auth_card_page(title: &str, heading: &str, content: maud::Markup) -> HttpResponse {
+    let page = html! {
+        (DOCTYPE)
+        html lang="en" {
+            (components::head(&format!("{} - GitLit", title), html! {
+                link rel="stylesheet" href=(SERVE_PATH.to_string() + "/auth.css") {}
+            }))
+            (components::body(html! {
+                main class="auth-container" {
+                    div class="auth-card" {
+                        div class="auth-header" {
+                            h1 { (heading) }
+                        }
+                        (content)
+                        div class="auth-footer" {
+                            p { a href="/login" { "Back to login" } }
+                        }
+                    }
+                }
+            }, None))
+        }
+    };
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string())
+}
+
+#[get("/forgot-password")]
+pub async fn get_forgot_password(query: web::Query<ErrorQuery>) -> Result<HttpResponse> {
+    Ok(auth_card_page("Forgot password", "Reset your password", html! {
+        @if let Some(err) = &query.error {
+            (components::alert(components::AlertKind::Error, err))
+        }
+        form class="auth-form" method="post" action="/forgot-password" {
+            div class="form-group" {
+                label for="login" { "Username or email" }
+                input type="text" id="login" name="login" autofocus required {}
+                div class="input-help" { "We will email you a link to choose a new password." }
+            }
+            button type="submit" class="auth-btn" { "Send reset link" }
+        }
+    }))
+}
+
+#[post("/forgot-password")]
+pub async fn post_forgot_password(
+    db: web::Data<Database>,
+    mailer: web::Data<dyn Mailer>,
+    form: web::Form<ForgotForm>,
+) -> Result<HttpResponse> {
+    if service::auth_request_password_reset(&db, mailer.get_ref(), &form.login).await.is_err() {
+        return Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, "/forgot-password?error=Something%20went%20wrong,%20please%20try%20again"))
+            .finish());
+    }
+    // same answer whether or not the account exists
+    Ok(auth_card_page("Forgot password", "Check your email", html! {
+        (components::alert(components::AlertKind::Success, "If an account matches, a reset link is on its way. The link expires in an hour."))
+    }))
+}
+
+#[get("/reset-password")]
+pub async fn get_reset_password(query: web::Query<ResetQuery>) -> Result<HttpResponse> {
+    let token = match &query.token {
+        Some(t) => t.clone(),
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/forgot-password")).finish()),
+    };
+    Ok(auth_card_page("Reset password", "Choose a new password", html! {
+        @if let Some(err) = &query.error {
+            (components::alert(components::AlertKind::Error, err))
+        }
+        form class="auth-form" method="post" action="/reset-password" {
+            input type="hidden" name="token" value=(token) {}
+            div class="form-group" {
+                label for="password" { "New password" }
+                input type="password" id="password" name="password" autocomplete="new-password" required {}
+                div class="input-help" { "Must be at least 8 characters long" }
+            }
+            div class="form-group" {
+                label for="confirm-password" { "Confirm password" }
+                input type="password" id="confirm-password" name="confirm_password" autocomplete="new-password" required {}
+            }
+            button type="submit" class="auth-btn" { "Change password" }
+        }
+    }))
+}
+
+#[post("/reset-password")]
+pub async fn post_reset_password(db: web::Data<Database>, form: web::Form<ResetForm>) -> Result<HttpResponse> {
+    let back = |msg: &str| {
+        HttpResponse::SeeOther()
+            .insert_header((LOCATION, format!("/reset-password?token={}&error={}", url_encode(&form.token), url_encode(msg))))
+            .finish()
+    };
+    if form.password != form.confirm_password {
+        return Ok(back("Passwords do not match"));
+    }
+    match service::auth_reset_password(&db, &form.token, &form.password).await {
+        Ok(()) => Ok(auth_card_page("Reset password", "Password changed", html! {
+            (components::alert(components::AlertKind::Success, "Your password was changed, all sessions were signed out and access tokens were revoked. You can now sign in with the new password."))
+        })),
+        Err(AuthError::InvalidPassword) => Ok(back(&AuthError::InvalidPassword.to_string())),
+        Err(AuthError::InvalidCredentials) => Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, "/forgot-password?error=This%20reset%20link%20is%20invalid%20or%20has%20expired"))
+            .finish()),
+        Err(_) => Ok(back("Something went wrong, please try again")),
+    }
+}
+
+#[get("/verify-email")]
+pub async fn get_verify_email(db: web::Data<Database>, query: web::Query<ResetQuery>) -> Result<HttpResponse> {
+    let verified = match &query.token {
+        Some(token) => service::auth_verify_email(&db, token).await.is_ok(),
+        None => false,
+    };
+    Ok(auth_card_page("Verify email", "Email verification", html! {
+        @if verified {
+            (components::alert(components::AlertKind::Success, "Your email address is verified."))
+        } @else {
+            (components::alert(components::AlertKind::Error, "This verification link is invalid or has expired. You can request a new one in your account settings."))
+        }
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RegisterQuery {
+    error: Option<String>,
+    invite: Option<String>,
+}
+
 #[get("/register")]
-pub async fn get_register(db: web::Data<Database>, req: HttpRequest, query: web::Query<ErrorQuery>) -> Result<HttpResponse> {
+pub async fn get_register(
+    db: web::Data<Database>,
+    captcha: web::Data<dyn Captcha>,
+    req: HttpRequest,
+    query: web::Query<RegisterQuery>,
+) -> Result<HttpResponse> {
     if let Some(resp) = redirect_if_logged(&db, &req).await {
         return Ok(resp);
     }
+    let mode = crate::auth::registration_mode();
     let page = html! {
         (DOCTYPE)
         html lang="en" {
             (components::head("Register - GitLit", html! {
                 link rel="stylesheet" href=(SERVE_PATH.to_string() + "/auth.css") {}
-                @if recaptcha_enabled() {
-                    @if let Some(_key) = recaptcha_site_key() {
-                        script src="https://www.google.com/recaptcha/api.js" { }
-                    }
+                @if mode != RegistrationMode::Closed {
+                    (captcha.head())
                 }
             }))
-            @if env::var("ALLOW_REGISTER").unwrap_or_else(|_| "false".to_string()) != "true" {
+            @if mode == RegistrationMode::Closed {
                 (components::body(html! {
                     main class="auth-container" { div class="auth-card" {
                         (components::alert(components::AlertKind::Warning, &AuthError::RegistrationDisabled.to_string()))
@@ -220,6 +472,13 @@ pub async fn get_register(db: web::Data<Database>, req: HttpRequest, query: web:
                                     label for="confirm-password" { "Confirm password" }
                                     input type="password" id="confirm-password" name="confirm-password" required {}
                                 }
+                                @if mode == RegistrationMode::Invite {
+                                    div class="form-group" {
+                                        label for="invite" { "Invite code" }
+                                        input type="text" id="invite" name="invite" value=[query.invite.as_deref()] required {}
+                                        div class="input-help" { "Registration is invite-only; ask an existing member for a code" }
+                                    }
+                                }
                                 div class="form-options" {
                                     label class="checkbox-label" {
                                         input type="checkbox" name="terms" required {}
@@ -230,13 +489,7 @@ pub async fn get_register(db: web::Data<Database>, req: HttpRequest, query: web:
                                         a href="/privacy" { "Privacy Policy" }
                                     }
                                 }
-                                @if recaptcha_enabled() {
-                                    @if let Some(key) = recaptcha_site_key() {
-                                        div class="form-group" {
-                                            div class="g-recaptcha" data-sitekey=(key) {}
-                                        }
-                                    }
-                                }
+                                (captcha.widget())
                                 button type="submit" class="auth-btn" { "Create account" }
                             }
                             div class="auth-footer" {
@@ -253,33 +506,141 @@ pub async fn get_register(db: web::Data<Database>, req: HttpRequest, query: web:
 }
 
 #[post("/register")]
-pub async fn post_register(db: web::Data<Database>, form: web::Form<RegisterForm>) -> Result<HttpResponse> {
-    if recaptcha_enabled() {
-        let token_opt = form.recaptcha_response.as_deref();
-        if token_opt.is_none() {
-            return Ok(HttpResponse::SeeOther()
-                .insert_header((LOCATION, "/register?error=Missing%20captcha"))
-                .finish());
-        }
-        let remote_ip = None::<&str>;
-        if !verify_recaptcha_token(token_opt.unwrap(), remote_ip).await {
-            return Ok(HttpResponse::SeeOther()
-                .insert_header((LOCATION, "/register?error=Captcha%20verification%20failed"))
-                .finish());
+pub async fn post_register(
+    db: web::Data<Database>,
+    mailer: web::Data<dyn Mailer>,
+    captcha: web::Data<dyn Captcha>,
+    req: HttpRequest,
+    form: web::Form<RegisterForm>,
+) -> Result<HttpResponse> {
+    let form = form.into_inner();
+    // keep the invite code filled in when sending the user back to the form
+    let back = |msg: &str| {
+        let mut location = format!("/register?error={}", url_encode(msg));
+        if let Some(code) = form.invite.as_deref().filter(|c| !c.is_empty()) {
+            location.push_str(&format!("&invite={}", url_encode(code)));
         }
+        HttpResponse::SeeOther().insert_header((LOCATION, location)).finish()
+    };
+
+    let remote_ip = req.connection_info().realip_remote_addr().map(|s| s.to_string());
+    if !captcha.verify(&form.captcha, remote_ip.as_deref()).await {
+        return Ok(back("Captcha verification failed"));
     }
 
-    match service::auth_register(&db, form.username.clone(), form.email.clone(), form.password.clone()).await {
+    let result = service::auth_register(
+        &db,
+        mailer.get_ref(),
+        form.username.clone(),
+        form.email.clone(),
+        form.password.clone(),
+        form.invite.clone(),
+    )
+    .await;
+    match result {
         Ok(()) => {
             Ok(HttpResponse::SeeOther()
                 .insert_header((LOCATION, "/login"))
                 .finish())
         }
-        Err(_) => {
-            Ok(HttpResponse::SeeOther()
-                .insert_header((LOCATION, "/register?error=Registration%20failed"))
-                .finish())
-        }
+        Err(e @ AuthError::InvalidInvite) => Ok(back(&e.to_string())),
+        Err(_) => Ok(back("Registration failed")),
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct OidcCallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Binds a single sign-on to the browser that started it.
+pub(crate) fn sso_state_cookie(state: String) -> Cookie<'static> {
+    Cookie::build("oidc_state", state)
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .path("/login/oidc")
+        .max_age(Duration::minutes(10))
+        .finish()
+}
+
+#[get("/login/oidc/{provider}")]
+pub async fn get_login_oidc(db: web::Data<Database>, oidc: web::Data<Oidc>, path: web::Path<String>) -> Result<HttpResponse> {
+    match service::auth_begin_sso(&db, &oidc, &path.into_inner(), None).await {
+        Ok((url, state)) => Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, url))
+            .cookie(sso_state_cookie(state))
+            .finish()),
+        Err(e) => Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, format!("/login?error={}", url_encode(&e.to_string()))))
+            .finish()),
+    }
+}
+
+#[get("/login/oidc/{provider}/callback")]
+pub async fn get_login_oidc_callback(
+    db: web::Data<Database>,
+    mailer: web::Data<dyn Mailer>,
+    oidc: web::Data<Oidc>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<OidcCallbackQuery>,
+) -> Result<HttpResponse> {
+    let removal = Cookie::build("oidc_state", "")
+        .path("/login/oidc")
+        .http_only(true)
+        .max_age(Duration::seconds(0))
+        .finish();
+    // a user who was linking an identity goes back to their settings
+    let signed_in = crate::frontend::repo::utils::requester_id(&db, &req).await.is_some();
+    let fail = |msg: &str| {
+        let target = if signed_in { "/settings/account" } else { "/login" };
+        HttpResponse::SeeOther()
+            .insert_header((LOCATION, format!("{}?error={}", target, url_encode(msg))))
+            .cookie(removal.clone())
+            .finish()
+    };
+
+    if let Some(err) = &query.error {
+        return Ok(fail(&format!("Sign-in was cancelled: {}", query.error_description.as_deref().unwrap_or(err))));
+    }
+    let (code, state) = match (&query.code, &query.state) {
+        (Some(c), Some(s)) => (c, s),
+        _ => return Ok(fail("Invalid sign-in response")),
+    };
+    if req.cookie("oidc_state").map(|c| c.value().to_string()).as_deref() != Some(state.as_str()) {
+        return Ok(fail("The sign-in was started in another browser, please try again"));
+    }
+
+    let outcome = service::auth_finish_sso(
+        &db,
+        mailer.get_ref(),
+        &oidc,
+        &path.into_inner(),
+        state,
+        code,
+        ClientInfo::from_request(&req),
+    )
+    .await;
+    match outcome {
+        Ok(SsoOutcome::Login(LoginStep::Token(token))) => Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, "/"))
+            .cookie(removal)
+            .cookie(session_cookie(token, false))
+            .finish()),
+        Ok(SsoOutcome::Login(LoginStep::SecondFactor(challenge))) => Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, "/login/2fa"))
+            .cookie(removal)
+            .cookie(challenge_cookie(challenge))
+            .finish()),
+        Ok(SsoOutcome::Linked) => Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, format!("/settings/account?success={}", url_encode("Account linked"))))
+            .cookie(removal)
+            .finish()),
+        Err(e @ (AuthError::Sso(_) | AuthError::Suspended)) => Ok(fail(&e.to_string())),
+        Err(_) => Ok(fail("Sign-in failed")),
     }
 }
 
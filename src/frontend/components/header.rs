@@ -22,6 +22,8 @@ pub fn header(display_name: Option<&str>) -> Markup {
                             div class="menu-name" { (name) }
                             div class="menu-options" {
                                 a href=("/".to_string() + name) class="menu-text" { "My Repositories" }
+                                a href="/organizations/new" class="menu-text" { "New organization" }
+                                a href="/settings/account" class="menu-text" { "Settings" }
                                 form method="post" class="menu-text" action="/logout" {
                                     button type="submit" class="red" { "Log Out" }
                                 }
@@ -40,11 +42,27 @@ pub fn header(display_name: Option<&str>) -> Markup {
     }
 }
 
+#[derive(PartialEq, Eq)]
+pub enum RepoTab {
+    Code,
+    Settings,
+}
+
 pub fn repo_header(
     display_name: Option<&str>,
     owner_slug: &str,
     repo_slug: &str,
     is_private: bool,
+) -> Markup {
+    repo_header_tab(display_name, owner_slug, repo_slug, is_private, RepoTab::Code)
+}
+
+pub fn repo_header_tab(
+    display_name: Option<&str>,
+    owner_slug: &str,
+    repo_slug: &str,
+    is_private: bool,
+    tab: RepoTab,
 ) -> Markup {
     let visibility = if is_private { "Private" } else { "Public" };
     html! {
@@ -70,6 +88,8 @@ pub fn repo_header(
                             div class="menu-name" { (name) }
                             div class="menu-options" {
                                 a href=("/".to_string() + name) class="menu-text" { "My Repositories" }
+                                a href="/organizations/new" class="menu-text" { "New organization" }
+                                a href="/settings/account" class="menu-text" { "Settings" }
                                 form method="post" class="menu-text" action="/logout" {
                                     button type="submit" class="red" { "Log Out" }
                                 }
@@ -85,11 +105,11 @@ pub fn repo_header(
                 }
             }
             nav class="repo-nav" {
-                a class="nav-item active" {
+                a.nav-item.active[tab == RepoTab::Code] href={(format!("/{}/{}", owner_slug, repo_slug))} {
                     img src=(SERVE_PATH.to_string() + "/code.svg") alt="code" class="icon-branch" {}
                     "Code"
                 }
-                a class="nav-item" { "Settings" }
+                a.nav-item.active[tab == RepoTab::Settings] href={(format!("/{}/{}/settings", owner_slug, repo_slug))} { "Settings" }
             }
         }
     }
@@ -36,7 +36,7 @@ pub async fn index(db: web::Data<Database>, req: HttpRequest) -> Result<Markup>
 
     let mut usernames: HashMap<ObjectId, String> = HashMap::new();
     for uid in &owner_ids {
-        match service::username_by_id(&db, uid).await {
+        match service::owner_name_by_id(&db, uid).await {
             Ok(Some(name)) => {
                 usernames.insert(uid.clone(), name);
             }
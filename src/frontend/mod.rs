@@ -5,6 +5,9 @@ mod auth;
 mod repo;
 mod profile;
 mod errors;
+mod orgs;
+mod settings;
+mod admin;
 
 use index::*;
 use actix_files::Files;
@@ -16,10 +19,60 @@ pub static SERVE_PATH: Lazy<String> = Lazy::new(|| {
     env::var("SERVE_FILES_PATH").unwrap_or_else(|_| "/static".to_string())
 });
 
+pub use auth::renew_session;
+
 pub fn token_from_req(req: &HttpRequest) -> Option<String> {
     req.cookie("token").map(|c| c.value().to_string())
 }
 
+/// Percent-encodes a value for use in a redirect query string.
+pub fn url_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+/// Permanent redirect from a page under a former `/{owner}` name to the same page
+/// under the current one.
+pub fn renamed_owner_redirect(req: &HttpRequest, new_name: &str) -> actix_web::HttpResponse {
+    let path = req.path().trim_start_matches('/');
+    let mut location = format!("/{}", new_name);
+    if let Some((_, rest)) = path.split_once('/') {
+        location.push('/');
+        location.push_str(rest);
+    }
+    if !req.query_string().is_empty() {
+        location.push('?');
+        location.push_str(req.query_string());
+    }
+    actix_web::HttpResponse::MovedPermanently()
+        .insert_header((actix_web::http::header::LOCATION, location))
+        .finish()
+}
+
+/// Permanent redirect from a page under a former `/{owner}/{repo}` path to the same
+/// page of the renamed or transferred repository.
+pub fn moved_repo_redirect(req: &HttpRequest, owner: &str, repo: &str) -> actix_web::HttpResponse {
+    let path = req.path().trim_start_matches('/');
+    let mut location = format!("/{}/{}", owner, repo);
+    if let Some(rest) = path.splitn(3, '/').nth(2) {
+        location.push('/');
+        location.push_str(rest);
+    }
+    if !req.query_string().is_empty() {
+        location.push('?');
+        location.push_str(req.query_string());
+    }
+    actix_web::HttpResponse::MovedPermanently()
+        .insert_header((actix_web::http::header::LOCATION, location))
+        .finish()
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg
         .service(Files::new(SERVE_PATH.to_string().as_str(), "./public").prefer_utf8(true))
@@ -28,10 +81,58 @@ pub fn config(cfg: &mut web::ServiceConfig) {
 
         .service(auth::get_login)
         .service(auth::post_login)
+        .service(auth::get_login_otp)
+        .service(auth::post_login_otp)
+        .service(auth::get_forgot_password)
+        .service(auth::post_forgot_password)
+        .service(auth::get_reset_password)
+        .service(auth::post_reset_password)
+        .service(auth::get_verify_email)
+        .service(auth::get_login_oidc)
+        .service(auth::get_login_oidc_callback)
         .service(auth::get_register)
         .service(auth::post_register)
         .service(auth::post_logout)
 
+        .service(settings::account::get)
+        .service(settings::account::username)
+        .service(settings::account::email)
+        .service(settings::account::password)
+        .service(settings::account::resend_verification)
+        .service(settings::account::link_identity)
+        .service(settings::account::unlink_identity)
+        .service(settings::security::get)
+        .service(settings::security::setup)
+        .service(settings::security::enable)
+        .service(settings::security::disable)
+        .service(settings::tokens::get)
+        .service(settings::tokens::create)
+        .service(settings::tokens::revoke)
+        .service(settings::keys::get)
+        .service(settings::keys::add)
+        .service(settings::keys::delete)
+        .service(settings::sessions::get)
+        .service(settings::sessions::revoke)
+        .service(settings::sessions::revoke_all)
+        .service(settings::invites::get)
+        .service(settings::invites::create)
+        .service(settings::invites::revoke)
+
+        .service(orgs::get_new)
+        .service(orgs::post_new)
+        .service(orgs::settings)
+        .service(orgs::post_settings)
+
+        .service(admin::overview)
+        .service(admin::user_list)
+        .service(admin::user_detail)
+        .service(admin::suspend_user)
+        .service(admin::delete_user)
+        .service(admin::repo_list)
+        .service(admin::repo_detail)
+        .service(admin::transfer_repo)
+        .service(admin::delete_repo)
+
         .service(repo::index)
         .service(repo::tree)
         .service(repo::tree_at_path)
@@ -42,6 +143,21 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         .service(repo::branches::list)
         .service(repo::branches::do_delete)
         .service(repo::branches::confirm_delete)
+        .service(repo::forks::list)
+        .service(repo::forks::create)
+        .service(repo::settings::general::get)
+        .service(repo::settings::general::update)
+        .service(repo::settings::general::delete)
+        .service(repo::settings::general::rename)
+        .service(repo::settings::general::transfer)
+        .service(repo::settings::collaborators::get)
+        .service(repo::settings::collaborators::add)
+        .service(repo::settings::collaborators::remove)
+        .service(repo::settings::deploy::get)
+        .service(repo::settings::deploy::add_key)
+        .service(repo::settings::deploy::delete_key)
+        .service(repo::settings::deploy::create_token)
+        .service(repo::settings::deploy::revoke_token)
 
         .service(profile::user_profile)
 
@@ -0,0 +1,294 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+use actix_web::http::{header::LOCATION, StatusCode};
+use maud::{html, Markup};
+
+use crate::api::service;
+use crate::db::{self, Database, OrgRole, Role};
+use crate::frontend::errors::render_error_page;
+use crate::frontend::repo::utils;
+use crate::frontend::{components, url_encode, SERVE_PATH};
+use crate::models::*;
+
+#[derive(serde::Deserialize)]
+pub struct MessageQuery {
+    error: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewOrgForm {
+    pub name: String,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Every management form on the settings page posts here with an `action`.
+#[derive(serde::Deserialize)]
+pub struct OrgSettingsForm {
+    pub action: String,
+    pub username: Option<String>,
+    pub role: Option<String>,
+    pub team: Option<String>,
+    pub description: Option<String>,
+    pub repo: Option<String>,
+}
+
+fn login_redirect() -> HttpResponse {
+    HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()
+}
+
+fn new_org_page(error: Option<&str>) -> Markup {
+    html! {
+        main class="new-repo-container" {
+            section class="new-repo-card" {
+                header class="new-repo-header" {
+                    h1 { "Create a new organization" }
+                    p class="sub" { "Organizations group people and repositories under a shared namespace." }
+                }
+                @if let Some(err) = error {
+                    (components::alert(components::AlertKind::Error, err))
+                }
+                form class="new-repo-form" method="post" action="/organizations/new" {
+                    fieldset class="form-group" {
+                        label for="org-name" { "Organization name" }
+                        input type="text" id="org-name" name="name" placeholder="my-team" required {}
+                        p class="help" { "Used in URLs: /<name>/<repository>." }
+                    }
+                    fieldset class="form-group" {
+                        label for="org-display" { "Display name " span class="muted" { "(optional)" } }
+                        input type="text" id="org-display" name="display_name" {}
+                    }
+                    fieldset class="form-group" {
+                        label for="org-desc" { "Description " span class="muted" { "(optional)" } }
+                        textarea id="org-desc" name="description" rows="3" {}
+                    }
+                    div class="actions" {
+                        button type="submit" class="create-btn" { "Create organization" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[get("/organizations/new")]
+pub async fn get_new(db: web::Data<Database>, req: HttpRequest, query: web::Query<MessageQuery>) -> Result<HttpResponse> {
+    let user_display = match utils::token_display(&db, &req).await {
+        Some(name) => name,
+        None => return Ok(login_redirect()),
+    };
+
+    let page = html! {
+        (maud::DOCTYPE)
+        html lang="en" {
+            (components::head("New organization - GitLit", html! {
+                link rel="stylesheet" href=(SERVE_PATH.to_string() + "/new.css") {}
+            }))
+            (components::body(new_org_page(query.error.as_deref()), Some(&user_display)))
+        }
+    };
+    Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string()))
+}
+
+#[post("/organizations/new")]
+pub async fn post_new(db: web::Data<Database>, req: HttpRequest, form: web::Form<NewOrgForm>) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(login_redirect()),
+    };
+
+    let payload = CreateOrgRequest {
+        name: form.name.clone(),
+        display_name: form.display_name.clone(),
+        description: form.description.clone().map(|s| s.trim().to_string()),
+    };
+    match service::org_create(&db, requester, payload).await {
+        Ok(org) => Ok(HttpResponse::SeeOther().insert_header((LOCATION, format!("/{}", org.name))).finish()),
+        Err(msg) => Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, format!("/organizations/new?error={}", url_encode(&msg))))
+            .finish()),
+    }
+}
+
+async fn resolve_owned_org(db: &Database, req: &HttpRequest, name: &str) -> Result<Option<(db::Organization, bson::oid::ObjectId)>> {
+    let org = db
+        .find_org_by_name(name)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("organization not found"))?;
+    let requester = match utils::requester_id(db, req).await {
+        Some(uid) => uid,
+        None => return Ok(None),
+    };
+    let member = db
+        .find_org_member(&org._id, &requester)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if member.map(|m| m.role) != Some(OrgRole::Owner) {
+        return Ok(None);
+    }
+    Ok(Some((org, requester)))
+}
+
+fn forbidden() -> HttpResponse {
+    render_error_page(
+        "403 - Forbidden",
+        "Forbidden",
+        "Only organization owners can manage this organization.",
+        StatusCode::FORBIDDEN,
+    )
+}
+
+#[get("/organizations/{org}/settings")]
+pub async fn settings(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String,)>,
+    query: web::Query<MessageQuery>,
+) -> Result<HttpResponse> {
+    let name = path.into_inner().0;
+    let (org, requester) = match resolve_owned_org(&db, &req, &name).await? {
+        Some(v) => v,
+        None => return Ok(forbidden()),
+    };
+    let user_display = utils::token_display(&db, &req).await;
+
+    let members = service::org_members(&db, &org.name)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let teams = service::org_teams(&db, Some(requester), &org.name)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let action = format!("/organizations/{}/settings", org.name);
+
+    let content = html! {
+        div class="container" {
+            h1 { (org.display_name) " " span class="muted" { "settings" } }
+            @if let Some(err) = &query.error {
+                (components::alert(components::AlertKind::Error, err))
+            }
+            div class="settings-content" {
+                h2 { "Members" }
+                ul class="settings-list" {
+                    @for m in &members {
+                        li {
+                            a href={(format!("/{}", m.username))} { (m.display_name) " " span class="muted" { "@" (m.username) } }
+                            span class="badge" { (m.role.as_str()) }
+                            form method="post" action=(action) {
+                                input type="hidden" name="action" value="remove_member" {}
+                                input type="hidden" name="username" value=(m.username) {}
+                                button type="submit" class="branch-delete-btn" { "Remove" }
+                            }
+                        }
+                    }
+                }
+                form class="settings-form" method="post" action=(action) {
+                    input type="hidden" name="action" value="set_member" {}
+                    div class="form-row" {
+                        input type="text" name="username" placeholder="Username or email" required {}
+                        select name="role" {
+                            option value="member" selected { "Member" }
+                            option value="owner" { "Owner" }
+                        }
+                        button type="submit" class="action-btn" { "Add or update member" }
+                    }
+                }
+
+                h2 { "Teams" }
+                @for t in &teams {
+                    div class="settings-team" {
+                        h3 { (t.name) " " span class="badge" { (t.permission.as_str()) } }
+                        @if !t.description.is_empty() { p class="muted" { (t.description) } }
+                        p { strong { "Members: " } (t.members.join(", ")) }
+                        p { strong { "Repositories: " } (t.repos.join(", ")) }
+                        form class="settings-form" method="post" action=(action) {
+                            input type="hidden" name="team" value=(t.name) {}
+                            div class="form-row" {
+                                input type="text" name="username" placeholder="Username" {}
+                                button type="submit" class="action-btn" name="action" value="add_team_member" { "Add member" }
+                                button type="submit" class="action-btn" name="action" value="remove_team_member" { "Remove member" }
+                            }
+                        }
+                        form class="settings-form" method="post" action=(action) {
+                            input type="hidden" name="team" value=(t.name) {}
+                            div class="form-row" {
+                                input type="text" name="repo" placeholder="Repository name" {}
+                                button type="submit" class="action-btn" name="action" value="add_team_repo" { "Grant repository" }
+                                button type="submit" class="action-btn" name="action" value="remove_team_repo" { "Revoke repository" }
+                            }
+                        }
+                        form method="post" action=(action) {
+                            input type="hidden" name="action" value="delete_team" {}
+                            input type="hidden" name="team" value=(t.name) {}
+                            button type="submit" class="branch-delete-btn" { "Delete team" }
+                        }
+                    }
+                }
+                form class="settings-form" method="post" action=(action) {
+                    h3 { "New team" }
+                    input type="hidden" name="action" value="create_team" {}
+                    div class="form-row" {
+                        input type="text" name="team" placeholder="Team name" required {}
+                        select name="role" {
+                            option value="read" selected { "Read" }
+                            option value="write" { "Write" }
+                            option value="admin" { "Admin" }
+                        }
+                    }
+                    input type="text" name="description" placeholder="Description (optional)" {}
+                    button type="submit" class="action-btn" { "Create team" }
+                }
+            }
+        }
+    };
+
+    let page = utils::page_shell(&format!("{} settings", org.name), content, user_display.as_deref());
+    Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string()))
+}
+
+#[post("/organizations/{org}/settings")]
+pub async fn post_settings(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String,)>,
+    form: web::Form<OrgSettingsForm>,
+) -> Result<HttpResponse> {
+    let name = path.into_inner().0;
+    let (org, requester) = match resolve_owned_org(&db, &req, &name).await? {
+        Some(v) => v,
+        None => return Ok(forbidden()),
+    };
+    let form = form.into_inner();
+    let username = form.username.unwrap_or_default();
+    let team = form.team.unwrap_or_default();
+    let repo = form.repo.unwrap_or_default();
+    let org_name = org.name.clone();
+
+    let result = match form.action.as_str() {
+        "set_member" => match form.role.as_deref().and_then(OrgRole::parse) {
+            Some(role) => service::org_member_set(&db, requester, SetOrgMemberRequest { org: org_name, username, role }).await,
+            None => Err("invalid role".to_string()),
+        },
+        "remove_member" => service::org_member_remove(&db, requester, &org_name, &username).await,
+        "create_team" => match form.role.as_deref().and_then(Role::parse) {
+            Some(permission) => {
+                let payload = CreateTeamRequest { org: org_name, name: team, description: form.description, permission };
+                service::team_create(&db, requester, payload).await
+            }
+            None => Err("invalid role".to_string()),
+        },
+        "delete_team" => service::team_delete(&db, requester, &org_name, &team).await,
+        "add_team_member" => service::team_member_add(&db, requester, TeamMemberQuery { org: org_name, team, username }).await,
+        "remove_team_member" => service::team_member_remove(&db, requester, TeamMemberQuery { org: org_name, team, username }).await,
+        "add_team_repo" => service::team_repo_add(&db, requester, TeamRepoQuery { org: org_name, team, repo }).await,
+        "remove_team_repo" => service::team_repo_remove(&db, requester, TeamRepoQuery { org: org_name, team, repo }).await,
+        _ => Err("unknown action".to_string()),
+    };
+
+    let back = format!("/organizations/{}/settings", org.name);
+    match result {
+        Ok(()) => Ok(HttpResponse::SeeOther().insert_header((LOCATION, back)).finish()),
+        Err(msg) => Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, format!("{}?error={}", back, url_encode(&msg))))
+            .finish()),
+    }
+}
@@ -16,7 +16,36 @@ fn profile_head() -> Markup {
     }
 }
 
-fn profile_page(user: &db::User, repos: &[db::Repository], requester: Option<&str>) -> Markup {
+fn repo_list(owner_name: &str, repos: &[db::Repository]) -> Markup {
+    html! {
+        div class="section-header" { h2 { "Repositories" } }
+        @if repos.is_empty() {
+            p class="muted" { "No repositories yet." }
+        } @else {
+            ul class="repo-list" {
+                @for r in repos {
+                    li class="repo-item" {
+                        div class="repo-primary" {
+                            a class="repo-name" href={(format!("/{}/{}", owner_name, r.name))} { (r.name.clone()) }
+                            @if !r.description.is_empty() { p class="repo-desc" { (r.description.clone()) } }
+                        }
+                        div class="repo-meta" {
+                            @if r.is_private { span class="badge" { "Private" } } @else { span class="badge" { "Public" } }
+                            span class="muted" { "Updated " (r.updated_at.to_string()) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn profile_page(
+    user: &db::User,
+    repos: &[db::Repository],
+    orgs: &[db::Organization],
+    requester: Option<&str>,
+) -> Markup {
     let display = if user.display_name.is_empty() { &user.username } else { &user.display_name };
     page_shell(
         &format!("{} ({}) · GitLit", display, user.username),
@@ -36,29 +65,64 @@ fn profile_page(user: &db::User, repos: &[db::Repository], requester: Option<&st
                     div class="meta" {
                         p { strong { "Member since: " } (user.created_at.to_string()) }
                         @if let Some(name) = requester { p { "Signed in as " (name) } }
+                        @if !orgs.is_empty() {
+                            p { strong { "Organizations" } }
+                            @for o in orgs {
+                                p { a href={(format!("/{}", o.name))} { (o.display_name.clone()) } }
+                            }
+                        }
                     }
                 }
                 section class="profile-content" {
-                    div class="section-header" { h2 { "Repositories" } }
-                    @if repos.is_empty() {
-                        p class="muted" { "No repositories yet." }
-                    } @else {
-                        ul class="repo-list" {
-                            @for r in repos {
-                                li class="repo-item" {
-                                    div class="repo-primary" {
-                                        a class="repo-name" href={(format!("/{}/{}", user.username, r.name))} { (r.name.clone()) }
-                                        @if !r.description.is_empty() { p class="repo-desc" { (r.description.clone()) } }
-                                    }
-                                    div class="repo-meta" {
-                                        @if r.is_private { span class="badge" { "Private" } } @else { span class="badge" { "Public" } }
-                                        span class="muted" { "Updated " (r.updated_at.to_string()) }
-                                    }
-                                }
+                    (repo_list(&user.username, repos))
+                }
+            }
+        },
+        requester,
+    )
+}
+
+fn org_page(
+    org: &db::Organization,
+    repos: &[db::Repository],
+    members: &[OrgMemberInfo],
+    is_owner: bool,
+    requester: Option<&str>,
+) -> Markup {
+    let display = if org.display_name.is_empty() { &org.name } else { &org.display_name };
+    page_shell(
+        &format!("{} · GitLit", display),
+        html! {
+            main class="container profile-page" {
+                div class="subheader" {
+                    div class="subheader-left" {
+                        h1 { (display) }
+                        p class="muted" { "@" (org.name) " · Organization" }
+                    }
+                    @if is_owner {
+                        a class="badge" href={(format!("/organizations/{}/settings", org.name))} { "Settings" }
+                    }
+                }
+                section class="profile-sidebar" {
+                    div class="avatar" {
+                        @if let Some(url) = &org.avatar_url { img src=(url) alt="avatar" {} }
+                        @else { div class="avatar-placeholder" { (org.name.chars().next().unwrap_or('?').to_uppercase()) } }
+                    }
+                    div class="meta" {
+                        @if !org.description.is_empty() { p { (org.description.clone()) } }
+                        p { strong { "Created: " } (org.created_at.to_string()) }
+                        p { strong { "Members" } }
+                        @for m in members {
+                            p {
+                                a href={(format!("/{}", m.username))} { (m.display_name.clone()) }
+                                @if m.role == db::OrgRole::Owner { " " span class="badge" { "Owner" } }
                             }
                         }
                     }
                 }
+                section class="profile-content" {
+                    (repo_list(&org.name, repos))
+                }
             }
         },
         requester,
@@ -86,18 +150,29 @@ pub async fn user_profile(
 ) -> actix_web::Result<impl Responder> {
     let username = path.into_inner().0;
 
-    let reserved = [
-        "static", "login", "register", "logout", "api", "new",
-    ];
-    if reserved.contains(&username.as_str()) {
+    if crate::auth::RESERVED_NAMES.contains(&username.as_str()) {
         return Err(actix_web::error::ErrorNotFound("not found"));
     }
 
-    let user = db
-        .find_user_by_login(&username)
+    let owner = match db
+        .find_owner_by_name(&username)
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?
-        .ok_or_else(|| actix_web::error::ErrorNotFound("user not found"))?;
+    {
+        Some(o) => o,
+        None => {
+            let renamed = db
+                .find_renamed_owner(&username)
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?
+                .ok_or_else(|| actix_web::error::ErrorNotFound("user not found"))?;
+            return Err(actix_web::error::InternalError::from_response(
+                "owner renamed",
+                crate::frontend::renamed_owner_redirect(&req, renamed.name()),
+            )
+            .into());
+        }
+    };
 
     let requester_id = match crate::frontend::token_from_req(&req) {
         Some(tok) => service::get_user_id_from_token(&db, tok).await.ok(),
@@ -107,15 +182,43 @@ pub async fn user_profile(
     let repos: Vec<db::Repository> = service::repo_list(
         &db,
         requester_id.clone(),
-        ReposQuery { owner: Some(user.username.clone()), filter: Some("updated".to_string()), q: None }
+        ReposQuery { owner: Some(owner.name().to_string()), filter: Some("updated".to_string()), q: None }
     ).await.map_err(actix_web::error::ErrorInternalServerError)?;
 
     let requester_name = requester_display(&db, &req).await;
 
+    let body = match &owner {
+        db::Owner::User(user) => {
+            let orgs: Vec<db::Organization> = db
+                .orgs_for_user(&user._id)
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?
+                .into_iter()
+                .map(|(org, _)| org)
+                .collect();
+            profile_page(user, &repos, &orgs, requester_name.as_deref())
+        }
+        db::Owner::Org(org) => {
+            let members = service::org_members(&db, &org.name)
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?;
+            let is_owner = match &requester_id {
+                Some(uid) => db
+                    .find_org_member(&org._id, uid)
+                    .await
+                    .map_err(actix_web::error::ErrorInternalServerError)?
+                    .map(|m| m.role == db::OrgRole::Owner)
+                    .unwrap_or(false),
+                None => false,
+            };
+            org_page(org, &repos, &members, is_owner, requester_name.as_deref())
+        }
+    };
+
     let markup = html! {
         (DOCTYPE)
-        html { (components::head(&format!("{} · GitLit", user.username), profile_head()))
-               (components::body(profile_page(&user, &repos, requester_name.as_deref()), requester_name.as_deref())) }
+        html { (components::head(&format!("{} · GitLit", owner.name()), profile_head()))
+               (components::body(body, requester_name.as_deref())) }
     };
 
     Ok(actix_web::HttpResponse::Ok().content_type("text/html; charset=utf-8").body(markup.into_string()))
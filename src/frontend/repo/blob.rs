@@ -12,7 +12,7 @@ pub async fn blob(
     path: web::Path<(String, String, String, String)>,
 ) -> Result<Markup> {
     let (username, reponame, rev_in, blob_path) = path.into_inner();
-    let (owner, repo) = utils::resolve_owner_repo(&db, &username, &reponame).await?;
+    let (owner, repo, _) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
     let user_display = utils::token_display(&db, &req).await;
 
     let (rev, branch_opt_owned): (String, Option<String>) = if utils::is_hex_hash(&rev_in) {
@@ -23,7 +23,7 @@ pub async fn blob(
     let branch_opt = branch_opt_owned.as_deref();
     let reference = branch_opt.unwrap_or(&rev);
 
-    let content_bytes = repo::get_file_content(&owner._id, &repo._id, &rev, branch_opt, &blob_path)
+    let content_bytes = repo::get_file_content(owner.id(), &repo._id, &rev, branch_opt, &blob_path)
         .await
         .map_err(|_| actix_web::error::ErrorNotFound("file not found"))?;
 
@@ -38,11 +38,11 @@ pub async fn blob(
     let parent_path = utils::parent_path(&blob_path);
 
     let content = html! {
-        (components::repo_header(user_display.as_deref(), &owner.username, &repo.name, repo.is_private))
+        (components::repo_header(user_display.as_deref(), owner.name(), &repo.name, repo.is_private))
         div class="container" {
             div class="main-content" {
                 div class="left-content" {
-                    (utils::breadcrumbs(&owner.username, &repo.name, reference, parent_path.as_deref()))
+                    (utils::breadcrumbs(owner.name(), &repo.name, reference, parent_path.as_deref()))
                     div class="content-viewer" {
                         div class="content-header" {
                             span class="content-title" { (format!("📄 {}", blob_path)) }
@@ -57,5 +57,5 @@ pub async fn blob(
         }
     };
 
-    Ok(utils::page_shell(&format!("{} / {} - {}", owner.username, repo.name, blob_path), content, user_display.as_deref()))
+    Ok(utils::page_shell(&format!("{} / {} - {}", owner.name(), repo.name, blob_path), content, user_display.as_deref()))
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
 use crate::db::Database;
+use crate::policy::{self, Action};
 use crate::frontend::repo::utils;
 use crate::repo;
 use maud::{html, Markup};
@@ -11,23 +12,23 @@ pub async fn list(
     path: web::Path<(String, String)>,
 ) -> Result<Markup> {
     let (username, reponame) = path.into_inner();
-    let (owner, repo) = utils::resolve_owner_repo(&db, &username, &reponame).await?;
+    let (owner, repo, actor) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
     let user_display = utils::token_display(&db, &req).await;
-    let branches = repo::list_branches(&owner._id, &repo._id).await.unwrap_or_default();
-    let is_owner = user_display.as_deref() == Some(&owner.username);
+    let branches = repo::list_branches(owner.id(), &repo._id).await.unwrap_or_default();
+    let can_delete = policy::can(&actor, Action::DeleteBranch, &repo);
 
     let content = html! {
-        (crate::frontend::components::repo_header(user_display.as_deref(), &owner.username, &repo.name, repo.is_private))
+        (crate::frontend::components::repo_header(user_display.as_deref(), owner.name(), &repo.name, repo.is_private))
         div class="container" {
             h2 { "Branches" }
             ul class="branch-list" {
                 @for b in &branches {
                     li {
-                        a href={(format!("/{}/{}/tree/{}", owner.username, repo.name, b.name))} {
+                        a href={(format!("/{}/{}/tree/{}", owner.name(), repo.name, b.name))} {
                             (b.name.clone())
                         }
-                        @if is_owner && !b.is_head {
-                            a href={(format!("/{}/{}/branches/delete/{}", owner.username, repo.name, b.name))} {
+                        @if can_delete && !b.is_head {
+                            a href={(format!("/{}/{}/branches/delete/{}", owner.name(), repo.name, b.name))} {
                                 button type="button" class="branch-delete-btn" { "Delete" }
                             }
                         }
@@ -39,7 +40,7 @@ pub async fn list(
             }
         }
     };
-    Ok(utils::page_shell(&format!("{} / {} / branches", owner.username, repo.name), content, user_display.as_deref()))
+    Ok(utils::page_shell(&format!("{} / {} / branches", owner.name(), repo.name), content, user_display.as_deref()))
 }
 
 #[get("/{username}/{reponame}/branches/delete/{branch}")]
@@ -49,26 +50,26 @@ pub async fn confirm_delete(
     path: web::Path<(String, String, String)>,
 ) -> Result<Markup> {
     let (username, reponame, branch) = path.into_inner();
-    let (owner, repo) = utils::resolve_owner_repo(&db, &username, &reponame).await?;
+    let (owner, repo, actor) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
     let user_display = utils::token_display(&db, &req).await;
-    let is_owner = user_display.as_deref() == Some(&owner.username);
+    let can_delete = policy::can(&actor, Action::DeleteBranch, &repo);
 
-    if !is_owner {
+    if !can_delete {
         return Ok(html! { p { "Forbidden" } });
     }
 
     let content = html! {
-    (crate::frontend::components::repo_header(user_display.as_deref(), &owner.username, &repo.name, repo.is_private))
+    (crate::frontend::components::repo_header(user_display.as_deref(), owner.name(), &repo.name, repo.is_private))
         div class="container" {
             div class="confirm-delete-box" {
                 h2 { "Delete branch: " (branch) }
                 p { "To confirm deletion, type the branch name below:" }
-                form method="post" action={(format!("/{}/{}/branches/delete/{}", owner.username, repo.name, branch))} {
+                form method="post" action={(format!("/{}/{}/branches/delete/{}", owner.name(), repo.name, branch))} {
                     input type="text" name="confirm_name" placeholder="Branch name" required {}
                     input type="hidden" name="branch" value=(branch.clone()) {}
                     button type="submit" class="branch-delete-btn" { "Delete branch" }
                 }
-                a href={(format!("/{}/{}/branches", owner.username, repo.name))} { "Cancel" }
+                a href={(format!("/{}/{}/branches", owner.name(), repo.name))} { "Cancel" }
             }
         }
     };
@@ -84,24 +85,23 @@ pub async fn do_delete(
     form: web::Form<std::collections::HashMap<String, String>>,
 ) -> Result<HttpResponse> {
     let (username, reponame, branch) = path.into_inner();
-    let (owner, repo) = utils::resolve_owner_repo(&db, &username, &reponame).await?;
-    let user_display = utils::token_display(&db, &req).await;
-    let is_owner = user_display.as_deref() == Some(&owner.username);
+    let (owner, repo, actor) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let can_delete = policy::can(&actor, Action::DeleteBranch, &repo);
 
-    if !is_owner {
+    if !can_delete {
         return Ok(HttpResponse::Forbidden().body("Forbidden"));
     }
 
     if let Some(confirm) = form.get("confirm_name") {
         if confirm == &branch {
-            let _ = repo::delete_branch(&owner._id, &repo._id, &branch).await;
+            let _ = repo::delete_branch(owner.id(), &repo._id, &branch).await;
             return Ok(HttpResponse::SeeOther()
-                .append_header(("Location", format!("/{}/{}/branches", owner.username, repo.name)))
+                .append_header(("Location", format!("/{}/{}/branches", owner.name(), repo.name)))
                 .finish());
         }
     }
     // TODO: error page
     Ok(HttpResponse::SeeOther()
-        .append_header(("Location", format!("/{}/{}/branches/delete/{}", owner.username, repo.name, branch)))
+        .append_header(("Location", format!("/{}/{}/branches/delete/{}", owner.name(), repo.name, branch)))
         .finish())
 }
\ No newline at end of file
@@ -12,7 +12,7 @@ pub async fn commits(
     path: web::Path<(String, String, String)>,
 ) -> Result<Markup> {
     let (username, reponame, rev_in) = path.into_inner();
-    let (owner, repository) = utils::resolve_owner_repo(&db, &username, &reponame).await?;
+    let (owner, repository, _) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
     let user_display = utils::token_display(&db, &req).await;
 
     let (rev, branch_opt_owned): (String, Option<String>) = if utils::is_hex_hash(&rev_in) {
@@ -22,9 +22,9 @@ pub async fn commits(
     };
     let reference = branch_opt_owned.as_deref().unwrap_or(&rev);
 
-    let branches = repo::list_branches(&owner._id, &repository._id).await.unwrap_or_default();
+    let branches = repo::list_branches(owner.id(), &repository._id).await.unwrap_or_default();
 
-    let commits = repo::list_commits(&owner._id, &repository._id, reference, branch_opt_owned.as_deref(), 100)
+    let commits = repo::list_commits(owner.id(), &repository._id, reference, branch_opt_owned.as_deref(), 100)
         .await
         .unwrap_or_default();
 
@@ -33,7 +33,7 @@ pub async fn commits(
     let (detail_markup_opt, title_suffix) = if is_hash_view {
         let c_opt = commits.get(0);
         let details = if let Some(c) = c_opt {
-            let diff_text = repo::commit_diff(&owner._id, &repository._id, &c.hash)
+            let diff_text = repo::commit_diff(owner.id(), &repository._id, &c.hash)
                 .await
                 .unwrap_or_else(|_| String::from("Failed to load diff."));
             Some(html! {
@@ -57,7 +57,7 @@ pub async fn commits(
     };
 
     let content = html! {
-        (components::repo_header(user_display.as_deref(), &owner.username, &repository.name, repository.is_private))
+        (components::repo_header(user_display.as_deref(), owner.name(), &repository.name, repository.is_private))
         div class="container" {
             div class="main-content" {
                 div class="left-content" {
@@ -70,7 +70,7 @@ pub async fn commits(
                                 ul class="branch-menu" {
                                     @for b in &branches {
                                         li {
-                                            a href={(format!("/{}/{}/commits/{}", owner.username, repository.name, b.name))} {
+                                            a href={(format!("/{}/{}/commits/{}", owner.name(), repository.name, b.name))} {
                                                 @if b.is_head { span class="badge-head" { "HEAD" } }
                                                 (b.name.clone())
                                             }
@@ -99,7 +99,7 @@ pub async fn commits(
                                     ul class="commits-ul" {
                                         @for c in &commits {
                                             li class="commit-li" {
-                                                a href={(format!("/{}/{}/commits/{}", owner.username, repository.name, c.hash))} class="commit-row" {
+                                                a href={(format!("/{}/{}/commits/{}", owner.name(), repository.name, c.hash))} class="commit-row" {
                                                     div class="commit-main" {
                                                         div class="commit-subject" { (&c.subject) }
                                                         div class="commit-meta" {
@@ -126,7 +126,7 @@ pub async fn commits(
     };
 
     Ok(utils::page_shell(
-        &format!("{}/{} - {}", owner.username, repository.name, title_suffix),
+        &format!("{}/{} - {}", owner.name(), repository.name, title_suffix),
         content,
         user_display.as_deref(),
     ))
@@ -0,0 +1,153 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+use actix_web::http::header::LOCATION;
+use maud::html;
+
+use crate::api::service;
+use crate::db::{Database, OrgRole};
+use crate::frontend::components;
+use crate::frontend::repo::utils;
+use crate::frontend::url_encode;
+use crate::models::ForkRepoRequest;
+
+#[derive(serde::Deserialize)]
+pub struct MessageQuery {
+    error: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ForkForm {
+    pub owner: Option<String>,
+    pub name: Option<String>,
+}
+
+fn error_text(msg: &str) -> &str {
+    match msg {
+        "already exists" => "A repository with this name already exists there; choose another name",
+        "invalid name" => "Repository names may only contain letters, digits, '-', '_' and '.'",
+        "email not verified" => "Verify your email address before creating repositories",
+        "forbidden" => "You cannot create repositories there",
+        "owner not found" => "No user or organization with this name",
+        _ => "Something went wrong",
+    }
+}
+
+#[get("/{username}/{reponame}/forks")]
+pub async fn list(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<MessageQuery>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, actor) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let user_display = utils::token_display(&db, &req).await;
+
+    let forks = service::repo_forks(&db, actor.user, &repo._id.to_hex())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let mut rows = Vec::new();
+    for f in forks {
+        let owner_name = service::owner_name_by_id(&db, &f.user)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?
+            .unwrap_or_default();
+        rows.push((owner_name, f));
+    }
+
+    // namespaces the viewer may fork into: their account and the organizations they own
+    let mut namespaces = Vec::new();
+    if let Some(uid) = &actor.user {
+        if let Some(name) = service::username_by_id(&db, uid).await.map_err(actix_web::error::ErrorInternalServerError)? {
+            namespaces.push(name);
+        }
+        for (org, role) in db.orgs_for_user(uid).await.map_err(actix_web::error::ErrorInternalServerError)? {
+            if role == OrgRole::Owner {
+                namespaces.push(org.name);
+            }
+        }
+    }
+
+    let content = html! {
+        (components::repo_header(user_display.as_deref(), owner.name(), &repo.name, repo.is_private))
+        div class="container" {
+            h2 { "Forks" }
+            @if let Some(err) = &query.error {
+                (components::alert(components::AlertKind::Error, err))
+            }
+            @if rows.is_empty() {
+                p class="muted" { "No forks yet." }
+            } @else {
+                ul class="settings-list" {
+                    @for (owner_name, f) in &rows {
+                        li {
+                            a href={(format!("/{}/{}", owner_name, f.name))} { (owner_name) " / " (f.name) }
+                            @if f.is_private { span class="badge" { "private" } }
+                        }
+                    }
+                }
+            }
+            @if !namespaces.is_empty() {
+                form class="settings-form" method="post" action={(format!("/{}/{}/fork", owner.name(), repo.name))} {
+                    h3 { "Create a fork" }
+                    @if repo.is_private {
+                        p class="help" { "Forks of a private repository are private too." }
+                    }
+                    div class="form-row" {
+                        select name="owner" {
+                            @for n in &namespaces {
+                                option value=(n) { (n) }
+                            }
+                        }
+                        input type="text" name="name" value=(repo.name) placeholder="Repository name" {}
+                        button type="submit" class="action-btn" { "Fork" }
+                    }
+                }
+            }
+        }
+    };
+
+    let page = utils::page_shell(
+        &format!("{} / {} / forks", owner.name(), repo.name),
+        content,
+        user_display.as_deref(),
+    );
+    Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string()))
+}
+
+#[post("/{username}/{reponame}/fork")]
+pub async fn create(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<ForkForm>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, _) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+
+    let payload = ForkRepoRequest {
+        id: repo._id.to_hex(),
+        owner: form.owner.clone(),
+        name: form.name.clone(),
+    };
+    match service::repo_fork(&db, requester, payload).await {
+        Ok(fork) => {
+            let owner_name = service::owner_name_by_id(&db, &fork.user)
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?
+                .unwrap_or_default();
+            Ok(HttpResponse::SeeOther()
+                .insert_header((LOCATION, format!("/{}/{}", owner_name, fork.name)))
+                .finish())
+        }
+        Err(msg) => Ok(HttpResponse::SeeOther()
+            .insert_header((
+                LOCATION,
+                format!("/{}/{}/forks?error={}", owner.name(), repo.name, url_encode(error_text(&msg))),
+            ))
+            .finish()),
+    }
+}
@@ -1,4 +1,5 @@
 use actix_web::{Result, web, HttpRequest, get};
+use crate::api::service;
 use crate::db::Database;
 use crate::frontend::components;
 use crate::frontend::repo::utils;
@@ -13,19 +14,32 @@ pub async fn index(
     path: web::Path<(String, String)>,
 ) -> Result<Markup> {
     let (username, reponame) = path.into_inner();
-    let (owner, repo) = utils::resolve_owner_repo(&db, &username, &reponame).await?;
+    let (owner, repo, actor) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
     let user_display = utils::token_display(&db, &req).await;
+    let parent = match service::repo_fork_parent(&db, actor.user, &repo).await {
+        Ok(Some(p)) => service::owner_name_by_id(&db, &p.user)
+            .await
+            .ok()
+            .flatten()
+            .map(|owner_name| format!("{}/{}", owner_name, p.name)),
+        _ => None,
+    };
+    let fork_count = service::repo_forks(&db, actor.user, &repo._id.to_hex())
+        .await
+        .map(|f| f.len())
+        .unwrap_or(0);
     let conn = req.connection_info();
-    let clone_url = format!("{}://{}/{}/{}.git", conn.scheme(), conn.host(), owner.username, repo.name);
+    let clone_url = format!("{}://{}/{}/{}.git", conn.scheme(), conn.host(), owner.name(), repo.name);
+    let ssh_url = crate::ssh::clone_url(conn.host(), owner.name(), &repo.name);
 
     let default = utils::default_ref(&db, &owner, &repo).await;
 
-    let entries = repo::list_tree(&owner._id, &repo._id, &default, Some(&default), None)
+    let entries = repo::list_tree(owner.id(), &repo._id, &default, Some(&default), None)
         .await
         .unwrap_or_default();
     
-    let branches = repo::list_branches(&owner._id, &repo._id).await.unwrap_or_default();
-    let total_commits = repo::list_commits(&owner._id, &repo._id, &default, Some(&default), 0)
+    let branches = repo::list_branches(owner.id(), &repo._id).await.unwrap_or_default();
+    let total_commits = repo::list_commits(owner.id(), &repo._id, &default, Some(&default), 0)
         .await
         .map(|v| v.len())
         .unwrap_or(0);
@@ -33,7 +47,7 @@ pub async fn index(
     let readme = async {
         let candidates = ["README.md", "Readme.md", "readme.md", "README.MD"];
         for c in candidates {
-            if let Ok(bytes) = repo::get_file_content(&owner._id, &repo._id, &default, Some(&default), c).await {
+            if let Ok(bytes) = repo::get_file_content(owner.id(), &repo._id, &default, Some(&default), c).await {
                 if let Some(markup) = utils::render_readme_html(&bytes) {
                     return Some(markup);
                 }
@@ -43,7 +57,7 @@ pub async fn index(
     }.await;
 
     let content = html! {
-        (components::repo_header(user_display.as_deref(), &owner.username, &repo.name, repo.is_private))
+        (components::repo_header(user_display.as_deref(), owner.name(), &repo.name, repo.is_private))
         div class="container" {
             div class="main-content" {
                 div class="left-content" {
@@ -57,7 +71,7 @@ pub async fn index(
                                 ul class="branch-menu" {
                                     @for b in &branches {
                                         li {
-                                            a href={(format!("/{}/{}/tree/{}", owner.username, repo.name, b.name))} {
+                                            a href={(format!("/{}/{}/tree/{}", owner.name(), repo.name, b.name))} {
                                                 @if b.is_head { span class="badge-head" { "HEAD" } }
                                                 (b.name.clone())
                                             }
@@ -65,31 +79,45 @@ pub async fn index(
                                     }
                                     li {
                                         hr {};
-                                        a class="see-all-branches" href={(format!("/{}/{}/branches", owner.username, repo.name))} {
+                                        a class="see-all-branches" href={(format!("/{}/{}/branches", owner.name(), repo.name))} {
                                             "See all branches"
                                         }
                                     }
                                 }
                             }
                             div class="commit-info" { "" }
-                            a class="commits-btn" href={(format!("/{}/{}/commits/{}", owner.username, repo.name, default))} {
+                            a class="commits-btn" href={(format!("/{}/{}/commits/{}", owner.name(), repo.name, default))} {
                                 "Commits "
                                 span class="badge" { (total_commits) }
                             }
                             div class="repo-actions" {
+                                @if actor.user.is_some() {
+                                    form method="post" action={(format!("/{}/{}/fork", owner.name(), repo.name))} {
+                                        button class="action-btn" type="submit" { "Fork" }
+                                    }
+                                }
+                                a class="action-btn" href={(format!("/{}/{}/forks", owner.name(), repo.name))} {
+                                    "Forks "
+                                    span class="badge" { (fork_count) }
+                                }
                                 div class="code-menu" {
                                     button class="action-btn menu-trigger" type="button" { "Code" }
                                     div class="code-popup" {
                                         div class="clone-field" {
                                             input type="text" readonly value=(clone_url.clone()) {}
                                         }
+                                        @if let Some(url) = &ssh_url {
+                                            div class="clone-field" {
+                                                input type="text" readonly value=(url) {}
+                                            }
+                                        }
                                         a class="download-zip" href={(format!("/api/v1/download?id={}&branch={}", repo._id, default))} { "Download as ZIP" }
                                     }
                                 }
                             }
                         }
-                        (utils::breadcrumbs(&owner.username, &repo.name, &default, None))
-                        (utils::file_list(&owner.username, &repo.name, &default, None, &entries))
+                        (utils::breadcrumbs(owner.name(), &repo.name, &default, None))
+                        (utils::file_list(owner.name(), &repo.name, &default, None, &entries))
                     }
 
                     @if let Some(readme_html) = readme {
@@ -110,6 +138,24 @@ pub async fn index(
                         div class="sidebar-header" { "About" }
                         div class="sidebar-content" {
                             div class="description" { (repo.description.clone()) }
+                            @if let Some(website) = &repo.website {
+                                div class="website" {
+                                    a href=(website) rel="nofollow noopener" { (website) }
+                                }
+                            }
+                            @if !repo.topics.is_empty() {
+                                div class="topics" {
+                                    @for topic in &repo.topics {
+                                        span class="topic" { (topic) }
+                                    }
+                                }
+                            }
+                            @if let Some(parent) = &parent {
+                                div class="forked-from" {
+                                    "Forked from "
+                                    a href={(format!("/{}", parent))} { (parent) }
+                                }
+                            }
                         }
                     }
                 }
@@ -117,5 +163,5 @@ pub async fn index(
         }
     };
 
-    Ok(utils::page_shell(&format!("{} / {}", owner.username, repo.name), content, user_display.as_deref()))
+    Ok(utils::page_shell(&format!("{} / {}", owner.name(), repo.name), content, user_display.as_deref()))
 }
\ No newline at end of file
@@ -5,6 +5,8 @@ mod commits;
 pub mod new;
 pub mod utils;
 pub mod branches;
+pub mod forks;
+pub mod settings;
 
 pub use index::*;
 pub use tree::*;
@@ -1,7 +1,7 @@
 use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
 use maud::{html, Markup};
 
-use crate::db::Database;
+use crate::db::{Database, OrgRole};
 use crate::frontend::{components, SERVE_PATH};
 use crate::frontend::repo::utils;
 use crate::api::service;
@@ -11,6 +11,7 @@ pub struct NewRepoForm {
     pub name: String,
     pub description: Option<String>,
     pub visibility: Option<String>,
+    pub owner: Option<String>,
     //pub init_readme: Option<String>, TODO: INIT README
 }
 
@@ -43,11 +44,12 @@ pub async fn post(db: web::Data<Database>, req: HttpRequest, form: web::Form<New
         name: form.name.trim().to_string(),
         description: form.description.clone().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
         is_private,
+        owner: form.owner.clone().filter(|s| !s.is_empty()),
     };
 
     match service::repo_create(&db, user_id.clone(), payload).await {
         Ok(repo) => {
-            let owner = service::username_by_id(&db, &user_id).await.ok().flatten().unwrap_or_else(|| "me".to_string());
+            let owner = service::owner_name_by_id(&db, &repo.user).await.ok().flatten().unwrap_or_else(|| "me".to_string());
             let location = format!("/{}/{}", owner, repo.name);
             Ok(HttpResponse::SeeOther()
                 .append_header((actix_web::http::header::LOCATION, location))
@@ -66,6 +68,20 @@ pub async fn post(db: web::Data<Database>, req: HttpRequest, form: web::Form<New
 pub async fn get(db: web::Data<Database>, req: HttpRequest) -> Result<Markup> {
     let user_display = utils::token_display(&db, &req).await;
 
+    let mut owners = Vec::new();
+    let mut unverified = false;
+    if let Some(uid) = utils::requester_id(&db, &req).await {
+        if let Ok(Some(user)) = db.find_user_by_id(&uid).await {
+            unverified = crate::auth::email_verification_pending(&user);
+            owners.push(user.username);
+        }
+        for (org, role) in db.orgs_for_user(&uid).await.unwrap_or_default() {
+            if role == OrgRole::Owner {
+                owners.push(org.name);
+            }
+        }
+    }
+
     Ok(html! {
         (maud::DOCTYPE)
         html lang="en" {
@@ -80,11 +96,26 @@ pub async fn get(db: web::Data<Database>, req: HttpRequest) -> Result<Markup> {
                             p class="sub" { "A repository contains all project files, including the revision history." }
                         }
 
+                        @if unverified {
+                            (components::alert(components::AlertKind::Warning, "Verify your email address before creating repositories. You can resend the link from your account settings."))
+                        }
+
                         form class="new-repo-form" method="post" action="/new" {
+                            @if owners.len() > 1 {
+                                fieldset class="form-group" {
+                                    label for="repo-owner" { "Owner" }
+                                    select id="repo-owner" name="owner" {
+                                        @for name in &owners {
+                                            option value=(name) { (name) }
+                                        }
+                                    }
+                                }
+                            }
+
                             fieldset class="form-group" {
                                 label for="repo-name" { "Repository name" }
-                                input type="text" id="repo-name" name="name" placeholder="my-awesome-project" required {}
-                                p class="help" { "Great repository names are short and memorable." }
+                                input type="text" id="repo-name" name="name" placeholder="my-awesome-project" pattern="[A-Za-z0-9_\\-][A-Za-z0-9_.\\-]*" maxlength="100" required {}
+                                p class="help" { "Great repository names are short and memorable. Use letters, digits, '-', '_' and '.'." }
                             }
 
                             fieldset class="form-group" {
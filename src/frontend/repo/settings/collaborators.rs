@@ -0,0 +1,162 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+use actix_web::http::{header::LOCATION, StatusCode};
+use maud::html;
+
+use crate::api::service;
+use crate::db::{Database, Role};
+use crate::policy::{self, Action};
+use crate::frontend::components::{self, RepoTab};
+use crate::frontend::errors::render_error_page;
+use crate::frontend::repo::settings::{settings_nav, Section};
+use crate::frontend::repo::utils;
+use crate::models::AddCollaboratorRequest;
+
+#[derive(serde::Deserialize)]
+pub struct MessageQuery {
+    error: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct CollaboratorForm {
+    pub username: String,
+    pub role: Option<String>,
+}
+
+fn forbidden() -> HttpResponse {
+    render_error_page(
+        "403 - Forbidden",
+        "Forbidden",
+        "You do not have permission to manage this repository.",
+        StatusCode::FORBIDDEN,
+    )
+}
+
+#[get("/{username}/{reponame}/settings/collaborators")]
+pub async fn get(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<MessageQuery>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, actor) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    if !policy::can(&actor, Action::Admin, &repo) {
+        return Ok(forbidden());
+    }
+    let user_display = utils::token_display(&db, &req).await;
+
+    let collaborators = service::collaborators_list(&db, actor.user, &repo._id.to_hex())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let action = format!("/{}/{}/settings/collaborators", owner.name(), repo.name);
+
+    let content = html! {
+        (components::repo_header_tab(user_display.as_deref(), owner.name(), &repo.name, repo.is_private, RepoTab::Settings))
+        div class="container settings-layout" {
+            (settings_nav(owner.name(), &repo.name, Section::Collaborators))
+            div class="settings-content" {
+                h2 { "Collaborators" }
+                @if let Some(err) = &query.error {
+                    (components::alert(components::AlertKind::Error, err))
+                }
+                @if collaborators.is_empty() {
+                    p class="muted" { "No collaborators yet." }
+                } @else {
+                    ul class="settings-list" {
+                        @for c in &collaborators {
+                            li {
+                                a href={(format!("/{}", c.username))} { (c.display_name) " " span class="muted" { "@" (c.username) } }
+                                span class="badge" { (c.role.as_str()) }
+                                form method="post" action={(format!("{}/remove", action))} {
+                                    input type="hidden" name="username" value=(c.username) {}
+                                    button type="submit" class="branch-delete-btn" { "Remove" }
+                                }
+                            }
+                        }
+                    }
+                }
+                form class="settings-form" method="post" action=(action) {
+                    h3 { "Add collaborator" }
+                    div class="form-row" {
+                        input type="text" name="username" placeholder="Username or email" required {}
+                        select name="role" {
+                            option value="read" { "Read" }
+                            option value="write" selected { "Write" }
+                            option value="admin" { "Admin" }
+                        }
+                        button type="submit" class="action-btn" { "Add" }
+                    }
+                }
+            }
+        }
+    };
+
+    let page = utils::page_shell(
+        &format!("{} / {} - collaborators", owner.name(), repo.name),
+        content,
+        user_display.as_deref(),
+    );
+    Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string()))
+}
+
+#[post("/{username}/{reponame}/settings/collaborators")]
+pub async fn add(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<CollaboratorForm>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, _) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+
+    let back = format!("/{}/{}/settings/collaborators", owner.name(), repo.name);
+    let role = match form.role.as_deref().and_then(Role::parse) {
+        Some(r) => r,
+        None => {
+            return Ok(HttpResponse::SeeOther()
+                .insert_header((LOCATION, format!("{}?error={}", back, crate::frontend::url_encode("Invalid role"))))
+                .finish());
+        }
+    };
+
+    let payload = AddCollaboratorRequest {
+        id: repo._id.to_hex(),
+        username: form.username.clone(),
+        role,
+    };
+    match service::collaborator_add(&db, requester, payload).await {
+        Ok(()) => Ok(HttpResponse::SeeOther().insert_header((LOCATION, back)).finish()),
+        Err(msg) if msg == "forbidden" => Ok(forbidden()),
+        Err(msg) => Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, format!("{}?error={}", back, crate::frontend::url_encode(&msg))))
+            .finish()),
+    }
+}
+
+#[post("/{username}/{reponame}/settings/collaborators/remove")]
+pub async fn remove(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<CollaboratorForm>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, _) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+
+    let back = format!("/{}/{}/settings/collaborators", owner.name(), repo.name);
+    match service::collaborator_remove(&db, requester, &repo._id.to_hex(), &form.username).await {
+        Ok(()) => Ok(HttpResponse::SeeOther().insert_header((LOCATION, back)).finish()),
+        Err(msg) if msg == "forbidden" => Ok(forbidden()),
+        Err(msg) => Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, format!("{}?error={}", back, crate::frontend::url_encode(&msg))))
+            .finish()),
+    }
+}
@@ -0,0 +1,335 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+use actix_web::http::{header::LOCATION, StatusCode};
+use maud::{html, Markup};
+
+use crate::api::service;
+use crate::db::{Database, Owner, Repository};
+use crate::policy::{self, Action};
+use crate::frontend::components::{self, RepoTab};
+use crate::frontend::errors::render_error_page;
+use crate::frontend::repo::settings::{settings_nav, Section};
+use crate::frontend::repo::utils;
+use crate::frontend::url_encode;
+use crate::models::{AddDeployKeyRequest, CreateDeployTokenRequest, DeployKeyInfo, DeployTokenInfo};
+
+#[derive(serde::Deserialize)]
+pub struct MessageQuery {
+    error: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewKeyForm {
+    pub name: String,
+    pub key: String,
+    pub write: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewTokenForm {
+    pub name: String,
+    pub write: Option<String>,
+    /// Days until expiry, or "never"
+    pub expiry: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteForm {
+    pub id: String,
+}
+
+fn forbidden() -> HttpResponse {
+    render_error_page(
+        "403 - Forbidden",
+        "Forbidden",
+        "You do not have permission to manage this repository.",
+        StatusCode::FORBIDDEN,
+    )
+}
+
+fn format_date(dt: &bson::DateTime) -> String {
+    utils::format_time(dt.timestamp_millis() / 1000).chars().take(10).collect()
+}
+
+fn error_text(msg: &str) -> &str {
+    match msg {
+        "invalid key" => "That is not an OpenSSH public key",
+        "already exists" => "This key is already in use",
+        "name must not be empty" => "Name must not be empty",
+        "invalid expiry" => "Invalid expiration",
+        "deploy key not found" => "Deploy key not found",
+        "deploy token not found" => "Deploy token not found",
+        _ => "Something went wrong",
+    }
+}
+
+fn back(owner: &Owner, repo: &Repository, error: Option<&str>) -> HttpResponse {
+    let mut location = format!("/{}/{}/settings/deploy", owner.name(), repo.name);
+    if let Some(msg) = error {
+        location = format!("{}?error={}", location, url_encode(error_text(msg)));
+    }
+    HttpResponse::SeeOther().insert_header((LOCATION, location)).finish()
+}
+
+fn access_badge(write: bool) -> Markup {
+    html! { span class="badge" { @if write { "read/write" } @else { "read-only" } } }
+}
+
+fn last_used(used: Option<&bson::DateTime>) -> Markup {
+    html! {
+        @match used {
+            Some(used) => { " · last used " (format_date(used)) }
+            None => { " · never used" }
+        }
+    }
+}
+
+struct Page<'a> {
+    owner: &'a Owner,
+    repo: &'a Repository,
+    keys: &'a [DeployKeyInfo],
+    tokens: &'a [DeployTokenInfo],
+    created: Option<&'a str>,
+    error: Option<&'a str>,
+}
+
+fn render(page: Page, user_display: Option<&str>) -> HttpResponse {
+    let base = format!("/{}/{}/settings/deploy", page.owner.name(), page.repo.name);
+    let content = html! {
+        (components::repo_header_tab(user_display, page.owner.name(), &page.repo.name, page.repo.is_private, RepoTab::Settings))
+        div class="container settings-layout" {
+            (settings_nav(page.owner.name(), &page.repo.name, Section::Deploy))
+            div class="settings-content" {
+                h2 { "Deploy keys" }
+                p class="muted" { "Deploy keys and tokens give CI and deployment hosts access to this repository only. They are read-only unless write access is granted." }
+                @if let Some(err) = page.error {
+                    (components::alert(components::AlertKind::Error, err))
+                }
+                @if !crate::ssh::enabled() {
+                    (components::alert(components::AlertKind::Warning, "Git over SSH is not enabled on this server, so deploy keys cannot be used yet."))
+                }
+                @if page.keys.is_empty() {
+                    p class="muted" { "No deploy keys yet." }
+                } @else {
+                    ul class="settings-list" {
+                        @for k in page.keys {
+                            li {
+                                span class="grow" {
+                                    strong { (k.name) } " " (access_badge(k.write))
+                                    br;
+                                    code { (k.fingerprint) }
+                                    br;
+                                    span class="muted" { "Added " (format_date(&k.created_at)) (last_used(k.last_used_at.as_ref())) }
+                                }
+                                form method="post" action={(format!("{}/keys/delete", base))} {
+                                    input type="hidden" name="id" value=(k.id) {}
+                                    button type="submit" class="branch-delete-btn" { "Delete" }
+                                }
+                            }
+                        }
+                    }
+                }
+                form class="settings-form" method="post" action={(format!("{}/keys", base))} {
+                    h3 { "Add deploy key" }
+                    label for="key-name" { "Title" }
+                    input type="text" id="key-name" name="name" placeholder="Production server" {}
+                    label for="key-value" { "Key" }
+                    textarea id="key-value" name="key" rows="5" placeholder="Begins with 'ssh-ed25519', 'ecdsa-sha2-nistp256' or 'ssh-rsa'" required {}
+                    label { input type="checkbox" name="write" value="on" {} " Allow write access" }
+                    button type="submit" class="action-btn" { "Add key" }
+                }
+
+                h2 { "Deploy tokens" }
+                p class="muted" { "Use a deploy token as the password for git over HTTPS; the user name is ignored." }
+                @if let Some(value) = page.created {
+                    (components::alert(components::AlertKind::Success, "Token created. Copy it now, it will not be shown again."))
+                    p { code class="token-value" { (value) } }
+                }
+                @if page.tokens.is_empty() {
+                    p class="muted" { "No deploy tokens yet." }
+                } @else {
+                    ul class="settings-list" {
+                        @for t in page.tokens {
+                            li {
+                                span class="grow" {
+                                    strong { (t.name) } " " (access_badge(t.write))
+                                    br;
+                                    span class="muted" {
+                                        "Created " (format_date(&t.created_at))
+                                        @match &t.expires_at {
+                                            Some(exp) => { " · expires " (format_date(exp)) }
+                                            None => { " · never expires" }
+                                        }
+                                        (last_used(t.last_used_at.as_ref()))
+                                    }
+                                }
+                                form method="post" action={(format!("{}/tokens/revoke", base))} {
+                                    input type="hidden" name="id" value=(t.id) {}
+                                    button type="submit" class="branch-delete-btn" { "Revoke" }
+                                }
+                            }
+                        }
+                    }
+                }
+                form class="settings-form" method="post" action={(format!("{}/tokens", base))} {
+                    h3 { "Generate deploy token" }
+                    label for="token-name" { "Name" }
+                    input type="text" id="token-name" name="name" placeholder="CI" required {}
+                    label for="token-expiry" { "Expiration" }
+                    select id="token-expiry" name="expiry" {
+                        option value="30" { "30 days" }
+                        option value="90" { "90 days" }
+                        option value="365" selected { "1 year" }
+                        option value="never" { "No expiration" }
+                    }
+                    label { input type="checkbox" name="write" value="on" {} " Allow write access" }
+                    button type="submit" class="action-btn" { "Generate token" }
+                }
+            }
+        }
+    };
+
+    let title = format!("{} / {} - deploy keys", page.owner.name(), page.repo.name);
+    let page = utils::page_shell(&title, content, user_display);
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string())
+}
+
+#[get("/{username}/{reponame}/settings/deploy")]
+pub async fn get(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<MessageQuery>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, actor) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let requester = match actor.user {
+        Some(uid) if policy::can(&actor, Action::Admin, &repo) => uid,
+        _ => return Ok(forbidden()),
+    };
+    let user_display = utils::token_display(&db, &req).await;
+
+    let repo_id = repo._id.to_hex();
+    let keys = service::deploy_keys_list(&db, requester, &repo_id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let tokens = service::deploy_tokens_list(&db, requester, &repo_id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let page = Page { owner: &owner, repo: &repo, keys: &keys, tokens: &tokens, created: None, error: query.error.as_deref() };
+    Ok(render(page, user_display.as_deref()))
+}
+
+#[post("/{username}/{reponame}/settings/deploy/keys")]
+pub async fn add_key(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<NewKeyForm>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, _) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+
+    let payload = AddDeployKeyRequest {
+        id: repo._id.to_hex(),
+        name: form.name.clone(),
+        key: form.key.clone(),
+        write: form.write.is_some(),
+    };
+    match service::deploy_key_add(&db, requester, payload).await {
+        Ok(_) => Ok(back(&owner, &repo, None)),
+        Err(msg) if msg == "forbidden" => Ok(forbidden()),
+        Err(msg) => Ok(back(&owner, &repo, Some(&msg))),
+    }
+}
+
+#[post("/{username}/{reponame}/settings/deploy/keys/delete")]
+pub async fn delete_key(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<DeleteForm>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, _) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+
+    match service::deploy_key_delete(&db, requester, &form.id).await {
+        Ok(()) => Ok(back(&owner, &repo, None)),
+        Err(msg) if msg == "forbidden" => Ok(forbidden()),
+        Err(msg) => Ok(back(&owner, &repo, Some(&msg))),
+    }
+}
+
+#[post("/{username}/{reponame}/settings/deploy/tokens")]
+pub async fn create_token(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<NewTokenForm>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, _) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+    let expires_in_days = match form.expiry.as_str() {
+        "never" => None,
+        days => match days.parse::<u32>() {
+            Ok(d) => Some(d),
+            Err(_) => return Ok(back(&owner, &repo, Some("invalid expiry"))),
+        },
+    };
+
+    let repo_id = repo._id.to_hex();
+    let payload = CreateDeployTokenRequest {
+        id: repo_id.clone(),
+        name: form.name.clone(),
+        write: form.write.is_some(),
+        expires_in_days,
+    };
+    let created = match service::deploy_token_create(&db, requester, payload).await {
+        Ok(c) => c,
+        Err(msg) if msg == "forbidden" => return Ok(forbidden()),
+        Err(msg) => return Ok(back(&owner, &repo, Some(&msg))),
+    };
+
+    // the secret is only ever shown in this response
+    let user_display = utils::token_display(&db, &req).await;
+    let keys = service::deploy_keys_list(&db, requester, &repo_id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let tokens = service::deploy_tokens_list(&db, requester, &repo_id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let page = Page { owner: &owner, repo: &repo, keys: &keys, tokens: &tokens, created: Some(&created.token), error: None };
+    Ok(render(page, user_display.as_deref()))
+}
+
+#[post("/{username}/{reponame}/settings/deploy/tokens/revoke")]
+pub async fn revoke_token(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<DeleteForm>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, _) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+
+    match service::deploy_token_revoke(&db, requester, &form.id).await {
+        Ok(()) => Ok(back(&owner, &repo, None)),
+        Err(msg) if msg == "forbidden" => Ok(forbidden()),
+        Err(msg) => Ok(back(&owner, &repo, Some(&msg))),
+    }
+}
@@ -0,0 +1,295 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+use actix_web::http::{header::LOCATION, StatusCode};
+use maud::html;
+
+use crate::api::service;
+use crate::db::{Database, OrgRole};
+use crate::policy::{self, Action};
+use crate::frontend::components::{self, RepoTab};
+use crate::frontend::errors::render_error_page;
+use crate::frontend::repo::settings::{settings_nav, Section};
+use crate::frontend::repo::utils;
+use crate::frontend::url_encode;
+use crate::models::{RenameRepoRequest, TransferRepoRequest, UpdateRepoRequest};
+
+#[derive(serde::Deserialize)]
+pub struct MessageQuery {
+    error: Option<String>,
+    saved: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SettingsForm {
+    pub description: String,
+    pub website: String,
+    /// Comma separated
+    pub topics: String,
+    pub visibility: String,
+    pub default_branch: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RenameForm {
+    pub name: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TransferForm {
+    pub owner: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ConfirmForm {
+    pub confirm_name: String,
+}
+
+fn forbidden() -> HttpResponse {
+    render_error_page(
+        "403 - Forbidden",
+        "Forbidden",
+        "You do not have permission to manage this repository.",
+        StatusCode::FORBIDDEN,
+    )
+}
+
+fn error_text(msg: &str) -> &str {
+    match msg {
+        "invalid name" => "Repository names may only contain letters, digits, '-', '_' and '.'",
+        "already exists" => "A repository with this name already exists there",
+        "owner not found" => "No user or organization with this name",
+        "cannot transfer to this owner" => "You can only transfer to an organization you own, or to an active user",
+        "invalid website" => "The website must be an http:// or https:// URL",
+        "invalid topic" => "Topics may only contain lowercase letters, digits and '-', up to 35 characters",
+        "too many topics" => "A repository can have at most 20 topics",
+        "forks of private repositories must stay private" => "Forks of a private repository must stay private",
+        "branch not found" => "That branch does not exist",
+        "name mismatch" => "The repository name does not match",
+        _ => "Something went wrong",
+    }
+}
+
+fn back_with_error(back: &str, msg: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((LOCATION, format!("{}?error={}", back, url_encode(error_text(msg)))))
+        .finish()
+}
+
+#[get("/{username}/{reponame}/settings")]
+pub async fn get(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<MessageQuery>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, actor) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    if !policy::can(&actor, Action::Admin, &repo) {
+        return Ok(forbidden());
+    }
+    let user_display = utils::token_display(&db, &req).await;
+
+    // suggestions for the new owner: the requester and the organizations they own;
+    // any other user name is accepted as well
+    let mut namespaces = Vec::new();
+    if let Some(uid) = &actor.user {
+        if let Some(name) = service::username_by_id(&db, uid).await.map_err(actix_web::error::ErrorInternalServerError)? {
+            namespaces.push(name);
+        }
+        for (org, role) in db.orgs_for_user(uid).await.map_err(actix_web::error::ErrorInternalServerError)? {
+            if role == OrgRole::Owner {
+                namespaces.push(org.name);
+            }
+        }
+    }
+    namespaces.retain(|n| n != owner.name());
+    let base = format!("/{}/{}/settings", owner.name(), repo.name);
+    let full_name = format!("{}/{}", owner.name(), repo.name);
+    let branches = crate::repo::list_branches(owner.id(), &repo._id).await.unwrap_or_default();
+
+    let content = html! {
+        (components::repo_header_tab(user_display.as_deref(), owner.name(), &repo.name, repo.is_private, RepoTab::Settings))
+        div class="container settings-layout" {
+            (settings_nav(owner.name(), &repo.name, Section::General))
+            div class="settings-content" {
+                h2 { "General" }
+                @if let Some(err) = &query.error {
+                    (components::alert(components::AlertKind::Error, err))
+                } @else if query.saved.is_some() {
+                    (components::alert(components::AlertKind::Success, "Settings saved"))
+                }
+                form class="settings-form" method="post" action=(base) {
+                    h3 { "Details" }
+                    label for="repo-desc" { "Description" }
+                    textarea id="repo-desc" name="description" rows="3" { (repo.description) }
+                    label for="repo-website" { "Website" }
+                    input type="url" id="repo-website" name="website" value=(repo.website.as_deref().unwrap_or("")) placeholder="https://example.com" {}
+                    label for="repo-topics" { "Topics" }
+                    input type="text" id="repo-topics" name="topics" value=(repo.topics.join(", ")) placeholder="rust, web-server" {}
+                    p class="help" { "Separate topics with commas." }
+                    label for="repo-visibility" { "Visibility" }
+                    select id="repo-visibility" name="visibility" {
+                        option value="public" selected[!repo.is_private] { "Public" }
+                        option value="private" selected[repo.is_private] { "Private" }
+                    }
+                    @if !branches.is_empty() {
+                        label for="repo-branch" { "Default branch" }
+                        select id="repo-branch" name="default_branch" {
+                            @for b in &branches {
+                                option value=(b.name) selected[b.is_head] { (b.name) }
+                            }
+                        }
+                    }
+                    button type="submit" class="action-btn" { "Save" }
+                }
+                form class="settings-form" method="post" action={(format!("{}/rename", base))} {
+                    h3 { "Repository name" }
+                    p class="help" { "Links and clone URLs using the old name keep working until another repository takes it." }
+                    div class="form-row" {
+                        input type="text" name="name" value=(repo.name) required {}
+                        button type="submit" class="action-btn" { "Rename" }
+                    }
+                }
+                form class="settings-form danger-zone" method="post" action={(format!("{}/transfer", base))} {
+                    h3 { "Transfer ownership" }
+                    p class="help" { "Moves the repository to another user, or to an organization you own. You lose access unless the new owner grants it. Collaborators and team access are removed; old links keep redirecting." }
+                    div class="form-row" {
+                        input type="text" name="owner" list="transfer-owners" placeholder="New owner" required {}
+                        datalist id="transfer-owners" {
+                            @for n in &namespaces {
+                                option value=(n) {}
+                            }
+                        }
+                        button type="submit" class="branch-delete-btn" { "Transfer" }
+                    }
+                }
+                form class="settings-form danger-zone" method="post" action={(format!("{}/delete", base))} {
+                    h3 { "Delete repository" }
+                    p class="help" { "This cannot be undone. Type " code { (full_name) } " to confirm." }
+                    input type="text" name="confirm_name" placeholder=(full_name) required {}
+                    button type="submit" class="branch-delete-btn" { "Delete repository" }
+                }
+            }
+        }
+    };
+
+    let page = utils::page_shell(
+        &format!("{} / {} - settings", owner.name(), repo.name),
+        content,
+        user_display.as_deref(),
+    );
+    Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string()))
+}
+
+#[post("/{username}/{reponame}/settings")]
+pub async fn update(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<SettingsForm>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, _) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+
+    let back = format!("/{}/{}/settings", owner.name(), repo.name);
+    let form = form.into_inner();
+    let payload = UpdateRepoRequest {
+        id: repo._id.to_hex(),
+        description: Some(form.description),
+        is_private: Some(form.visibility == "private"),
+        website: Some(form.website),
+        topics: Some(form.topics.split(',').map(str::to_string).collect()),
+        default_branch: form.default_branch,
+    };
+    match service::repo_update(&db, requester, payload).await {
+        Ok(_) => Ok(HttpResponse::SeeOther().insert_header((LOCATION, format!("{}?saved=1", back))).finish()),
+        Err(msg) if msg == "forbidden" => Ok(forbidden()),
+        Err(msg) => Ok(back_with_error(&back, &msg)),
+    }
+}
+
+#[post("/{username}/{reponame}/settings/rename")]
+pub async fn rename(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<RenameForm>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, _) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+
+    let back = format!("/{}/{}/settings", owner.name(), repo.name);
+    let payload = RenameRepoRequest { id: repo._id.to_hex(), name: form.name.clone() };
+    match service::repo_rename(&db, requester, payload).await {
+        Ok(renamed) => Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, format!("/{}/{}/settings", owner.name(), renamed.name)))
+            .finish()),
+        Err(msg) if msg == "forbidden" => Ok(forbidden()),
+        Err(msg) => Ok(back_with_error(&back, &msg)),
+    }
+}
+
+#[post("/{username}/{reponame}/settings/transfer")]
+pub async fn transfer(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<TransferForm>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, _) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+
+    let back = format!("/{}/{}/settings", owner.name(), repo.name);
+    let payload = TransferRepoRequest { id: repo._id.to_hex(), owner: form.owner.clone() };
+    match service::repo_transfer(&db, requester, payload).await {
+        Ok(moved) => {
+            // after a transfer to another user the requester may no longer see the repository
+            let actor = service::repo_actor(&db, Some(requester), &moved)
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?;
+            let location = match policy::can(&actor, Action::Read, &moved) {
+                true => format!("/{}/{}", form.owner.trim(), moved.name),
+                false => format!("/{}", owner.name()),
+            };
+            Ok(HttpResponse::SeeOther().insert_header((LOCATION, location)).finish())
+        }
+        Err(msg) if msg == "forbidden" => Ok(forbidden()),
+        Err(msg) => Ok(back_with_error(&back, &msg)),
+    }
+}
+
+#[post("/{username}/{reponame}/settings/delete")]
+pub async fn delete(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<ConfirmForm>,
+) -> Result<HttpResponse> {
+    let (username, reponame) = path.into_inner();
+    let (owner, repo, _) = utils::resolve_owner_repo(&db, &req, &username, &reponame).await?;
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+
+    let back = format!("/{}/{}/settings", owner.name(), repo.name);
+    if form.confirm_name.trim() != format!("{}/{}", owner.name(), repo.name) {
+        return Ok(back_with_error(&back, "name mismatch"));
+    }
+    match service::repo_delete(&db, requester, &repo._id.to_hex()).await {
+        Ok(()) => Ok(HttpResponse::SeeOther().insert_header((LOCATION, format!("/{}", owner.name()))).finish()),
+        Err(msg) if msg == "forbidden" => Ok(forbidden()),
+        Err(msg) => Ok(back_with_error(&back, &msg)),
+    }
+}
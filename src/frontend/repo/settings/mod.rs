@@ -0,0 +1,23 @@
+pub mod collaborators;
+pub mod deploy;
+pub mod general;
+
+use maud::{html, Markup};
+
+#[derive(PartialEq, Eq)]
+pub enum Section {
+    General,
+    Collaborators,
+    Deploy,
+}
+
+pub fn settings_nav(owner_slug: &str, repo_slug: &str, active: Section) -> Markup {
+    let base = format!("/{}/{}/settings", owner_slug, repo_slug);
+    html! {
+        nav class="settings-nav" {
+            a.active[active == Section::General] href=(base) { "General" }
+            a.active[active == Section::Collaborators] href={(format!("{}/collaborators", base))} { "Collaborators" }
+            a.active[active == Section::Deploy] href={(format!("{}/deploy", base))} { "Deploy keys" }
+        }
+    }
+}
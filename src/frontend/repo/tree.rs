@@ -13,10 +13,11 @@ async fn render_tree_page(
     rev_in: String,
     subpath: Option<String>,
 ) -> Result<Markup> {
-    let (owner, repo) = utils::resolve_owner_repo(db, &username, &reponame).await?;
+    let (owner, repo, _) = utils::resolve_owner_repo(db, req, &username, &reponame).await?;
     let user_display = utils::token_display(db, req).await;
     let conn = req.connection_info();
-    let clone_url = format!("{}://{}/{}/{}.git", conn.scheme(), conn.host(), owner.username, repo.name);
+    let clone_url = format!("{}://{}/{}/{}.git", conn.scheme(), conn.host(), owner.name(), repo.name);
+    let ssh_url = crate::ssh::clone_url(conn.host(), owner.name(), &repo.name);
 
     let rev_owned = rev_in.clone();
     let (rev, branch_opt_owned): (String, Option<String>) = if utils::is_hex_hash(&rev_in) {
@@ -27,19 +28,19 @@ async fn render_tree_page(
     let branch_opt = branch_opt_owned.as_deref();
     let path_opt = subpath.as_deref();
 
-    let entries = repo::list_tree(&owner._id, &repo._id, &rev, branch_opt, path_opt)
+    let entries = repo::list_tree(owner.id(), &repo._id, &rev, branch_opt, path_opt)
         .await
         .unwrap_or_default();
 
-    let branches = repo::list_branches(&owner._id, &repo._id).await.unwrap_or_default();
+    let branches = repo::list_branches(owner.id(), &repo._id).await.unwrap_or_default();
     let reference = branch_opt.unwrap_or(&rev);
-    let total_commits = repo::list_commits(&owner._id, &repo._id, reference, branch_opt, 0)
+    let total_commits = repo::list_commits(owner.id(), &repo._id, reference, branch_opt, 0)
         .await
         .map(|v| v.len())
         .unwrap_or(0);
 
     let content = html! {
-        (components::repo_header(user_display.as_deref(), &owner.username, &repo.name, repo.is_private))
+        (components::repo_header(user_display.as_deref(), owner.name(), &repo.name, repo.is_private))
         div class="container" {
             div class="main-content" {
                 div class="left-content" {
@@ -53,7 +54,7 @@ async fn render_tree_page(
                                 ul class="branch-menu" {
                                     @for b in &branches {
                                         li {
-                                            a href={(format!("/{}/{}/tree/{}", owner.username, repo.name, b.name))} {
+                                            a href={(format!("/{}/{}/tree/{}", owner.name(), repo.name, b.name))} {
                                                 @if b.is_head { span class="badge-head" { "HEAD" } }
                                                 (b.name.clone())
                                             }
@@ -62,7 +63,7 @@ async fn render_tree_page(
                                 }
                             }
                             div class="commit-info" { "" }
-                            a class="commits-btn" href={(format!("/{}/{}/commits/{}", owner.username, repo.name, reference))} {
+                            a class="commits-btn" href={(format!("/{}/{}/commits/{}", owner.name(), repo.name, reference))} {
                                 "Commits "
                                 span class="badge" { (total_commits) }
                             }
@@ -73,6 +74,11 @@ async fn render_tree_page(
                                         div class="clone-field" {
                                             input type="text" readonly value=(clone_url.clone()) {}
                                         }
+                                        @if let Some(url) = &ssh_url {
+                                            div class="clone-field" {
+                                                input type="text" readonly value=(url) {}
+                                            }
+                                        }
                                         @if utils::is_hex_hash(&reference) {
                                             a class="download-zip" href={(format!("/api/v1/download?id={}&commit={}", repo._id, reference))} { "Download ZIP" }
                                         } @else {
@@ -82,8 +88,8 @@ async fn render_tree_page(
                                 }
                             }
                         }
-                        (utils::breadcrumbs(&owner.username, &repo.name, reference, path_opt))
-                        (utils::file_list(&owner.username, &repo.name, reference, path_opt, &entries))
+                        (utils::breadcrumbs(owner.name(), &repo.name, reference, path_opt))
+                        (utils::file_list(owner.name(), &repo.name, reference, path_opt, &entries))
                     }
                 }
                 div class="sidebar" { }
@@ -91,7 +97,7 @@ async fn render_tree_page(
         }
     };
 
-    Ok(utils::page_shell(&format!("{} / {} - tree {}", owner.username, repo.name, rev), content, user_display.as_deref()))
+    Ok(utils::page_shell(&format!("{} / {} - tree {}", owner.name(), repo.name, rev), content, user_display.as_deref()))
 }
 
 #[get("/{username}/{reponame}/tree/{rev}")]
@@ -6,6 +6,7 @@ use crate::repo;
 use maud::{Markup, html, PreEscaped, DOCTYPE};
 use crate::api::service;
 use crate::db;
+use crate::policy::{self, Action, Actor};
 
 pub fn is_hex_hash(s: &str) -> bool {
     let len = s.len();
@@ -24,28 +25,99 @@ pub async fn token_display(db: &Database, req: &actix_web::HttpRequest) -> Optio
         None => None,
     }
 }
+pub async fn requester_id(db: &Database, req: &actix_web::HttpRequest) -> Option<bson::oid::ObjectId> {
+    match crate::frontend::token_from_req(req) {
+        Some(token) => service::get_user_id_from_token(db, token).await.ok(),
+        None => None,
+    }
+}
+
+/// Page returned both for repositories that do not exist and for private ones the
+/// viewer may not read, so the two cases are indistinguishable.
+fn repo_not_found() -> actix_web::Error {
+    actix_web::error::InternalError::from_response(
+        "repository not found",
+        crate::frontend::errors::render_error_page(
+            "404 - Page Not Found",
+            "Page not found",
+            "The page you are looking for does not exist.",
+            actix_web::http::StatusCode::NOT_FOUND,
+        ),
+    )
+    .into()
+}
+
+/// Redirects a former owner name to its current one, otherwise the usual not-found page.
+async fn renamed_or_not_found(db: &Database, req: &actix_web::HttpRequest, owner_name: &str) -> actix_web::Error {
+    match db.find_renamed_owner(owner_name).await {
+        Ok(Some(owner)) => actix_web::error::InternalError::from_response(
+            "owner renamed",
+            crate::frontend::renamed_owner_redirect(req, owner.name()),
+        )
+        .into(),
+        _ => repo_not_found(),
+    }
+}
+
+/// Redirects a former `owner/repo` path to where the repository lives now, provided the
+/// viewer may read it; otherwise the usual not-found page.
+async fn moved_or_not_found(db: &Database, req: &actix_web::HttpRequest, owner_id: &bson::oid::ObjectId, reponame: &str) -> actix_web::Error {
+    let repo = match db.find_renamed_repo(owner_id, reponame).await {
+        Ok(Some(r)) => r,
+        _ => return repo_not_found(),
+    };
+    let readable = match service::repo_actor(db, requester_id(db, req).await, &repo).await {
+        Ok(actor) => policy::can(&actor, Action::Read, &repo),
+        Err(_) => false,
+    };
+    match db.find_owner_by_id(&repo.user).await {
+        Ok(Some(owner)) if readable => actix_web::error::InternalError::from_response(
+            "repository moved",
+            crate::frontend::moved_repo_redirect(req, owner.name(), &repo.name),
+        )
+        .into(),
+        _ => repo_not_found(),
+    }
+}
+
+/// Resolves `owner_name/reponame` for the current viewer together with their policy actor.
+/// Every repository page goes through here so private repositories stay hidden.
 pub async fn resolve_owner_repo(
     db: &Database,
-    username: &str,
+    req: &actix_web::HttpRequest,
+    owner_name: &str,
     reponame: &str,
-) -> actix_web::Result<(db::User, db::Repository)> {
-    let owner = db
-        .find_user_by_login(username)
+) -> actix_web::Result<(db::Owner, db::Repository, Actor)> {
+    let owner = match db
+        .find_owner_by_name(owner_name)
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?
-        .ok_or_else(|| actix_web::error::ErrorNotFound("owner not found"))?;
+    {
+        Some(o) => o,
+        None => return Err(renamed_or_not_found(db, req, owner_name).await),
+    };
 
-    let repo = db
-        .find_repo_by_user_and_name(&owner._id, reponame)
+    let repo = match db
+        .find_repo_by_user_and_name(owner.id(), reponame)
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?
-        .ok_or_else(|| actix_web::error::ErrorNotFound("repository not found"))?;
+    {
+        Some(r) => r,
+        None => return Err(moved_or_not_found(db, req, owner.id(), reponame).await),
+    };
+
+    let actor = service::repo_actor(db, requester_id(db, req).await, &repo)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if !policy::can(&actor, Action::Read, &repo) {
+        return Err(repo_not_found());
+    }
 
-    Ok((owner, repo))
+    Ok((owner, repo, actor))
 }
 
-pub async fn default_ref(_db: &Database, owner: &db::User, repo: &db::Repository) -> String {
-    match repo::list_branches(&owner._id, &repo._id).await {
+pub async fn default_ref(_db: &Database, owner: &db::Owner, repo: &db::Repository) -> String {
+    match repo::list_branches(owner.id(), &repo._id).await {
         Ok(list) => {
             if let Some(h) = list.iter().find(|b| b.is_head) {
                 h.name.clone()
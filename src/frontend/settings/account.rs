@@ -0,0 +1,288 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+use actix_web::http::header::LOCATION;
+use maud::html;
+
+use crate::api::service;
+use crate::auth::ClientInfo;
+use crate::auth_backend::AuthBackend;
+use crate::db::{Database, Token, User};
+use crate::frontend::components;
+use crate::frontend::repo::utils;
+use crate::frontend::settings::{settings_nav, Section};
+use crate::frontend::url_encode;
+use crate::mail::Mailer;
+use crate::models::{ChangeEmailRequest, ChangePasswordRequest, ChangeUsernameRequest, IdentityInfo};
+use crate::oidc::Oidc;
+
+#[derive(serde::Deserialize)]
+pub struct MessageQuery {
+    error: Option<String>,
+    success: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UsernameForm {
+    pub username: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailForm {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct LinkForm {
+    pub provider: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UnlinkForm {
+    pub id: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasswordForm {
+    pub current_password: String,
+    pub new_password: String,
+    pub confirm_password: String,
+}
+
+/// The login session behind the request's cookie.
+async fn current_session(db: &Database, req: &HttpRequest) -> Option<Token> {
+    let token = crate::frontend::token_from_req(req)?;
+    service::session_info(db, token).await.ok()
+}
+
+fn login_redirect() -> HttpResponse {
+    HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()
+}
+
+fn back(key: &str, msg: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((LOCATION, format!("/settings/account?{}={}", key, url_encode(msg))))
+        .finish()
+}
+
+fn error_text(msg: &str) -> &str {
+    match msg {
+        "wrong password" => "Wrong password",
+        "too many failed attempts" => "Too many failed attempts, try again later",
+        "invalid password" => "Password must be between 8 and 128 characters",
+        "invalid email" => "Invalid email address",
+        "invalid name" => "Invalid username",
+        "already exists" => "Already taken",
+        "email already verified" => "Your email address is already verified",
+        "identity not found" => "Linked account not found",
+        "forbidden" => "Directory accounts cannot be unlinked",
+        _ => "Something went wrong",
+    }
+}
+
+fn render(user: &User, identities: &[IdentityInfo], oidc: &Oidc, query: &MessageQuery) -> HttpResponse {
+    let content = html! {
+        div class="container settings-layout" {
+            (settings_nav(Section::Account))
+            div class="settings-content" {
+                h2 { "Account" }
+                @if let Some(err) = &query.error {
+                    (components::alert(components::AlertKind::Error, err))
+                }
+                @if let Some(msg) = &query.success {
+                    (components::alert(components::AlertKind::Success, msg))
+                }
+
+                form class="settings-form" method="post" action="/settings/account/username" {
+                    h3 { "Username" }
+                    div class="form-row" {
+                        input type="text" name="username" value=(user.username) required {}
+                        button type="submit" class="action-btn" { "Rename" }
+                    }
+                    p class="help" { "Links to your old profile and repositories, including clone URLs, redirect to the new name until someone else takes it." }
+                }
+
+                form class="settings-form" method="post" action="/settings/account/email" {
+                    h3 { "Email" }
+                    @if !user.email_verified {
+                        p class="help" { span class="badge" { "Unverified" } " Check your inbox for the verification link." }
+                    }
+                    label for="email" { "Email address" }
+                    input type="email" id="email" name="email" value=(user.email) required {}
+                    label for="email-password" { "Current password" }
+                    input type="password" id="email-password" name="password" required {}
+                    button type="submit" class="action-btn" { "Change email" }
+                }
+
+                @if !user.email_verified {
+                    form class="settings-form" method="post" action="/settings/account/verify-email" {
+                        button type="submit" class="action-btn" { "Resend verification email" }
+                    }
+                }
+
+                form class="settings-form" method="post" action="/settings/account/password" {
+                    h3 { "Password" }
+                    label for="current-password" { "Current password" }
+                    input type="password" id="current-password" name="current_password" autocomplete="current-password" required {}
+                    label for="new-password" { "New password" }
+                    input type="password" id="new-password" name="new_password" autocomplete="new-password" minlength="8" maxlength="128" required {}
+                    label for="confirm-password" { "Confirm new password" }
+                    input type="password" id="confirm-password" name="confirm_password" autocomplete="new-password" required {}
+                    p class="help" { "Changing your password signs you out of all other sessions and revokes your personal access tokens." }
+                    button type="submit" class="action-btn" { "Change password" }
+                }
+
+                @if !oidc.providers().is_empty() {
+                    div class="settings-form" {
+                        h3 { "Linked accounts" }
+                        p class="help" { "Sign in with these accounts instead of your password." }
+                        @if !identities.is_empty() {
+                            ul class="settings-list" {
+                                @for i in identities {
+                                    li {
+                                        span class="grow" {
+                                            strong { (i.provider_name) }
+                                            @if let Some(addr) = &i.email {
+                                                " " span class="muted" { (addr) }
+                                            }
+                                        }
+                                        @if i.provider != crate::auth_backend::LDAP_PROVIDER {
+                                            form method="post" action="/settings/account/identities/unlink" {
+                                                input type="hidden" name="id" value=(i.id) {}
+                                                button type="submit" class="branch-delete-btn" { "Unlink" }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        @for p in oidc.providers() {
+                            @if !identities.iter().any(|i| i.provider == p.id) {
+                                form method="post" action="/settings/account/identities/link" {
+                                    input type="hidden" name="provider" value=(p.id) {}
+                                    button type="submit" class="action-btn" { "Link " (p.name) }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+    let page = utils::page_shell("Account settings", content, Some(user.display_name.as_str()));
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string())
+}
+
+#[get("/settings/account")]
+pub async fn get(db: web::Data<Database>, oidc: web::Data<Oidc>, req: HttpRequest, query: web::Query<MessageQuery>) -> Result<HttpResponse> {
+    let user = match utils::requester_id(&db, &req).await {
+        Some(uid) => db.find_user_by_id(&uid).await.map_err(actix_web::error::ErrorInternalServerError)?,
+        None => None,
+    };
+    let user = match user {
+        Some(u) => u,
+        None => return Ok(login_redirect()),
+    };
+    let identities = service::identities_list(&db, &oidc, user._id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(render(&user, &identities, &oidc, &query))
+}
+
+#[post("/settings/account/username")]
+pub async fn username(db: web::Data<Database>, req: HttpRequest, form: web::Form<UsernameForm>) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(login_redirect()),
+    };
+    let payload = ChangeUsernameRequest { username: form.username.clone() };
+    match service::account_change_username(&db, requester, payload).await {
+        Ok(()) => Ok(back("success", "Username changed")),
+        Err(msg) => Ok(back("error", error_text(&msg))),
+    }
+}
+
+#[post("/settings/account/email")]
+pub async fn email(
+    db: web::Data<Database>,
+    backend: web::Data<dyn AuthBackend>,
+    mailer: web::Data<dyn Mailer>,
+    req: HttpRequest,
+    form: web::Form<EmailForm>,
+) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(login_redirect()),
+    };
+    let payload = ChangeEmailRequest { password: form.password.clone(), email: form.email.clone() };
+    let client = ClientInfo::from_request(&req);
+    match service::account_change_email(&db, backend.get_ref(), mailer.get_ref(), requester, payload, client.ip.as_deref()).await {
+        Ok(()) => Ok(back("success", "Email changed, check your inbox to verify the new address")),
+        Err(msg) => Ok(back("error", error_text(&msg))),
+    }
+}
+
+#[post("/settings/account/password")]
+pub async fn password(
+    db: web::Data<Database>,
+    backend: web::Data<dyn AuthBackend>,
+    req: HttpRequest,
+    form: web::Form<PasswordForm>,
+) -> Result<HttpResponse> {
+    let current = match current_session(&db, &req).await {
+        Some(t) => t,
+        None => return Ok(login_redirect()),
+    };
+    if form.new_password != form.confirm_password {
+        return Ok(back("error", "Passwords do not match"));
+    }
+    let payload = ChangePasswordRequest {
+        current_password: form.current_password.clone(),
+        new_password: form.new_password.clone(),
+    };
+    let client = ClientInfo::from_request(&req);
+    match service::account_change_password(&db, backend.get_ref(), current.user, Some(&current._id), payload, client.ip.as_deref()).await {
+        Ok(()) => Ok(back("success", "Password changed, other sessions were signed out and access tokens revoked")),
+        Err(msg) => Ok(back("error", error_text(&msg))),
+    }
+}
+
+#[post("/settings/account/verify-email")]
+pub async fn resend_verification(db: web::Data<Database>, mailer: web::Data<dyn Mailer>, req: HttpRequest) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(login_redirect()),
+    };
+    match service::account_resend_verification(&db, mailer.get_ref(), requester).await {
+        Ok(()) => Ok(back("success", "Verification email sent")),
+        Err(msg) => Ok(back("error", error_text(&msg))),
+    }
+}
+
+/// Sends the user to the provider; the callback links the identity to this account.
+/// A POST, so another site cannot start linking its own identity to the signed-in account.
+#[post("/settings/account/identities/link")]
+pub async fn link_identity(db: web::Data<Database>, oidc: web::Data<Oidc>, req: HttpRequest, form: web::Form<LinkForm>) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(login_redirect()),
+    };
+    match service::auth_begin_sso(&db, &oidc, &form.provider, Some(requester)).await {
+        Ok((url, state)) => Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, url))
+            .cookie(crate::frontend::auth::sso_state_cookie(state))
+            .finish()),
+        Err(e) => Ok(back("error", &e.to_string())),
+    }
+}
+
+#[post("/settings/account/identities/unlink")]
+pub async fn unlink_identity(db: web::Data<Database>, req: HttpRequest, form: web::Form<UnlinkForm>) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(login_redirect()),
+    };
+    match service::identity_unlink(&db, requester, &form.id).await {
+        Ok(()) => Ok(back("success", "Account unlinked")),
+        Err(msg) => Ok(back("error", error_text(&msg))),
+    }
+}
@@ -0,0 +1,186 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+use actix_web::http::header::LOCATION;
+use maud::{html, Markup};
+
+use crate::api::service;
+use crate::db::Database;
+use crate::frontend::components;
+use crate::frontend::repo::utils;
+use crate::frontend::settings::{settings_nav, Section};
+use crate::frontend::url_encode;
+use crate::models::{CreateInviteRequest, CreatedInviteResponse, InviteInfo};
+
+#[derive(serde::Deserialize)]
+pub struct MessageQuery {
+    error: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewInviteForm {
+    pub note: String,
+    pub max_uses: u32,
+    /// Days until expiry, or "never"
+    pub expiry: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RevokeForm {
+    pub id: String,
+}
+
+fn format_date(dt: &bson::DateTime) -> String {
+    utils::format_time(dt.timestamp_millis() / 1000).chars().take(10).collect()
+}
+
+fn back_with_error(msg: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((LOCATION, format!("/settings/invites?error={}", url_encode(msg))))
+        .finish()
+}
+
+fn to_login() -> HttpResponse {
+    HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()
+}
+
+fn render(
+    invites: Option<&[InviteInfo]>,
+    created: Option<&CreatedInviteResponse>,
+    error: Option<&str>,
+    user_display: Option<&str>,
+) -> HttpResponse {
+    let content = html! {
+        div class="container settings-layout" {
+            (settings_nav(Section::Invites))
+            div class="settings-content" {
+                h2 { "Invites" }
+                @if let Some(err) = error {
+                    (components::alert(components::AlertKind::Error, err))
+                }
+                @match invites {
+                    None => {
+                        (components::alert(components::AlertKind::Info, "Only administrators can invite new members."))
+                    }
+                    Some(invites) => {
+                        p class="muted" { "Invite codes let people create an account while registration is invite-only." }
+                        @if let Some(c) = created {
+                            (components::alert(components::AlertKind::Success, "Invite created. Copy it now, it will not be shown again."))
+                            p { code class="token-value" { (c.url) } }
+                        }
+                        @if invites.is_empty() {
+                            p class="muted" { "No active invites." }
+                        } @else {
+                            ul class="settings-list" {
+                                @for i in invites {
+                                    li {
+                                        span class="grow" {
+                                            strong { (i.note.as_deref().unwrap_or("Invite")) }
+                                            " "
+                                            span class="badge" { (i.uses) " / " (i.max_uses) " used" }
+                                            br;
+                                            span class="muted" {
+                                                "Created " (format_date(&i.created_at))
+                                                @match &i.expires_at {
+                                                    Some(exp) => { " · expires " (format_date(exp)) }
+                                                    None => { " · never expires" }
+                                                }
+                                            }
+                                        }
+                                        form method="post" action="/settings/invites/revoke" {
+                                            input type="hidden" name="id" value=(i.id) {}
+                                            button type="submit" class="branch-delete-btn" { "Revoke" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        (new_invite_form())
+                    }
+                }
+            }
+        }
+    };
+    let page = utils::page_shell("Invites", content, user_display);
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string())
+}
+
+fn new_invite_form() -> Markup {
+    html! {
+        form class="settings-form" method="post" action="/settings/invites" {
+            h3 { "Create invite" }
+            label for="invite-note" { "Note" }
+            input type="text" id="invite-note" name="note" placeholder="For Alex" {}
+            label for="invite-uses" { "Number of accounts" }
+            input type="number" id="invite-uses" name="max_uses" value="1" min="1" required {}
+            label for="invite-expiry" { "Expiration" }
+            select id="invite-expiry" name="expiry" {
+                option value="1" { "1 day" }
+                option value="7" selected { "7 days" }
+                option value="30" { "30 days" }
+                option value="never" { "No expiration" }
+            }
+            button type="submit" class="action-btn" { "Create invite" }
+        }
+    }
+}
+
+/// The requester's invites, or `None` when they may not invite.
+async fn load(db: &Database, requester: bson::oid::ObjectId) -> Result<Option<Vec<InviteInfo>>> {
+    match service::invites_list(db, requester).await {
+        Ok(list) => Ok(Some(list)),
+        Err(msg) if msg == "forbidden" => Ok(None),
+        Err(msg) => Err(actix_web::error::ErrorInternalServerError(msg)),
+    }
+}
+
+#[get("/settings/invites")]
+pub async fn get(db: web::Data<Database>, req: HttpRequest, query: web::Query<MessageQuery>) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(to_login()),
+    };
+    let user_display = utils::token_display(&db, &req).await;
+    let invites = load(&db, requester).await?;
+    Ok(render(invites.as_deref(), None, query.error.as_deref(), user_display.as_deref()))
+}
+
+#[post("/settings/invites")]
+pub async fn create(db: web::Data<Database>, req: HttpRequest, form: web::Form<NewInviteForm>) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(to_login()),
+    };
+    let expires_in_days = match form.expiry.as_str() {
+        "never" => None,
+        days => match days.parse::<u32>() {
+            Ok(d) => Some(d),
+            Err(_) => return Ok(back_with_error("Invalid expiration")),
+        },
+    };
+
+    let payload = CreateInviteRequest {
+        note: Some(form.note.clone()),
+        max_uses: Some(form.max_uses),
+        expires_in_days,
+    };
+    let created = match service::invite_create(&db, requester, payload).await {
+        Ok(c) => c,
+        Err(msg) => return Ok(back_with_error(&msg)),
+    };
+
+    // the code is only ever shown in this response
+    let user_display = utils::token_display(&db, &req).await;
+    let invites = load(&db, requester).await?;
+    Ok(render(invites.as_deref(), Some(&created), None, user_display.as_deref()))
+}
+
+#[post("/settings/invites/revoke")]
+pub async fn revoke(db: web::Data<Database>, req: HttpRequest, form: web::Form<RevokeForm>) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(to_login()),
+    };
+    match service::invite_revoke(&db, requester, &form.id).await {
+        Ok(()) => Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/settings/invites")).finish()),
+        Err(msg) => Ok(back_with_error(&msg)),
+    }
+}
@@ -0,0 +1,144 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+use actix_web::http::header::LOCATION;
+use maud::html;
+
+use crate::api::service;
+use crate::db::Database;
+use crate::frontend::components;
+use crate::frontend::repo::utils;
+use crate::frontend::settings::{settings_nav, Section};
+use crate::frontend::url_encode;
+use crate::models::{AddSshKeyRequest, SshKeyInfo};
+
+#[derive(serde::Deserialize)]
+pub struct MessageQuery {
+    error: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewKeyForm {
+    pub name: String,
+    pub key: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteForm {
+    pub id: String,
+}
+
+fn format_date(dt: &bson::DateTime) -> String {
+    utils::format_time(dt.timestamp_millis() / 1000).chars().take(10).collect()
+}
+
+fn login_redirect() -> HttpResponse {
+    HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()
+}
+
+fn back_with_error(msg: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((LOCATION, format!("/settings/keys?error={}", url_encode(msg))))
+        .finish()
+}
+
+fn error_text(msg: &str) -> &str {
+    match msg {
+        "invalid key" => "That is not an OpenSSH public key",
+        "already exists" => "This key is already in use",
+        "ssh key not found" => "SSH key not found",
+        _ => "Something went wrong",
+    }
+}
+
+fn render(keys: &[SshKeyInfo], error: Option<&str>, user_display: Option<&str>) -> HttpResponse {
+    let content = html! {
+        div class="container settings-layout" {
+            (settings_nav(Section::SshKeys))
+            div class="settings-content" {
+                h2 { "SSH keys" }
+                @if crate::ssh::enabled() {
+                    p class="muted" { "Keys let you clone and push over SSH without a password." }
+                } @else {
+                    (components::alert(components::AlertKind::Warning, "Git over SSH is not enabled on this server."))
+                }
+                @if let Some(err) = error {
+                    (components::alert(components::AlertKind::Error, err))
+                }
+                @if keys.is_empty() {
+                    p class="muted" { "No SSH keys yet." }
+                } @else {
+                    ul class="settings-list" {
+                        @for k in keys {
+                            li {
+                                span class="grow" {
+                                    strong { (k.name) }
+                                    br;
+                                    code { (k.fingerprint) }
+                                    br;
+                                    span class="muted" {
+                                        "Added " (format_date(&k.created_at))
+                                        @match &k.last_used_at {
+                                            Some(used) => { " · last used " (format_date(used)) }
+                                            None => { " · never used" }
+                                        }
+                                    }
+                                }
+                                form method="post" action="/settings/keys/delete" {
+                                    input type="hidden" name="id" value=(k.id) {}
+                                    button type="submit" class="branch-delete-btn" { "Delete" }
+                                }
+                            }
+                        }
+                    }
+                }
+                form class="settings-form" method="post" action="/settings/keys" {
+                    h3 { "Add SSH key" }
+                    label for="key-name" { "Title" }
+                    input type="text" id="key-name" name="name" placeholder="Work laptop" {}
+                    label for="key-value" { "Key" }
+                    textarea id="key-value" name="key" rows="5" placeholder="Begins with 'ssh-ed25519', 'ecdsa-sha2-nistp256' or 'ssh-rsa'" required {}
+                    button type="submit" class="action-btn" { "Add key" }
+                }
+            }
+        }
+    };
+    let page = utils::page_shell("SSH keys", content, user_display);
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string())
+}
+
+#[get("/settings/keys")]
+pub async fn get(db: web::Data<Database>, req: HttpRequest, query: web::Query<MessageQuery>) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(login_redirect()),
+    };
+    let user_display = utils::token_display(&db, &req).await;
+    let keys = service::ssh_keys_list(&db, requester)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(render(&keys, query.error.as_deref(), user_display.as_deref()))
+}
+
+#[post("/settings/keys")]
+pub async fn add(db: web::Data<Database>, req: HttpRequest, form: web::Form<NewKeyForm>) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(login_redirect()),
+    };
+    let payload = AddSshKeyRequest { name: form.name.clone(), key: form.key.clone() };
+    match service::ssh_key_add(&db, requester, payload).await {
+        Ok(_) => Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/settings/keys")).finish()),
+        Err(msg) => Ok(back_with_error(error_text(&msg))),
+    }
+}
+
+#[post("/settings/keys/delete")]
+pub async fn delete(db: web::Data<Database>, req: HttpRequest, form: web::Form<DeleteForm>) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(login_redirect()),
+    };
+    match service::ssh_key_delete(&db, requester, &form.id).await {
+        Ok(()) => Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/settings/keys")).finish()),
+        Err(msg) => Ok(back_with_error(error_text(&msg))),
+    }
+}
@@ -0,0 +1,33 @@
+pub mod account;
+pub mod invites;
+pub mod keys;
+pub mod security;
+pub mod sessions;
+pub mod tokens;
+
+use maud::{html, Markup};
+
+#[derive(PartialEq, Eq)]
+pub enum Section {
+    Account,
+    Security,
+    Tokens,
+    SshKeys,
+    Sessions,
+    Invites,
+}
+
+pub fn settings_nav(active: Section) -> Markup {
+    html! {
+        nav class="settings-nav" {
+            a.active[active == Section::Account] href="/settings/account" { "Account" }
+            a.active[active == Section::Security] href="/settings/security" { "Two-factor authentication" }
+            a.active[active == Section::Tokens] href="/settings/tokens" { "Access tokens" }
+            a.active[active == Section::SshKeys] href="/settings/keys" { "SSH keys" }
+            a.active[active == Section::Sessions] href="/settings/sessions" { "Sessions" }
+            @if active == Section::Invites || crate::auth::registration_mode() == crate::auth::RegistrationMode::Invite {
+                a.active[active == Section::Invites] href="/settings/invites" { "Invites" }
+            }
+        }
+    }
+}
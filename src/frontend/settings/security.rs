@@ -0,0 +1,162 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+use actix_web::http::header::LOCATION;
+use maud::{html, Markup, PreEscaped};
+
+use crate::api::service;
+use crate::auth::ClientInfo;
+use crate::auth_backend::AuthBackend;
+use crate::db::{Database, User};
+use crate::errors::AuthError;
+use crate::frontend::components;
+use crate::frontend::repo::utils;
+use crate::frontend::settings::{settings_nav, Section};
+use crate::frontend::url_encode;
+use crate::totp;
+
+#[derive(serde::Deserialize)]
+pub struct MessageQuery {
+    error: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct CodeForm {
+    pub code: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasswordForm {
+    pub password: String,
+}
+
+async fn current_user(db: &Database, req: &HttpRequest) -> Option<User> {
+    let uid = utils::requester_id(db, req).await?;
+    db.find_user_by_id(&uid).await.ok().flatten()
+}
+
+fn login_redirect() -> HttpResponse {
+    HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()
+}
+
+fn back_with_error(msg: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((LOCATION, format!("/settings/security?error={}", url_encode(msg))))
+        .finish()
+}
+
+fn render(user: &User, body: Markup, error: Option<&str>) -> HttpResponse {
+    let content = html! {
+        div class="container settings-layout" {
+            (settings_nav(Section::Security))
+            div class="settings-content" {
+                h2 { "Two-factor authentication" }
+                @if let Some(err) = error {
+                    (components::alert(components::AlertKind::Error, err))
+                }
+                (body)
+            }
+        }
+    };
+    let page = utils::page_shell("Security settings", content, Some(user.display_name.as_str()));
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string())
+}
+
+#[get("/settings/security")]
+pub async fn get(db: web::Data<Database>, req: HttpRequest, query: web::Query<MessageQuery>) -> Result<HttpResponse> {
+    let user = match current_user(&db, &req).await {
+        Some(u) => u,
+        None => return Ok(login_redirect()),
+    };
+
+    let body = match (&user.totp_secret, user.totp_enabled) {
+        (Some(_), true) => html! {
+            p { "Two-factor authentication is " strong { "enabled" } "." }
+            p class="muted" { (user.recovery_codes.len()) " unused recovery codes remaining." }
+            p class="muted" { "Git over HTTPS no longer accepts your password; use a " a href="/settings/tokens" { "personal access token" } " instead." }
+            form class="settings-form danger-zone" method="post" action="/settings/security/2fa/disable" {
+                h3 { "Disable two-factor authentication" }
+                input type="password" name="password" placeholder="Current password" required {}
+                button type="submit" class="branch-delete-btn" { "Disable" }
+            }
+        },
+        (Some(secret), false) => {
+            let uri = totp::otpauth_uri("GitLit", &user.username, secret);
+            html! {
+                p { "Scan this QR code with your authenticator app, then enter the 6-digit code it shows." }
+                @if let Some(svg) = totp::qr_svg(&uri) {
+                    div class="totp-qr" { (PreEscaped(svg)) }
+                }
+                p class="help" { "Can't scan it? Enter this key manually: " code { (secret) } }
+                form class="settings-form" method="post" action="/settings/security/2fa/enable" {
+                    div class="form-row" {
+                        input type="text" name="code" inputmode="numeric" autocomplete="one-time-code" placeholder="123456" required {}
+                        button type="submit" class="action-btn" { "Verify and enable" }
+                    }
+                }
+            }
+        }
+        (None, _) => html! {
+            p class="muted" { "Protect your account with a code from an authenticator app in addition to your password." }
+            form class="settings-form" method="post" action="/settings/security/2fa/setup" {
+                button type="submit" class="action-btn" { "Set up authenticator app" }
+            }
+        },
+    };
+
+    Ok(render(&user, body, query.error.as_deref()))
+}
+
+#[post("/settings/security/2fa/setup")]
+pub async fn setup(db: web::Data<Database>, req: HttpRequest) -> Result<HttpResponse> {
+    let user = match current_user(&db, &req).await {
+        Some(u) => u,
+        None => return Ok(login_redirect()),
+    };
+    match service::totp_setup(&db, user._id).await {
+        Ok(_) => Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/settings/security")).finish()),
+        Err(e) => Ok(back_with_error(&e.to_string())),
+    }
+}
+
+#[post("/settings/security/2fa/enable")]
+pub async fn enable(db: web::Data<Database>, req: HttpRequest, form: web::Form<CodeForm>) -> Result<HttpResponse> {
+    let user = match current_user(&db, &req).await {
+        Some(u) => u,
+        None => return Ok(login_redirect()),
+    };
+    let codes = match service::totp_enable(&db, user._id, &form.code).await {
+        Ok(resp) => resp.recovery_codes,
+        Err(_) => return Ok(back_with_error("Invalid code, please try again")),
+    };
+
+    // recovery codes are only ever shown here, so render instead of redirecting
+    let body = html! {
+        (components::alert(components::AlertKind::Success, "Two-factor authentication is now enabled."))
+        p { "Save these recovery codes somewhere safe. Each one can be used once if you lose access to your authenticator app." }
+        ul class="settings-list recovery-codes" {
+            @for c in &codes {
+                li { code { (c) } }
+            }
+        }
+        a href="/settings/security" class="action-btn" { "Done" }
+    };
+    Ok(render(&user, body, None))
+}
+
+#[post("/settings/security/2fa/disable")]
+pub async fn disable(
+    db: web::Data<Database>,
+    backend: web::Data<dyn AuthBackend>,
+    req: HttpRequest,
+    form: web::Form<PasswordForm>,
+) -> Result<HttpResponse> {
+    let user = match current_user(&db, &req).await {
+        Some(u) => u,
+        None => return Ok(login_redirect()),
+    };
+    let client = ClientInfo::from_request(&req);
+    match service::totp_disable(&db, backend.get_ref(), user._id, &form.password, client.ip.as_deref()).await {
+        Ok(()) => Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/settings/security")).finish()),
+        Err(e @ AuthError::TooManyAttempts(_)) => Ok(back_with_error(&e.to_string())),
+        Err(_) => Ok(back_with_error("Wrong password")),
+    }
+}
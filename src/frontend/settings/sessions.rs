@@ -0,0 +1,134 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+use actix_web::http::header::LOCATION;
+use maud::html;
+
+use crate::api::service;
+use crate::db::{Database, Token, TokenKind};
+use crate::frontend::components;
+use crate::frontend::repo::utils;
+use crate::frontend::settings::{settings_nav, Section};
+use crate::frontend::url_encode;
+use crate::models::SessionInfo;
+
+#[derive(serde::Deserialize)]
+pub struct MessageQuery {
+    error: Option<String>,
+    revoked: Option<u64>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RevokeForm {
+    pub id: String,
+}
+
+fn format_date(dt: &bson::DateTime) -> String {
+    utils::format_time(dt.timestamp_millis() / 1000)
+}
+
+fn back_with_error(msg: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((LOCATION, format!("/settings/sessions?error={}", url_encode(msg))))
+        .finish()
+}
+
+fn to_login() -> HttpResponse {
+    HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()
+}
+
+/// The login session behind the request's cookie.
+async fn current_session(db: &Database, req: &HttpRequest) -> Option<Token> {
+    let token = crate::frontend::token_from_req(req)?;
+    service::session_info(db, token).await.ok()
+}
+
+fn render(sessions: &[SessionInfo], query: &MessageQuery, user_display: Option<&str>) -> HttpResponse {
+    let content = html! {
+        div class="container settings-layout" {
+            (settings_nav(Section::Sessions))
+            div class="settings-content" {
+                h2 { "Sessions" }
+                p class="muted" { "Browsers and tokens currently signed in to your account. Revoke any you do not recognize." }
+                @if let Some(err) = &query.error {
+                    (components::alert(components::AlertKind::Error, err))
+                }
+                @if let Some(n) = query.revoked {
+                    (components::alert(components::AlertKind::Success, &format!("Signed out of {} other session(s).", n)))
+                }
+                ul class="settings-list" {
+                    @for s in sessions {
+                        li {
+                            span class="grow" {
+                                @match s.kind {
+                                    TokenKind::Session => { strong { (s.user_agent.as_deref().unwrap_or("Unknown browser")) } }
+                                    TokenKind::Personal => { strong { "Token: " (s.name.as_deref().unwrap_or("")) } }
+                                }
+                                @if s.current {
+                                    " " span class="badge" { "This session" }
+                                }
+                                br;
+                                span class="muted" {
+                                    (s.ip.as_deref().unwrap_or("unknown address"))
+                                    " · signed in " (format_date(&s.created_at))
+                                    @if let Some(used) = &s.last_used_at {
+                                        " · last active " (format_date(used))
+                                    }
+                                }
+                            }
+                            @if !s.current {
+                                form method="post" action="/settings/sessions/revoke" {
+                                    input type="hidden" name="id" value=(s.id) {}
+                                    button type="submit" class="branch-delete-btn" { "Revoke" }
+                                }
+                            }
+                        }
+                    }
+                }
+                form class="settings-form" method="post" action="/settings/sessions/revoke-all" {
+                    p class="help" { "Signs out every other browser. Personal access tokens stay valid." }
+                    button type="submit" class="action-btn" { "Sign out everywhere else" }
+                }
+            }
+        }
+    };
+    let page = utils::page_shell("Sessions", content, user_display);
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string())
+}
+
+#[get("/settings/sessions")]
+pub async fn get(db: web::Data<Database>, req: HttpRequest, query: web::Query<MessageQuery>) -> Result<HttpResponse> {
+    let current = match current_session(&db, &req).await {
+        Some(t) => t,
+        None => return Ok(to_login()),
+    };
+    let user_display = utils::token_display(&db, &req).await;
+    let sessions = service::sessions_list(&db, current.user, &current._id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(render(&sessions, &query, user_display.as_deref()))
+}
+
+#[post("/settings/sessions/revoke")]
+pub async fn revoke(db: web::Data<Database>, req: HttpRequest, form: web::Form<RevokeForm>) -> Result<HttpResponse> {
+    let current = match current_session(&db, &req).await {
+        Some(t) => t,
+        None => return Ok(to_login()),
+    };
+    match service::session_revoke(&db, current.user, &form.id).await {
+        Ok(()) => Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/settings/sessions")).finish()),
+        Err(msg) => Ok(back_with_error(&msg)),
+    }
+}
+
+#[post("/settings/sessions/revoke-all")]
+pub async fn revoke_all(db: web::Data<Database>, req: HttpRequest) -> Result<HttpResponse> {
+    let current = match current_session(&db, &req).await {
+        Some(t) => t,
+        None => return Ok(to_login()),
+    };
+    match service::sessions_revoke_others(&db, current.user, &current._id).await {
+        Ok(n) => Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, format!("/settings/sessions?revoked={}", n)))
+            .finish()),
+        Err(msg) => Ok(back_with_error(&msg)),
+    }
+}
@@ -0,0 +1,175 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+use actix_web::http::header::LOCATION;
+use maud::{html, Markup};
+
+use crate::api::service;
+use crate::auth::ClientInfo;
+use crate::db::{Database, Scope};
+use crate::frontend::components;
+use crate::frontend::repo::utils;
+use crate::frontend::settings::{settings_nav, Section};
+use crate::frontend::url_encode;
+use crate::models::{CreateTokenRequest, TokenInfo};
+
+#[derive(serde::Deserialize)]
+pub struct MessageQuery {
+    error: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewTokenForm {
+    pub name: String,
+    pub scope: String,
+    /// Days until expiry, or "never"
+    pub expiry: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RevokeForm {
+    pub id: String,
+}
+
+fn format_date(dt: &bson::DateTime) -> String {
+    utils::format_time(dt.timestamp_millis() / 1000).chars().take(10).collect()
+}
+
+fn back_with_error(msg: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((LOCATION, format!("/settings/tokens?error={}", url_encode(msg))))
+        .finish()
+}
+
+fn render(tokens: &[TokenInfo], created: Option<&str>, error: Option<&str>, user_display: Option<&str>) -> HttpResponse {
+    let content = html! {
+        div class="container settings-layout" {
+            (settings_nav(Section::Tokens))
+            div class="settings-content" {
+                h2 { "Personal access tokens" }
+                p class="muted" { "Tokens can be used instead of a password for git over HTTPS and as Bearer tokens for the API." }
+                @if let Some(err) = error {
+                    (components::alert(components::AlertKind::Error, err))
+                }
+                @if let Some(value) = created {
+                    (components::alert(components::AlertKind::Success, "Token created. Copy it now, it will not be shown again."))
+                    p { code class="token-value" { (value) } }
+                }
+                @if tokens.is_empty() {
+                    p class="muted" { "No tokens yet." }
+                } @else {
+                    ul class="settings-list" {
+                        @for t in tokens {
+                            li {
+                                span class="grow" {
+                                    strong { (t.name) }
+                                    " "
+                                    @for s in &t.scopes { span class="badge" { (s.as_str()) } " " }
+                                    br;
+                                    span class="muted" {
+                                        "Created " (format_date(&t.created_at))
+                                        @match &t.expires_at {
+                                            Some(exp) => { " · expires " (format_date(exp)) }
+                                            None => { " · never expires" }
+                                        }
+                                        @match &t.last_used_at {
+                                            Some(used) => { " · last used " (format_date(used)) }
+                                            None => { " · never used" }
+                                        }
+                                    }
+                                }
+                                form method="post" action="/settings/tokens/revoke" {
+                                    input type="hidden" name="id" value=(t.id) {}
+                                    button type="submit" class="branch-delete-btn" { "Revoke" }
+                                }
+                            }
+                        }
+                    }
+                }
+                (new_token_form())
+            }
+        }
+    };
+    let page = utils::page_shell("Access tokens", content, user_display);
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(page.into_string())
+}
+
+fn new_token_form() -> Markup {
+    html! {
+        form class="settings-form" method="post" action="/settings/tokens" {
+            h3 { "Generate new token" }
+            label for="token-name" { "Name" }
+            input type="text" id="token-name" name="name" placeholder="CI deploy" required {}
+            label for="token-scope" { "Scope" }
+            select id="token-scope" name="scope" {
+                option value="repo:read" { "repo:read - clone and browse repositories" }
+                option value="repo:write" selected { "repo:write - also push and manage branches" }
+                option value="admin" { "admin - also manage repositories, collaborators and organizations" }
+            }
+            label for="token-expiry" { "Expiration" }
+            select id="token-expiry" name="expiry" {
+                option value="7" { "7 days" }
+                option value="30" selected { "30 days" }
+                option value="90" { "90 days" }
+                option value="365" { "1 year" }
+                option value="never" { "No expiration" }
+            }
+            button type="submit" class="action-btn" { "Generate token" }
+        }
+    }
+}
+
+#[get("/settings/tokens")]
+pub async fn get(db: web::Data<Database>, req: HttpRequest, query: web::Query<MessageQuery>) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+    let user_display = utils::token_display(&db, &req).await;
+    let tokens = service::tokens_list(&db, requester)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(render(&tokens, None, query.error.as_deref(), user_display.as_deref()))
+}
+
+#[post("/settings/tokens")]
+pub async fn create(db: web::Data<Database>, req: HttpRequest, form: web::Form<NewTokenForm>) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+    let scope = match Scope::parse(&form.scope) {
+        Some(s) => s,
+        None => return Ok(back_with_error("Invalid scope")),
+    };
+    let expires_in_days = match form.expiry.as_str() {
+        "never" => None,
+        days => match days.parse::<u32>() {
+            Ok(d) => Some(d),
+            Err(_) => return Ok(back_with_error("Invalid expiration")),
+        },
+    };
+
+    let payload = CreateTokenRequest { name: form.name.clone(), scopes: vec![scope], expires_in_days };
+    let created = match service::token_create(&db, requester, payload, ClientInfo::from_request(&req)).await {
+        Ok(c) => c,
+        Err(msg) => return Ok(back_with_error(&msg)),
+    };
+
+    // the secret is only ever shown in this response
+    let user_display = utils::token_display(&db, &req).await;
+    let tokens = service::tokens_list(&db, requester)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(render(&tokens, Some(&created.token), None, user_display.as_deref()))
+}
+
+#[post("/settings/tokens/revoke")]
+pub async fn revoke(db: web::Data<Database>, req: HttpRequest, form: web::Form<RevokeForm>) -> Result<HttpResponse> {
+    let requester = match utils::requester_id(&db, &req).await {
+        Some(uid) => uid,
+        None => return Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/login")).finish()),
+    };
+    match service::token_revoke(&db, requester, &form.id).await {
+        Ok(()) => Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/settings/tokens")).finish()),
+        Err(msg) => Ok(back_with_error(&msg)),
+    }
+}
@@ -1,4 +1,3 @@
-use clap::Parser;
 use git_http_backend::config::{DefaultGitHttpConfig};
 use git_http_backend::{GitConfig, GitOperation};
 use std::path::{Path, PathBuf};
@@ -7,24 +6,286 @@ use std::fs;
 use http_auth_basic::Credentials;
 use crate::db::Database;
 use crate::repo::repo_path;
+use crate::db::{self, Scope};
+use crate::policy::{self, Action, Actor};
+use crate::errors::AuthError;
 use bson::oid::ObjectId;
-use bcrypt::verify;
-
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-pub struct ActixServerArgs {
-    #[arg(short, long, default_value = "e:")]
-    pub root: String,
-    #[arg(short, long, default_value = "80")]
-    pub port: u16,
-    #[arg(short, long, default_value = "0.0.0.0")]
-    pub addr: String,
-}
+use std::sync::Arc;
+use crate::auth_backend::AuthBackend;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpMessage, HttpResponse};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+
+/// `Authorization` value that `authorize` hands to the git backend in place of
+/// credentials it has already verified; random per process so clients cannot send it.
+static AUTHORIZED_MARKER: Lazy<String> = Lazy::new(|| {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("GitLit-Authorized {}", data_encoding::HEXLOWER.encode(&bytes))
+});
 
 #[derive(Clone, Debug)]
 pub struct MyGitHttpConfig {
     pub inner: DefaultGitHttpConfig,
     pub db: Database,
+    pub backend: Arc<dyn AuthBackend>,
+}
+
+/// Kind of access a git request needs on the repository it targets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GitAccess {
+    Read,
+    Write,
+}
+
+/// Splits a smart-HTTP git request path into `(owner, repo, access)`.
+/// Returns `None` for anything that is not a git transport request.
+pub fn classify_request(path: &str, query: &str) -> Option<(String, String, GitAccess)> {
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    if segments.len() < 3 || segments[0].is_empty() || segments[1].is_empty() {
+        return None;
+    }
+
+    let access = match &segments[2..] {
+        ["info", "refs"] => {
+            if query.split('&').any(|p| p == "service=git-receive-pack") {
+                GitAccess::Write
+            } else {
+                GitAccess::Read
+            }
+        }
+        ["git-receive-pack"] => GitAccess::Write,
+        ["git-upload-pack"] => GitAccess::Read,
+        ["HEAD"] => GitAccess::Read,
+        ["objects", ..] => GitAccess::Read,
+        _ => return None,
+    };
+
+    let reponame = segments[1].strip_suffix(".git").unwrap_or(segments[1]);
+    Some((segments[0].to_string(), reponame.to_string(), access))
+}
+
+/// A git client that passed authentication.
+pub enum GitUser {
+    /// An account; `token` is set when it used an access token.
+    Account { user: db::User, token: Option<db::Token> },
+    /// A deploy key or deploy token, which only reaches the repository it belongs to.
+    Deploy { repo: ObjectId, name: String, write: bool },
+}
+
+impl GitUser {
+    pub fn account(&self) -> Option<&db::User> {
+        match self {
+            GitUser::Account { user, .. } => Some(user),
+            GitUser::Deploy { .. } => None,
+        }
+    }
+
+    /// Name to greet the client with and to log.
+    pub fn name(&self) -> &str {
+        match self {
+            GitUser::Account { user, .. } => &user.username,
+            GitUser::Deploy { name, .. } => name,
+        }
+    }
+}
+
+impl MyGitHttpConfig {
+    /// Resolves `owner/repo` where the owner is a user or an organization.
+    /// Clone URLs under a former owner or repository name keep working; on disk
+    /// repositories are keyed by id, so the renamed or moved repository is served directly.
+    pub async fn find_repo(&self, owner: &str, reponame: &str) -> Option<db::Repository> {
+        let owner = match self.db.find_owner_by_name(owner).await.ok().flatten() {
+            Some(o) => o,
+            None => self.db.find_renamed_owner(owner).await.ok().flatten()?,
+        };
+        match self.db.find_repo_by_user_and_name(owner.id(), reponame).await.ok().flatten() {
+            Some(repo) => Some(repo),
+            None => self.db.find_renamed_repo(owner.id(), reponame).await.ok().flatten(),
+        }
+    }
+
+    /// Verifies a `Basic` Authorization header and returns the matching user.
+    /// The password field may hold a personal access token; accounts with 2FA only accept tokens.
+    /// A deploy token is accepted with any user name.
+    /// Password attempts count towards the login throttle for the account and `ip`;
+    /// a successful one resets the account's failure count.
+    pub async fn user_from_header(&self, header: &str, ip: Option<&str>) -> Result<GitUser, AuthError> {
+        let credentials = match Credentials::from_header(header.to_string()) {
+            Ok(c) => c,
+            Err(_) => {
+                tracing::warn!("Unsupported Authorization header format");
+                return Err(AuthError::InvalidAuthHeader);
+            }
+        };
+        let login = credentials.user_id;
+        let password = credentials.password;
+        tracing::info!("Authenticating with Basic credentials (username/password), login={}", login);
+
+        if crate::auth::is_deploy_token(&password) {
+            let token = crate::auth::authenticate_deploy_token(&self.db, &password).await.map_err(|e| {
+                tracing::warn!("Authentication failed for deploy token: {}", e);
+                e
+            })?;
+            tracing::debug!("Authentication successful for deploy token '{}'", token.name);
+            return Ok(GitUser::Deploy { repo: token.repo, name: token.name, write: token.write });
+        }
+
+        let user = match self.db.find_user_by_login(&login).await {
+            Ok(u) => u,
+            Err(e) => {
+                tracing::warn!("Authentication error looking up user '{}': {}", login, e);
+                return Err(AuthError::Internal(e.to_string()));
+            }
+        };
+
+        // a personal access token may always stand in for the password, even while the
+        // account is throttled; browser session tokens are not accepted
+        if let Some(user) = &user {
+            if let Ok(token) = crate::auth::authenticate_personal_token(&self.db, &password).await {
+                if token.user == user._id && !user.suspended {
+                    tracing::debug!("Authentication successful for user '{}' via token", login);
+                    return Ok(GitUser::Account { user: user.clone(), token: Some(token) });
+                }
+            }
+        }
+
+        let user = match crate::auth::verify_password(&self.db, self.backend.as_ref(), &login, &password, ip).await {
+            Ok(u) => u,
+            Err(e) => {
+                tracing::warn!("Authentication failed for '{}': {}", login, e);
+                return Err(e);
+            }
+        };
+        if user.totp_enabled {
+            tracing::warn!("Authentication failed: user '{}' has 2FA enabled and must use a token", login);
+            return Err(AuthError::InvalidCredentials);
+        }
+        crate::throttle::record_success(&self.db, &user._id).await;
+        tracing::debug!("Authentication successful for user '{}'", login);
+        Ok(GitUser::Account { user, token: None })
+    }
+
+    /// Whether `user` may perform `access` on `repo`, honoring collaborator roles,
+    /// the scopes of the token they authenticated with and the reach of deploy credentials.
+    pub async fn permits(&self, user: Option<&GitUser>, repo: &db::Repository, access: GitAccess) -> bool {
+        let (action, scope) = match access {
+            GitAccess::Read => (Action::Read, Scope::RepoRead),
+            GitAccess::Write => (Action::Write, Scope::RepoWrite),
+        };
+        let user_id = match user {
+            Some(GitUser::Deploy { repo: deploy_repo, write, .. }) => {
+                return *deploy_repo == repo._id && (access == GitAccess::Read || *write);
+            }
+            Some(GitUser::Account { user, token }) => {
+                if token.as_ref().is_some_and(|t| !t.allows(scope)) {
+                    return false;
+                }
+                Some(user._id)
+            }
+            None => None,
+        };
+
+        let actor = match Actor::resolve(&self.db, user_id, repo).await {
+            Ok(actor) => actor,
+            Err(_) => return false,
+        };
+        policy::can(&actor, action, repo)
+    }
+}
+
+fn unauthorized() -> actix_web::Error {
+    let resp = HttpResponse::Unauthorized()
+        .insert_header(("WWW-Authenticate", "Basic realm=\"GitLit\""))
+        .body("unauthorized");
+    actix_web::error::InternalError::from_response("unauthorized", resp).into()
+}
+
+fn too_many_attempts(secs: i64) -> actix_web::Error {
+    let resp = HttpResponse::TooManyRequests()
+        .insert_header((actix_web::http::header::RETRY_AFTER, secs.to_string()))
+        .body("too many failed login attempts");
+    actix_web::error::InternalError::from_response("too many attempts", resp).into()
+}
+
+/// Stand-in for repositories that could not be resolved; never exists on disk,
+/// so the backend cannot fall through to a raw `repos/<owner id>/<repo id>` path.
+fn unresolved_path() -> PathBuf {
+    PathBuf::from("./repos/.unresolved")
+}
+
+/// Middleware that authorizes git transport requests against the repository
+/// they target before they reach the git backend. Unknown repositories are
+/// answered with 404; an authorized client is stored in the request extensions
+/// and its credentials are replaced by `AUTHORIZED_MARKER`.
+pub async fn authorize(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let (username, reponame, access) = match classify_request(req.path(), req.query_string()) {
+        Some(v) => v,
+        None => return next.call(req).await,
+    };
+    let config = match req.app_data::<web::Data<MyGitHttpConfig>>() {
+        Some(c) => c.clone(),
+        None => return next.call(req).await,
+    };
+
+    let repo = match config.find_repo(&username, &reponame).await {
+        Some(r) => r,
+        None => return Err(actix_web::error::ErrorNotFound("repository not found")),
+    };
+
+    let header = req
+        .headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s.to_string());
+
+    let ip = req.connection_info().realip_remote_addr().map(|s| s.to_string());
+    let user = match header {
+        Some(h) => match config.user_from_header(&h, ip.as_deref()).await {
+            Ok(u) => Some(u),
+            Err(AuthError::TooManyAttempts(secs)) => return Err(too_many_attempts(secs)),
+            Err(_) => return Err(unauthorized()),
+        },
+        None => None,
+    };
+
+    if access == GitAccess::Write {
+        if let Some(u) = user.as_ref().and_then(GitUser::account) {
+            if crate::auth::email_verification_pending(u) {
+                return Err(actix_web::error::ErrorForbidden("verify your email address before pushing"));
+            }
+        }
+    }
+
+    if config.permits(user.as_ref(), &repo, access).await {
+        let headers = req.headers_mut();
+        headers.remove(actix_web::http::header::AUTHORIZATION);
+        if let Some(u) = user {
+            headers.insert(
+                actix_web::http::header::AUTHORIZATION,
+                actix_web::http::header::HeaderValue::from_str(&AUTHORIZED_MARKER).expect("marker is ASCII"),
+            );
+            req.extensions_mut().insert(u);
+        }
+        return next.call(req).await;
+    }
+
+    match user {
+        None => Err(unauthorized()),
+        Some(u) => {
+            tracing::warn!(
+                "authorize: user '{}' denied {:?} access to {}/{}",
+                u.name(), access, username, reponame
+            );
+            Err(actix_web::error::ErrorForbidden("forbidden"))
+        }
+    }
 }
 
 
@@ -37,8 +298,7 @@ impl GitConfig for MyGitHttpConfig {
 
         if segments.len() < 2 {
             tracing::warn!("rewrite: unexpected path '{}'", original_path);
-            let fallback = PathBuf::from("./repos".to_string() + &original_path);
-            return fs::canonicalize(&fallback).unwrap_or(fallback);
+            return unresolved_path();
         }
 
         let username = segments[0];
@@ -52,20 +312,19 @@ impl GitConfig for MyGitHttpConfig {
             None
         };
 
-        let resolved_path = if let Ok(Some(user)) = self.db.find_user_by_login(username).await {
-            match self.db.find_repo_by_user_and_name(&user._id, reponame).await {
-                Ok(Some(repo)) => {
-                    let base = repo_path(&user._id, &repo._id);
-                    if let Some(r) = rest.as_deref() {
-                        base.join(r)
-                    } else {
-                        base
-                    }
+        let resolved_path = match self.find_repo(username, reponame).await {
+            Some(repo) => {
+                let base = repo_path(&repo.user, &repo._id);
+                if let Some(r) = rest.as_deref() {
+                    base.join(r)
+                } else {
+                    base
                 }
-                _ => PathBuf::from("./repos".to_string() + &original_path),
             }
-        } else {
-            PathBuf::from("./repos".to_string() + &original_path)
+            None => {
+                tracing::warn!("rewrite: no repository for '{}'", original_path);
+                return unresolved_path();
+            }
         };
 
         tracing::info!("rewrite: resolved path: {:?}", resolved_path);
@@ -73,46 +332,18 @@ impl GitConfig for MyGitHttpConfig {
         fs::canonicalize(&resolved_path).unwrap_or(resolved_path)
     }
 
+    /// Credentials were already verified by `authorize`, which leaves its marker behind.
     async fn authenticate(&self, auth: Option<String>) -> Result<(), ()> {
-        if let Some(h) = auth {
-            if let Ok(credentials) = Credentials::from_header(h.clone()) {
-                let login = credentials.user_id;
-                let password = credentials.password;
-                tracing::info!("Authenticating with Basic credentials (username/password), login={}", login);
-
-                match self.db.find_user_by_login(&login).await {
-                    Ok(Some(user)) => {
-                        match verify(password, &user.password) {
-                            Ok(true) => {
-                                tracing::debug!("Authentication successful for user '{}'", login);
-                                Ok(())
-                            }
-                            Ok(false) => {
-                                tracing::warn!("Authentication failed: invalid password for user '{}'", login);
-                                Err(())
-                            }
-                            Err(e) => {
-                                tracing::warn!("Authentication error during password verify for '{}': {}", login, e);
-                                Err(())
-                            }
-                        }
-                    }
-                    Ok(None) => {
-                        tracing::warn!("Authentication failed: user '{}' not found", login);
-                        Err(())
-                    }
-                    Err(e) => {
-                        tracing::warn!("Authentication error looking up user '{}': {}", login, e);
-                        Err(())
-                    }
-                }
-            } else {
-                tracing::warn!("Unsupported Authorization header format");
+        match auth {
+            Some(h) if h == *AUTHORIZED_MARKER => Ok(()),
+            Some(_) => {
+                tracing::warn!("authenticate: request was not authorized by the middleware");
+                Err(())
+            }
+            None => {
+                tracing::warn!("Missing Authorization header");
                 Err(())
             }
-        } else {
-            tracing::warn!("Missing Authorization header");
-            Err(())
         }
     }
 
@@ -135,7 +366,7 @@ impl GitConfig for MyGitHttpConfig {
 
         match self.db.find_repo_by_hex(&repo_hex).await {
             Ok(Some(repo)) => {
-                let public = ObjectId::parse_str(&user_hex).ok().map(|oid| oid == repo.user).unwrap_or(false) && !repo.is_private;
+                let public = ObjectId::parse_str(&user_hex).ok().map(|oid| oid == repo.user).unwrap_or(false) && policy::can(&Actor::anonymous(), Action::Read, &repo);
                 tracing::debug!(
                     "is_public_repo: repo {} (user {}) is {}",
                     repo_hex,
@@ -0,0 +1,121 @@
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use tracing::info;
+
+/// A plain-text message to a single recipient.
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers account emails (password resets, verification links).
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), String>;
+}
+
+/// Base URL used in links sent by email, from `PUBLIC_URL`.
+pub fn public_url() -> String {
+    match env::var("PUBLIC_URL") {
+        Ok(url) if !url.is_empty() => url.trim_end_matches('/').to_string(),
+        _ => format!("http://localhost:{}", env::var("PORT").unwrap_or_else(|_| "8080".to_string())),
+    }
+}
+
+fn from_address() -> String {
+    env::var("MAIL_FROM").unwrap_or_else(|_| "GitLit <noreply@localhost>".to_string())
+}
+
+/// Picks the transport from `MAIL_TRANSPORT`: `smtp`, `file` or `log` (the default).
+pub fn from_env() -> Arc<dyn Mailer> {
+    match env::var("MAIL_TRANSPORT").unwrap_or_default().as_str() {
+        "smtp" => Arc::new(SmtpMailer::from_env().expect("invalid SMTP configuration")),
+        "file" => Arc::new(FileMailer {
+            dir: PathBuf::from(env::var("MAIL_DIR").unwrap_or_else(|_| "mail".to_string())),
+        }),
+        _ => Arc::new(LogMailer),
+    }
+}
+
+/// Sends through an SMTP relay (`SMTP_HOST`, `SMTP_PORT`, `SMTP_USER`, `SMTP_PASSWORD`).
+/// `SMTP_TLS` is `starttls` (default), `tls` or `none`.
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn from_env() -> Result<Self, String> {
+        let host = env::var("SMTP_HOST").map_err(|_| "SMTP_HOST is not set".to_string())?;
+        let mut builder = match env::var("SMTP_TLS").unwrap_or_default().as_str() {
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host).map_err(|e| e.to_string())?,
+            _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host).map_err(|e| e.to_string())?,
+        };
+        if let Some(port) = env::var("SMTP_PORT").ok().and_then(|p| p.parse::<u16>().ok()) {
+            builder = builder.port(port);
+        }
+        if let Ok(user) = env::var("SMTP_USER") {
+            let password = env::var("SMTP_PASSWORD").unwrap_or_default();
+            builder = builder.credentials(Credentials::new(user, password));
+        }
+        let from = from_address().parse::<Mailbox>().map_err(|e| e.to_string())?;
+        Ok(SmtpMailer { transport: builder.build(), from })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(email.to.parse::<Mailbox>().map_err(|e| e.to_string())?)
+            .subject(email.subject.clone())
+            .body(email.body.clone())
+            .map_err(|e| e.to_string())?;
+        self.transport.send(message).await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+/// Writes every message to a file in `MAIL_DIR`; for development and tests.
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        tokio::fs::create_dir_all(&self.dir).await.map_err(|e| e.to_string())?;
+        let path = self.dir.join(format!("{}.eml", uuid::Uuid::new_v4().simple()));
+        let content = format!(
+            "From: {}\nTo: {}\nSubject: {}\n\n{}\n",
+            from_address(),
+            email.to,
+            email.subject,
+            email.body
+        );
+        tokio::fs::write(&path, content).await.map_err(|e| e.to_string())?;
+        info!("Wrote email for {} to {:?}", email.to, path);
+        Ok(())
+    }
+}
+
+/// Prints every message to the log instead of sending it.
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        info!("Email to {}: {}\n{}", email.to, email.subject, email.body);
+        Ok(())
+    }
+}
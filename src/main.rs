@@ -1,18 +1,29 @@
 mod api;
 mod auth;
+mod auth_backend;
+mod captcha;
+mod cli;
 mod db;
 mod errors;
 mod frontend;
 mod git;
+mod mail;
 mod models;
+mod oidc;
+mod policy;
 mod repo;
+mod ssh;
+mod throttle;
+mod totp;
 
+use crate::cli::{Cli, Command, ServeArgs};
 use crate::git::*;
 use db::Database;
 use dotenvy;
 use std::env;
 
 use actix_web::{App, HttpServer, web};
+use clap::Parser;
 use git_http_backend::config::{DefaultGitHttpConfig};
 use git_http_backend::handlers::configure_routes;
 use git_http_backend::{GitConfig, GitHttpConfig, GitOperation};
@@ -23,18 +34,41 @@ pub async fn main() -> io::Result<()> {
     tracing_subscriber::fmt().init();
     let _ = dotenvy::dotenv();
 
+    let args = match Cli::parse().command {
+        None => ServeArgs::default(),
+        Some(Command::Serve(args)) => args,
+        Some(command) => {
+            let db = Database::init().await;
+            if let Err(e) = cli::run(&db, command).await {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+    };
+    serve(args).await
+}
+
+async fn serve(args: ServeArgs) -> io::Result<()> {
     let p = Path::new("repos");
     fs::create_dir_all(p)?;
     let root = fs::canonicalize(p)?;
 
     let db = Database::init().await;
     let db_data = web::Data::new(db);
+    let backends = auth_backend::from_env();
+    let backend: web::Data<dyn auth_backend::AuthBackend> = web::Data::from(backends.clone());
+    let mailer: web::Data<dyn mail::Mailer> = web::Data::from(mail::from_env());
+    let captcha: web::Data<dyn captcha::Captcha> = web::Data::from(captcha::from_env());
+    let oidc = web::Data::new(oidc::Oidc::from_env());
 
-    let addr = String::from("localhost");
-    let port: u16 = env::var("PORT")
-        .unwrap_or("8080".to_string())
-        .parse()
-        .unwrap_or(8080);
+    let addr = args.addr.unwrap_or_else(|| String::from("localhost"));
+    let port: u16 = args.port.unwrap_or_else(|| {
+        env::var("PORT")
+            .unwrap_or("8080".to_string())
+            .parse()
+            .unwrap_or(8080)
+    });
 
     let api_config = env::var("API").unwrap_or("/api".to_string());
     let (_api_port, _api_prefix): (Option<u16>, String) =
@@ -56,14 +90,31 @@ pub async fn main() -> io::Result<()> {
     let config = MyGitHttpConfig {
         inner: base,
         db: db_data.get_ref().clone(),
+        backend: backends,
     };
+    let git_data = web::Data::new(config.clone());
+
+    let ssh_config = config.clone();
+    let ssh_addr = env::var("SSH_ADDR").unwrap_or_else(|_| "0.0.0.0".to_string());
+    tokio::spawn(async move {
+        if let Err(e) = ssh::serve(ssh_config, ssh_addr).await {
+            tracing::error!("SSH server stopped: {}", e);
+        }
+    });
     let config_service: Arc<dyn GitConfig> = Arc::new(config);
 
     let bind_addr = format!("{}:{}", addr.clone(), port);
     HttpServer::new(move || {
         App::new()
             .app_data(db_data.clone())
+            .app_data(backend.clone())
+            .app_data(mailer.clone())
+            .app_data(captcha.clone())
+            .app_data(oidc.clone())
             .app_data(web::Data::from(config_service.clone()))
+            .app_data(git_data.clone())
+            .wrap(actix_web::middleware::from_fn(git::authorize))
+            .wrap(actix_web::middleware::from_fn(frontend::renew_session))
             .wrap(actix_web::middleware::Logger::default())
             .configure(api::config)
             .service(utoipa_swagger_ui::SwaggerUi::new("/api/docs/{_:.*}").url(
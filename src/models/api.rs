@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 pub struct LoginRequest {
     pub login: String,
     pub password: String,
+    /// TOTP or recovery code, required when the account has 2FA enabled
+    pub otp: Option<String>,
 }
 
 #[derive(Debug, Serialize, utoipa::ToSchema)]
@@ -15,6 +17,40 @@ pub struct RegisterRequest {
     pub username: String,
     pub email: String,
     pub password: String,
+    /// Invite code, required when registration is invite-only
+    pub invite: Option<String>,
+    #[serde(flatten)]
+    pub captcha: CaptchaResponse,
+}
+
+/// Answer to the configured captcha; each provider reads its own fields.
+#[derive(Debug, Default, Deserialize, utoipa::ToSchema)]
+pub struct CaptchaResponse {
+    #[serde(rename = "g-recaptcha-response")]
+    pub recaptcha: Option<String>,
+    #[serde(rename = "h-captcha-response")]
+    pub hcaptcha: Option<String>,
+    #[serde(rename = "cf-turnstile-response")]
+    pub turnstile: Option<String>,
+    /// Challenge issued by the `pow` and `arithmetic` providers
+    pub captcha_challenge: Option<String>,
+    /// Nonce for `pow`, the sum for `arithmetic`
+    pub captcha_answer: Option<String>,
+}
+
+/// The captcha registration requires.
+#[derive(Debug, Default, Serialize, utoipa::ToSchema)]
+pub struct CaptchaInfo {
+    /// `none`, `recaptcha`, `hcaptcha`, `turnstile`, `pow` or `arithmetic`
+    pub provider: String,
+    /// Widget key of the hosted providers
+    pub site_key: Option<String>,
+    /// Fresh challenge for `pow` and `arithmetic`, valid for 10 minutes
+    pub challenge: Option<String>,
+    /// Question to answer for `arithmetic`
+    pub question: Option<String>,
+    /// Leading zero bits `SHA-256("{challenge}:{nonce}")` needs for `pow`
+    pub difficulty: Option<u32>,
 }
 
 #[derive(Debug, Deserialize, utoipa::ToSchema)]
@@ -22,6 +58,53 @@ pub struct CreateRepoRequest {
     pub name: String,
     pub description: Option<String>,
     pub is_private: Option<bool>,
+    /// Organization to create the repository under; defaults to the requester
+    pub owner: Option<String>,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct ForkRepoRequest {
+    /// Id of the repository to fork
+    pub id: String,
+    /// Organization to create the fork under; defaults to the requester
+    pub owner: Option<String>,
+    /// Defaults to the name of the forked repository
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ForksQuery {
+    pub id: String,
+}
+
+/// Fields left out stay unchanged.
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct UpdateRepoRequest {
+    /// Repository id
+    pub id: String,
+    pub description: Option<String>,
+    pub is_private: Option<bool>,
+    /// `http(s)` URL; empty removes it
+    pub website: Option<String>,
+    pub topics: Option<Vec<String>>,
+    /// Branch that `HEAD` points to
+    pub default_branch: Option<String>,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct RenameRepoRequest {
+    /// Repository id
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct TransferRepoRequest {
+    /// Repository id
+    pub id: String,
+    /// Name of the user or organization that receives the repository
+    pub owner: String,
 }
 
 #[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
@@ -89,4 +172,418 @@ pub struct CommitsQuery {
 #[derive(Debug, serde::Serialize, utoipa::ToSchema)]
 pub struct ErrorResponse {
     pub error: String,
-}
\ No newline at end of file
+}
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CollaboratorsQuery {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct AddCollaboratorRequest {
+    pub id: String,
+    pub username: String,
+    pub role: crate::db::Role,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RemoveCollaboratorQuery {
+    pub id: String,
+    pub username: String,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct CollaboratorInfo {
+    pub username: String,
+    pub display_name: String,
+    pub role: crate::db::Role,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct CreateOrgRequest {
+    pub name: String,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OrgQuery {
+    pub org: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct SetOrgMemberRequest {
+    pub org: String,
+    pub username: String,
+    pub role: crate::db::OrgRole,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RemoveOrgMemberQuery {
+    pub org: String,
+    pub username: String,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct OrgMemberInfo {
+    pub username: String,
+    pub display_name: String,
+    pub role: crate::db::OrgRole,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct CreateTeamRequest {
+    pub org: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub permission: crate::db::Role,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TeamQuery {
+    pub org: String,
+    pub team: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TeamMemberQuery {
+    pub org: String,
+    pub team: String,
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TeamRepoQuery {
+    pub org: String,
+    pub team: String,
+    /// Repository name within the organization
+    pub repo: String,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct TeamInfo {
+    pub name: String,
+    pub description: String,
+    pub permission: crate::db::Role,
+    pub members: Vec<String>,
+    pub repos: Vec<String>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct TotpSetupResponse {
+    /// Base32 secret for manual entry into an authenticator app
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct TotpEnableRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct RecoveryCodesResponse {
+    /// Shown only once; each code can be used a single time instead of a TOTP code
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct TotpDisableRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct CreateTokenRequest {
+    pub name: String,
+    /// Any of `repo:read`, `repo:write`, `admin`; higher scopes include the lower ones
+    pub scopes: Vec<crate::db::Scope>,
+    /// Lifetime in days; omit for a token that never expires
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct TokenInfo {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<crate::db::Scope>,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: bson::DateTime,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub expires_at: Option<bson::DateTime>,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub last_used_at: Option<bson::DateTime>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct CreatedTokenResponse {
+    /// The secret value; it is not retrievable later
+    pub token: String,
+    pub info: TokenInfo,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RevokeTokenQuery {
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct SessionInfo {
+    pub id: String,
+    /// Whether this is the session making the request
+    pub current: bool,
+    pub kind: crate::db::TokenKind,
+    /// Name of a personal access token
+    pub name: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: bson::DateTime,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub expires_at: Option<bson::DateTime>,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub last_used_at: Option<bson::DateTime>,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RevokeSessionQuery {
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct RevokedResponse {
+    pub revoked: u64,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    /// 8 to 128 characters
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct ChangeEmailRequest {
+    /// Current password, required to confirm the change
+    pub password: String,
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct ChangeUsernameRequest {
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct ForgotPasswordRequest {
+    /// Username or email
+    pub login: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct ResetPasswordRequest {
+    /// Token from the emailed reset link
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct VerifyEmailRequest {
+    /// Token from the emailed verification link
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct CreateInviteRequest {
+    /// Reminder of who the invite is for
+    pub note: Option<String>,
+    /// Number of accounts that can register with the code; defaults to 1
+    pub max_uses: Option<u32>,
+    /// Lifetime in days; omit for an invite that never expires
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct InviteInfo {
+    pub id: String,
+    pub note: Option<String>,
+    pub max_uses: i32,
+    pub uses: i32,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: bson::DateTime,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub expires_at: Option<bson::DateTime>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct CreatedInviteResponse {
+    /// The invite code; it is not retrievable later
+    pub code: String,
+    /// Registration page with the code filled in
+    pub url: String,
+    pub info: InviteInfo,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct IdentityInfo {
+    pub id: String,
+    /// Provider id from `OIDC_PROVIDERS`
+    pub provider: String,
+    pub provider_name: String,
+    /// Email address the provider reported at the last sign-in
+    pub email: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: bson::DateTime,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub last_used_at: Option<bson::DateTime>,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct AddSshKeyRequest {
+    pub name: String,
+    /// Public key in OpenSSH format, e.g. the contents of `~/.ssh/id_ed25519.pub`
+    pub key: String,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct SshKeyInfo {
+    pub id: String,
+    pub name: String,
+    /// `SHA256:...`, as printed by `ssh-keygen -l`
+    pub fingerprint: String,
+    pub public_key: String,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: bson::DateTime,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub last_used_at: Option<bson::DateTime>,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeployQuery {
+    /// Repository id
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct AddDeployKeyRequest {
+    /// Repository id
+    pub id: String,
+    pub name: String,
+    /// Public key in OpenSSH format; it must not be in use by an account or another repository
+    pub key: String,
+    /// Allow pushing; deploy keys are read-only by default
+    #[serde(default)]
+    pub write: bool,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct DeployKeyInfo {
+    pub id: String,
+    pub name: String,
+    pub fingerprint: String,
+    pub public_key: String,
+    pub write: bool,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: bson::DateTime,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub last_used_at: Option<bson::DateTime>,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct CreateDeployTokenRequest {
+    /// Repository id
+    pub id: String,
+    pub name: String,
+    /// Allow pushing; deploy tokens are read-only by default
+    #[serde(default)]
+    pub write: bool,
+    /// Lifetime in days; omit for a token that never expires
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct DeployTokenInfo {
+    pub id: String,
+    pub name: String,
+    pub write: bool,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: bson::DateTime,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub expires_at: Option<bson::DateTime>,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub last_used_at: Option<bson::DateTime>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct CreatedDeployTokenResponse {
+    /// The secret value, used as the password of any user name; it is not retrievable later
+    pub token: String,
+    pub info: DeployTokenInfo,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AdminSearchQuery {
+    /// Matches user names, display names and emails, or repository names
+    pub q: Option<String>,
+    /// 1-based page of 50 results
+    pub page: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AdminUserQuery {
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct SuspendUserRequest {
+    pub username: String,
+    /// `false` lifts the suspension
+    pub suspended: bool,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct AdminUserInfo {
+    pub id: String,
+    pub username: String,
+    pub display_name: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub admin: bool,
+    pub suspended: bool,
+    pub totp_enabled: bool,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: bson::DateTime,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct AdminRepoInfo {
+    pub id: String,
+    pub owner: String,
+    pub name: String,
+    pub description: String,
+    pub is_private: bool,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: bson::DateTime,
+    #[schema(value_type = String, format = DateTime)]
+    pub updated_at: bson::DateTime,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct InstanceStats {
+    pub version: String,
+    pub users: u64,
+    pub suspended_users: u64,
+    pub repositories: u64,
+    pub private_repositories: u64,
+    pub organizations: u64,
+}
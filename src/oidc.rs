@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+use data_encoding::BASE64URL_NOPAD;
+use rand::RngCore;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+/// An OpenID Connect identity provider users can sign in with.
+#[derive(Clone, Debug)]
+pub struct Provider {
+    /// Short name used in URLs and stored with linked identities.
+    pub id: String,
+    /// Shown on the "Sign in with ..." button.
+    pub name: String,
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    scopes: String,
+}
+
+/// Endpoints from the provider's `/.well-known/openid-configuration`.
+#[derive(Clone, Debug, Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: Option<String>,
+}
+
+/// What GitLit learns about the user from a completed sign-in.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Claims {
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub preferred_username: Option<String>,
+    pub name: Option<String>,
+}
+
+/// Secrets tying a callback to the request that started the sign-in.
+pub struct Pending {
+    pub state: String,
+    pub nonce: String,
+    pub verifier: String,
+}
+
+/// The configured providers, from `OIDC_PROVIDERS` (comma-separated ids) and for each id
+/// `OIDC_<ID>_ISSUER`, `OIDC_<ID>_CLIENT_ID`, `OIDC_<ID>_CLIENT_SECRET`, `OIDC_<ID>_NAME`
+/// and `OIDC_<ID>_SCOPES`. Discovery documents are fetched on first use.
+pub struct Oidc {
+    providers: Vec<Provider>,
+    client: Client,
+    discovered: Mutex<HashMap<String, Discovery>>,
+}
+
+fn random_string() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    BASE64URL_NOPAD.encode(&bytes)
+}
+
+/// `code_challenge` for a PKCE verifier (S256).
+fn pkce_challenge(verifier: &str) -> String {
+    BASE64URL_NOPAD.encode(&Sha256::digest(verifier.as_bytes()))
+}
+
+impl Oidc {
+    pub fn from_env() -> Self {
+        let providers = env::var("OIDC_PROVIDERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|id| id.trim().to_ascii_lowercase())
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                if !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                    panic!("invalid OIDC provider id '{}'", id);
+                }
+                let key = id.to_ascii_uppercase().replace('-', "_");
+                let var = |name: &str| env::var(format!("OIDC_{}_{}", key, name)).ok().filter(|v| !v.is_empty());
+                Provider {
+                    name: var("NAME").unwrap_or_else(|| id.clone()),
+                    issuer: var("ISSUER")
+                        .unwrap_or_else(|| panic!("OIDC_{}_ISSUER must be set", key))
+                        .trim_end_matches('/')
+                        .to_string(),
+                    client_id: var("CLIENT_ID").unwrap_or_else(|| panic!("OIDC_{}_CLIENT_ID must be set", key)),
+                    client_secret: var("CLIENT_SECRET"),
+                    scopes: var("SCOPES").unwrap_or_else(|| "openid email profile".to_string()),
+                    id,
+                }
+            })
+            .collect();
+        Oidc { providers, client: Client::new(), discovered: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn providers(&self) -> &[Provider] {
+        &self.providers
+    }
+
+    pub fn provider(&self, id: &str) -> Option<&Provider> {
+        self.providers.iter().find(|p| p.id == id)
+    }
+
+    /// Where the provider sends the browser back to.
+    pub fn redirect_uri(provider: &Provider) -> String {
+        format!("{}/login/oidc/{}/callback", crate::mail::public_url(), provider.id)
+    }
+
+    async fn discovery(&self, provider: &Provider) -> Result<Discovery, String> {
+        if let Some(d) = self.discovered.lock().unwrap_or_else(|e| e.into_inner()).get(&provider.id) {
+            return Ok(d.clone());
+        }
+        let url = format!("{}/.well-known/openid-configuration", provider.issuer);
+        let discovery: Discovery = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("discovery failed: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("invalid discovery document: {}", e))?;
+        if discovery.issuer.trim_end_matches('/') != provider.issuer {
+            return Err(format!("discovery document is for issuer {}", discovery.issuer));
+        }
+        self.discovered
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(provider.id.clone(), discovery.clone());
+        Ok(discovery)
+    }
+
+    /// Starts a sign-in: returns the provider's authorization URL and the secrets to keep
+    /// until the callback.
+    pub async fn begin(&self, provider: &Provider) -> Result<(String, Pending), String> {
+        let discovery = self.discovery(provider).await?;
+        let pending = Pending { state: random_string(), nonce: random_string(), verifier: random_string() };
+
+        let mut url = Url::parse(&discovery.authorization_endpoint).map_err(|e| e.to_string())?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &provider.client_id)
+            .append_pair("redirect_uri", &Self::redirect_uri(provider))
+            .append_pair("scope", &provider.scopes)
+            .append_pair("state", &pending.state)
+            .append_pair("nonce", &pending.nonce)
+            .append_pair("code_challenge", &pkce_challenge(&pending.verifier))
+            .append_pair("code_challenge_method", "S256");
+        Ok((url.to_string(), pending))
+    }
+
+    /// Redeems the authorization code and returns who signed in. The ID token comes
+    /// straight from the token endpoint over TLS, so its claims are checked but its
+    /// signature is not (OpenID Connect Core 3.1.3.7).
+    pub async fn finish(&self, provider: &Provider, code: &str, nonce: &str, verifier: &str) -> Result<Claims, String> {
+        let discovery = self.discovery(provider).await?;
+
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            access_token: String,
+            id_token: Option<String>,
+        }
+
+        let redirect_uri = Self::redirect_uri(provider);
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("client_id", provider.client_id.as_str()),
+            ("code_verifier", verifier),
+        ];
+        if let Some(secret) = &provider.client_secret {
+            params.push(("client_secret", secret.as_str()));
+        }
+        let tokens: TokenResponse = self
+            .client
+            .post(&discovery.token_endpoint)
+            .form(&params)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("token request failed: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("invalid token response: {}", e))?;
+
+        let id_token = tokens.id_token.ok_or_else(|| "no id_token in token response".to_string())?;
+        let mut claims = validate_id_token(&id_token, &discovery.issuer, &provider.client_id, nonce, now_secs())?;
+
+        // many providers only put the profile in the userinfo response
+        if claims.email.is_none() {
+            if let Some(endpoint) = &discovery.userinfo_endpoint {
+                let info: UserInfo = self
+                    .client
+                    .get(endpoint)
+                    .bearer_auth(&tokens.access_token)
+                    .send()
+                    .await
+                    .and_then(|r| r.error_for_status())
+                    .map_err(|e| format!("userinfo request failed: {}", e))?
+                    .json()
+                    .await
+                    .map_err(|e| format!("invalid userinfo response: {}", e))?;
+                if info.sub != claims.subject {
+                    return Err("userinfo is for a different subject".into());
+                }
+                claims.email = info.email;
+                claims.email_verified = is_true(&info.email_verified);
+                claims.preferred_username = claims.preferred_username.or(info.preferred_username);
+                claims.name = claims.name.or(info.name);
+            }
+        }
+        Ok(claims)
+    }
+}
+
+fn now_secs() -> i64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs() as i64
+}
+
+#[derive(Deserialize)]
+struct UserInfo {
+    sub: String,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: serde_json::Value,
+    preferred_username: Option<String>,
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IdClaims {
+    iss: String,
+    sub: String,
+    aud: serde_json::Value,
+    exp: i64,
+    nonce: Option<String>,
+    azp: Option<String>,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: serde_json::Value,
+    preferred_username: Option<String>,
+    name: Option<String>,
+}
+
+/// Some providers send `email_verified` as the string `"true"`.
+fn is_true(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Bool(b) => *b,
+        serde_json::Value::String(s) => s == "true",
+        _ => false,
+    }
+}
+
+/// Checks issuer, audience, expiry and nonce of an ID token and returns its claims.
+fn validate_id_token(token: &str, issuer: &str, client_id: &str, nonce: &str, now: i64) -> Result<Claims, String> {
+    let payload = token.split('.').nth(1).ok_or_else(|| "malformed id_token".to_string())?;
+    let payload = BASE64URL_NOPAD
+        .decode(payload.trim_end_matches('=').as_bytes())
+        .map_err(|_| "malformed id_token".to_string())?;
+    let claims: IdClaims = serde_json::from_slice(&payload).map_err(|e| format!("invalid id_token claims: {}", e))?;
+
+    if claims.iss.trim_end_matches('/') != issuer.trim_end_matches('/') {
+        return Err(format!("id_token issued by {}", claims.iss));
+    }
+    let audiences: Vec<&str> = match &claims.aud {
+        serde_json::Value::String(s) => vec![s.as_str()],
+        serde_json::Value::Array(a) => a.iter().filter_map(|v| v.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    if !audiences.contains(&client_id) {
+        return Err("id_token is for another client".into());
+    }
+    if audiences.len() > 1 && claims.azp.as_deref() != Some(client_id) {
+        return Err("id_token is for another client".into());
+    }
+    if claims.exp <= now {
+        return Err("id_token has expired".into());
+    }
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err("id_token nonce mismatch".into());
+    }
+
+    Ok(Claims {
+        subject: claims.sub,
+        email_verified: is_true(&claims.email_verified),
+        email: claims.email,
+        preferred_username: claims.preferred_username,
+        name: claims.name,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(claims: serde_json::Value) -> String {
+        format!(
+            "{}.{}.sig",
+            BASE64URL_NOPAD.encode(br#"{"alg":"RS256"}"#),
+            BASE64URL_NOPAD.encode(claims.to_string().as_bytes())
+        )
+    }
+
+    fn claims() -> serde_json::Value {
+        serde_json::json!({
+            "iss": "http://localhost:8081/default",
+            "sub": "alice",
+            "aud": "gitlit",
+            "exp": 2000,
+            "nonce": "n",
+            "email": "alice@example.com",
+            "email_verified": true,
+        })
+    }
+
+    #[test]
+    fn accepts_a_valid_id_token() {
+        let c = validate_id_token(&token(claims()), "http://localhost:8081/default/", "gitlit", "n", 1000).unwrap();
+        assert_eq!(c.subject, "alice");
+        assert_eq!(c.email.as_deref(), Some("alice@example.com"));
+        assert!(c.email_verified);
+    }
+
+    #[test]
+    fn rejects_wrong_audience_nonce_issuer_or_expiry() {
+        let t = token(claims());
+        assert!(validate_id_token(&t, "http://localhost:8081/default", "other", "n", 1000).is_err());
+        assert!(validate_id_token(&t, "http://localhost:8081/default", "gitlit", "m", 1000).is_err());
+        assert!(validate_id_token(&t, "http://evil.example", "gitlit", "n", 1000).is_err());
+        assert!(validate_id_token(&t, "http://localhost:8081/default", "gitlit", "n", 2000).is_err());
+    }
+
+    #[test]
+    fn pkce_challenge_matches_rfc_7636_example() {
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use bson::oid::ObjectId;
+
+use crate::db::{Database, Repository, Role};
+
+/// Something a caller may try to do with a repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Browse files, commits and branches, clone and fetch.
+    Read,
+    /// Push commits.
+    Write,
+    /// Remove a branch.
+    DeleteBranch,
+    /// Manage collaborators, settings and deletion.
+    Admin,
+}
+
+/// The caller as seen by the policy: who they are and their effective role on one repository.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Actor {
+    pub user: Option<ObjectId>,
+    pub role: Option<Role>,
+}
+
+impl Actor {
+    pub fn anonymous() -> Self {
+        Actor::default()
+    }
+
+    /// Looks up the effective role of `user` on `repo` (ownership, org, collaborator, teams).
+    pub async fn resolve(db: &Database, user: Option<ObjectId>, repo: &Repository) -> mongodb::error::Result<Self> {
+        let role = match user {
+            Some(uid) => db.find_repo_role(&uid, repo).await?,
+            None => None,
+        };
+        Ok(Actor { user, role })
+    }
+}
+
+/// Single source of truth for repository permissions.
+pub fn can(actor: &Actor, action: Action, repo: &Repository) -> bool {
+    match action {
+        Action::Read => !repo.is_private || actor.role.is_some(),
+        Action::Write | Action::DeleteBranch => actor.role >= Some(Role::Write),
+        Action::Admin => actor.role == Some(Role::Admin),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::DateTime;
+
+    fn repo(is_private: bool) -> Repository {
+        Repository {
+            _id: ObjectId::new(),
+            user: ObjectId::new(),
+            name: "repo".into(),
+            description: String::new(),
+            is_private,
+            website: None,
+            topics: Vec::new(),
+            forked_from: None,
+            created_at: DateTime::now(),
+            updated_at: DateTime::now(),
+        }
+    }
+
+    fn actor(role: Option<Role>) -> Actor {
+        Actor { user: role.map(|_| ObjectId::new()), role }
+    }
+
+    const ACTIONS: [Action; 4] = [Action::Read, Action::Write, Action::DeleteBranch, Action::Admin];
+
+    fn allowed(role: Option<Role>, is_private: bool) -> Vec<Action> {
+        let repo = repo(is_private);
+        ACTIONS.into_iter().filter(|a| can(&actor(role), *a, &repo)).collect()
+    }
+
+    #[test]
+    fn public_repository_matrix() {
+        assert_eq!(allowed(None, false), vec![Action::Read]);
+        assert_eq!(allowed(Some(Role::Read), false), vec![Action::Read]);
+        assert_eq!(allowed(Some(Role::Write), false), vec![Action::Read, Action::Write, Action::DeleteBranch]);
+        assert_eq!(allowed(Some(Role::Admin), false), ACTIONS.to_vec());
+    }
+
+    #[test]
+    fn private_repository_matrix() {
+        assert_eq!(allowed(None, true), Vec::<Action>::new());
+        assert_eq!(allowed(Some(Role::Read), true), vec![Action::Read]);
+        assert_eq!(allowed(Some(Role::Write), true), vec![Action::Read, Action::Write, Action::DeleteBranch]);
+        assert_eq!(allowed(Some(Role::Admin), true), ACTIONS.to_vec());
+    }
+
+    #[test]
+    fn signed_in_user_without_role_is_treated_as_anonymous() {
+        let stranger = Actor { user: Some(ObjectId::new()), role: None };
+        assert!(can(&stranger, Action::Read, &repo(false)));
+        assert!(!can(&stranger, Action::Read, &repo(true)));
+        assert!(!can(&stranger, Action::DeleteBranch, &repo(false)));
+        assert!(!can(&Actor::anonymous(), Action::Write, &repo(false)));
+    }
+}
@@ -4,8 +4,15 @@ use crate::models::*;
 use git2::{BranchType, ObjectType, Repository, Sort};
 use bson::oid::ObjectId;
 
+/// On-disk location of a bare repository, keyed by the owning user's or
+/// organization's id so that renames never move data.
 pub fn repo_path(user_id: &ObjectId, repo_id: &ObjectId) -> PathBuf {
-    PathBuf::from("./repos").join(user_id.to_string()).join(repo_id.to_string())
+    owner_path(user_id).join(repo_id.to_string())
+}
+
+/// Directory holding all repositories of a user or organization.
+pub fn owner_path(owner_id: &ObjectId) -> PathBuf {
+    PathBuf::from("./repos").join(owner_id.to_string())
 }
 
 pub async fn init(user_id: ObjectId, repo_id: ObjectId) -> Result<PathBuf, GitError> {
@@ -18,6 +25,36 @@ pub async fn init(user_id: ObjectId, repo_id: ObjectId) -> Result<PathBuf, GitEr
     Ok(path)
 }
 
+/// Creates `repo_id` as a copy of the repository `parent_id`. Objects are hardlinked where
+/// the filesystem allows, so a fork is cheap yet survives its parent being moved or deleted.
+pub async fn fork(
+    parent_owner: &ObjectId,
+    parent_id: &ObjectId,
+    user_id: &ObjectId,
+    repo_id: &ObjectId,
+) -> Result<PathBuf, GitError> {
+    let from = repo_path(parent_owner, parent_id);
+    let path = repo_path(user_id, repo_id);
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let out = tokio::process::Command::new("git")
+        .args(["clone", "--bare", "--local", "--quiet"])
+        .arg(&from)
+        .arg(&path)
+        .output()
+        .await?;
+    if !out.status.success() {
+        return Err(GitError::Git(String::from_utf8_lossy(&out.stderr).trim().to_string()));
+    }
+
+    // the clone remembers its source as `origin`, which a fork does not need
+    let repo = Repository::open_bare(&path).map_err(|e| GitError::Git(e.to_string()))?;
+    repo.remote_delete("origin").map_err(|e| GitError::Git(e.to_string()))?;
+    Ok(path)
+}
+
 pub async fn list_branches(
     user_id: &ObjectId,
     repo_id: &ObjectId,
@@ -67,6 +104,17 @@ pub async fn list_branches(
     Ok(out)
 }
 
+/// Points the repository's `HEAD`, and with it the default branch, at an existing branch.
+pub async fn set_default_branch(user_id: &ObjectId, repo_id: &ObjectId, branch: &str) -> Result<(), GitError> {
+    let repo = Repository::open_bare(repo_path(user_id, repo_id)).map_err(|e| GitError::Git(e.to_string()))?;
+    let reference = repo
+        .find_branch(branch, BranchType::Local)
+        .map_err(|e| GitError::Git(e.to_string()))?
+        .into_reference();
+    let name = reference.name().ok_or_else(|| GitError::Parse("branch name is not UTF-8".into()))?;
+    repo.set_head(name).map_err(|e| GitError::Git(e.to_string()))
+}
+
 pub async fn delete_branch(
     user_id: &ObjectId,
     repo_id: &ObjectId,
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::env;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+
+use russh::keys::ssh_key::private::Ed25519Keypair;
+use russh::keys::ssh_key::LineEnding;
+use russh::keys::{HashAlg, PrivateKey, PublicKey};
+use russh::server::{Auth, ChannelOpenHandle, Config, Handler, Msg, Server, Session};
+use russh::{Channel, ChannelId, MethodKind, MethodSet};
+use tokio::process::Command;
+
+use crate::git::{GitAccess, GitUser, MyGitHttpConfig};
+use crate::repo::repo_path;
+
+const DEFAULT_HOST_KEY: &str = "ssh_host_ed25519_key";
+
+/// Git over SSH is served when `SSH_PORT` is set.
+pub fn enabled() -> bool {
+    ssh_port().is_some()
+}
+
+fn ssh_port() -> Option<u16> {
+    env::var("SSH_PORT").ok().and_then(|p| p.parse().ok())
+}
+
+/// SSH clone URL for a repository, or `None` while SSH is disabled. `SSH_HOST` overrides
+/// the host name of the web request, e.g. when SSH is reachable under another name.
+pub fn clone_url(web_host: &str, owner: &str, repo: &str) -> Option<String> {
+    let port = ssh_port()?;
+    let host = env::var("SSH_HOST").unwrap_or_else(|_| web_host.split(':').next().unwrap_or(web_host).to_string());
+    Some(match port {
+        22 => format!("git@{}:{}/{}.git", host, owner, repo),
+        p => format!("ssh://git@{}:{}/{}/{}.git", host, p, owner, repo),
+    })
+}
+
+/// Parses an OpenSSH public key line into its comment-less encoding and SHA-256 fingerprint.
+pub fn parse_public_key(line: &str) -> Option<(String, String)> {
+    let mut key = PublicKey::from_openssh(line.trim()).ok()?;
+    key.set_comment("");
+    let encoded = key.to_openssh().ok()?;
+    Some((encoded.trim().to_string(), key.fingerprint(HashAlg::Sha256).to_string()))
+}
+
+/// Splits an exec request such as `git-upload-pack '/owner/repo.git'` into the git
+/// service to run and `(owner, repo, access)`.
+fn parse_command(command: &str) -> Option<(&'static str, String, String, GitAccess)> {
+    let (program, arg) = command.trim().split_once(' ')?;
+    let (service, access) = match program {
+        "git-upload-pack" => ("upload-pack", GitAccess::Read),
+        "git-receive-pack" => ("receive-pack", GitAccess::Write),
+        _ => return None,
+    };
+    let path = arg.trim().trim_matches(|c| c == '\'' || c == '"').trim_start_matches('/');
+    let (owner, repo) = path.split_once('/')?;
+    let repo = repo.strip_suffix(".git").unwrap_or(repo);
+    if owner.is_empty() || repo.is_empty() || repo.contains('/') {
+        return None;
+    }
+    Some((service, owner.to_string(), repo.to_string(), access))
+}
+
+/// Loads the host key from `SSH_HOST_KEY`, generating an Ed25519 key there on first start.
+fn host_key() -> std::io::Result<PrivateKey> {
+    let path = PathBuf::from(env::var("SSH_HOST_KEY").unwrap_or_else(|_| DEFAULT_HOST_KEY.to_string()));
+    if path.exists() {
+        let pem = std::fs::read_to_string(&path)?;
+        return PrivateKey::from_openssh(pem).map_err(|e| std::io::Error::other(e.to_string()));
+    }
+
+    let key = PrivateKey::from(Ed25519Keypair::from_seed(&rand::random::<[u8; 32]>()));
+    let pem = key.to_openssh(LineEnding::LF).map_err(|e| std::io::Error::other(e.to_string()))?;
+    std::fs::write(&path, pem.as_bytes())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    tracing::info!("Generated SSH host key {:?}", path);
+    Ok(key)
+}
+
+/// Serves git over SSH on `SSH_PORT` until the process exits. Does nothing while SSH is disabled.
+pub async fn serve(config: MyGitHttpConfig, addr: String) -> std::io::Result<()> {
+    let port = match ssh_port() {
+        Some(p) => p,
+        None => return Ok(()),
+    };
+    let ssh_config = Config {
+        methods: MethodSet::from(&[MethodKind::PublicKey][..]),
+        keys: vec![host_key()?],
+        ..Default::default()
+    };
+    tracing::info!("Serving git over SSH on {}:{}", addr, port);
+    GitSsh { config }.run_on_address(Arc::new(ssh_config), (addr.as_str(), port)).await
+}
+
+struct GitSsh {
+    config: MyGitHttpConfig,
+}
+
+impl Server for GitSsh {
+    type Handler = Connection;
+
+    fn new_client(&mut self, peer: Option<SocketAddr>) -> Connection {
+        Connection { config: self.config.clone(), peer, user: None, channels: HashMap::new() }
+    }
+}
+
+/// One SSH client; the SSH user name is ignored, the key alone identifies the account
+/// or, for a deploy key, the repository.
+struct Connection {
+    config: MyGitHttpConfig,
+    peer: Option<SocketAddr>,
+    user: Option<GitUser>,
+    channels: HashMap<ChannelId, Channel<Msg>>,
+}
+
+impl Connection {
+    /// Resolves the repository and checks access the same way as git over HTTP.
+    async fn resolve(&self, owner: &str, name: &str, access: GitAccess) -> Result<PathBuf, &'static str> {
+        let repo = self.config.find_repo(owner, name).await.ok_or("Repository not found.")?;
+        if !self.config.permits(self.user.as_ref(), &repo, GitAccess::Read).await {
+            return Err("Repository not found.");
+        }
+        if access == GitAccess::Write {
+            if self.user.as_ref().and_then(GitUser::account).is_some_and(crate::auth::email_verification_pending) {
+                return Err("Verify your email address before pushing.");
+            }
+            if !self.config.permits(self.user.as_ref(), &repo, access).await {
+                return Err("You do not have write access to this repository.");
+            }
+        }
+        let path = repo_path(&repo.user, &repo._id);
+        Ok(std::fs::canonicalize(&path).unwrap_or(path))
+    }
+}
+
+impl Handler for Connection {
+    type Error = russh::Error;
+
+    async fn auth_publickey(&mut self, _user: &str, public_key: &PublicKey) -> Result<Auth, Self::Error> {
+        let fingerprint = public_key.fingerprint(HashAlg::Sha256).to_string();
+        let db = &self.config.db;
+        let user = match db.find_ssh_key(&fingerprint).await {
+            Ok(Some(key)) => match db.find_user_by_id(&key.user).await {
+                Ok(Some(user)) if !user.suspended => {
+                    let _ = db.touch_ssh_key(&key._id).await;
+                    GitUser::Account { user, token: None }
+                }
+                _ => return Ok(Auth::reject()),
+            },
+            Ok(None) => match db.find_deploy_key(&fingerprint).await {
+                Ok(Some(key)) => {
+                    let _ = db.touch_deploy_key(&key._id).await;
+                    GitUser::Deploy { repo: key.repo, name: key.name, write: key.write }
+                }
+                _ => return Ok(Auth::reject()),
+            },
+            Err(_) => return Ok(Auth::reject()),
+        };
+        tracing::debug!("SSH: '{}' authenticated with key {} from {:?}", user.name(), fingerprint, self.peer);
+        self.user = Some(user);
+        Ok(Auth::Accept)
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        channel: Channel<Msg>,
+        reply: ChannelOpenHandle,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.channels.insert(channel.id(), channel);
+        reply.accept().await;
+        Ok(())
+    }
+
+    async fn shell_request(&mut self, channel: ChannelId, session: &mut Session) -> Result<(), Self::Error> {
+        let name = self.user.as_ref().map(GitUser::name).unwrap_or("there");
+        let greeting = format!("Hi {}! You've successfully authenticated, but GitLit does not provide shell access.\r\n", name);
+        session.channel_success(channel)?;
+        session.extended_data(channel, 1, greeting.into_bytes())?;
+        session.exit_status_request(channel, 1)?;
+        session.close(channel)?;
+        Ok(())
+    }
+
+    async fn exec_request(&mut self, channel: ChannelId, data: &[u8], session: &mut Session) -> Result<(), Self::Error> {
+        let command = String::from_utf8_lossy(data).to_string();
+        let resolved = match parse_command(&command) {
+            Some((service, owner, name, access)) => self.resolve(&owner, &name, access).await.map(|path| (service, path)),
+            None => Err("Only git fetch and push are supported."),
+        };
+        let (service, path) = match resolved {
+            Ok(v) => v,
+            Err(msg) => {
+                tracing::warn!("SSH: refused '{}' from {:?}: {}", command, self.peer, msg);
+                session.channel_success(channel)?;
+                session.extended_data(channel, 1, format!("ERROR: {}\n", msg).into_bytes())?;
+                session.exit_status_request(channel, 1)?;
+                session.close(channel)?;
+                return Ok(());
+            }
+        };
+        let channel = match self.channels.remove(&channel) {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+        session.channel_success(channel.id())?;
+        tokio::spawn(run_git(channel, service, path));
+        Ok(())
+    }
+}
+
+/// Runs `git <service>` on the repository with its stdio wired to the channel.
+async fn run_git(channel: Channel<Msg>, service: &'static str, path: PathBuf) {
+    let (mut read, write) = channel.split();
+    let status = match spawn_git(service, &path) {
+        Ok(mut child) => {
+            let mut stdin = child.stdin.take().expect("piped stdin");
+            let mut stdout = child.stdout.take().expect("piped stdout");
+            let mut stderr = child.stderr.take().expect("piped stderr");
+
+            // the client may keep its side open after git is done, so input is not awaited
+            let input = tokio::spawn(async move {
+                let _ = tokio::io::copy(&mut read.make_reader(), &mut stdin).await;
+            });
+            let mut out = write.make_writer();
+            let mut err = write.make_writer_ext(Some(1));
+            let _ = tokio::join!(tokio::io::copy(&mut stdout, &mut out), tokio::io::copy(&mut stderr, &mut err));
+            input.abort();
+            match child.wait().await {
+                Ok(s) => s.code().unwrap_or(1) as u32,
+                Err(_) => 1,
+            }
+        }
+        Err(e) => {
+            tracing::warn!("SSH: could not start git {}: {}", service, e);
+            1
+        }
+    };
+    let _ = write.exit_status(status).await;
+    let _ = write.eof().await;
+    let _ = write.close().await;
+}
+
+fn spawn_git(service: &str, path: &Path) -> std::io::Result<tokio::process::Child> {
+    Command::new("git")
+        .arg(service)
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_git_commands() {
+        let (service, owner, repo, access) = parse_command("git-upload-pack '/alice/project.git'").unwrap();
+        assert_eq!((service, owner.as_str(), repo.as_str(), access), ("upload-pack", "alice", "project", GitAccess::Read));
+        let (service, _, repo, access) = parse_command("git-receive-pack 'alice/project'").unwrap();
+        assert_eq!((service, repo.as_str(), access), ("receive-pack", "project", GitAccess::Write));
+        assert!(parse_command("git-upload-pack '/alice/project/../x.git'").is_none());
+        assert!(parse_command("rm -rf /").is_none());
+    }
+
+    #[test]
+    fn normalizes_public_keys() {
+        let line = " ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAILM+rvN+ot98qgEN796jTiQfZfG1KaT0PtFDJ/XFSqti user@example.com\n";
+        let (encoded, fingerprint) = parse_public_key(line).unwrap();
+        assert_eq!(encoded, "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAILM+rvN+ot98qgEN796jTiQfZfG1KaT0PtFDJ/XFSqti");
+        assert_eq!(fingerprint, "SHA256:UCUiLr7Pjs9wFFJMDByLgc3NrtdU344OgUM45wZPcIQ");
+        assert!(parse_public_key("not a key").is_none());
+    }
+}
@@ -0,0 +1,122 @@
+use bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use tracing::{debug, warn};
+
+use crate::db::Database;
+use crate::errors::AuthError;
+
+/// Failures allowed before an account has to wait between attempts.
+const ACCOUNT_FREE_ATTEMPTS: i32 = 5;
+/// A single address may sign in to several accounts, so it gets more room.
+const IP_FREE_ATTEMPTS: i32 = 20;
+/// Longest wait between attempts; reaching it counts as a lockout.
+const MAX_LOCKOUT_SECS: i64 = 15 * 60;
+/// Failure counters are forgotten this long after the last failure.
+const ATTEMPT_MEMORY_SECS: i64 = 24 * 60 * 60;
+
+/// Counter key for an account; unknown logins are tracked by name so probing them costs the same.
+pub fn account_key(user: Option<&ObjectId>, login: &str) -> String {
+    match user {
+        Some(id) => format!("user:{}", id.to_hex()),
+        None => format!("login:{}", login.trim().to_lowercase()),
+    }
+}
+
+fn keys(account: &str, ip: Option<&str>) -> Vec<(String, i32)> {
+    let mut keys = vec![(account.to_string(), ACCOUNT_FREE_ATTEMPTS)];
+    if let Some(ip) = ip {
+        keys.push((format!("ip:{}", ip), IP_FREE_ATTEMPTS));
+    }
+    keys
+}
+
+/// Wait imposed after `failures` consecutive failures: doubles with every failure past
+/// `free`, capped at [`MAX_LOCKOUT_SECS`].
+pub fn backoff_secs(failures: i32, free: i32) -> Option<i64> {
+    if failures < free {
+        return None;
+    }
+    let exp = (failures - free).min(30) as u32;
+    Some((1i64 << exp).min(MAX_LOCKOUT_SECS))
+}
+
+/// Fails with [`AuthError::TooManyAttempts`] while the account or the address is backing off.
+pub async fn check(db: &Database, account: &str, ip: Option<&str>) -> Result<(), AuthError> {
+    let keys: Vec<String> = keys(account, ip).into_iter().map(|(k, _)| k).collect();
+    let attempts = db
+        .find_login_attempts(&keys)
+        .await
+        .map_err(|e| AuthError::Internal(e.to_string()))?;
+
+    let now = DateTime::now().timestamp_millis();
+    let wait_ms = attempts
+        .iter()
+        .filter_map(|a| a.locked_until)
+        .map(|until| until.timestamp_millis() - now)
+        .max()
+        .unwrap_or(0);
+    if wait_ms > 0 {
+        return Err(AuthError::TooManyAttempts((wait_ms + 999) / 1000));
+    }
+    Ok(())
+}
+
+/// Counts a failed attempt against the account and the address and backs them off as needed.
+pub async fn record_failure(db: &Database, account: &str, ip: Option<&str>) {
+    let now = DateTime::now().timestamp_millis();
+    let forget_at = DateTime::from_millis(now + ATTEMPT_MEMORY_SECS * 1000);
+
+    for (key, free) in keys(account, ip) {
+        let failures = match db.record_login_failure(&key, forget_at).await {
+            Ok(a) => a.failures,
+            Err(e) => {
+                warn!("Could not record failed login for {}: {}", key, e);
+                continue;
+            }
+        };
+        let delay = match backoff_secs(failures, free) {
+            Some(d) => d,
+            None => continue,
+        };
+        if let Err(e) = db.lock_login(&key, DateTime::from_millis(now + delay * 1000)).await {
+            warn!("Could not lock {}: {}", key, e);
+        }
+        if delay >= MAX_LOCKOUT_SECS {
+            warn!("Locked out {} for {}s after {} failed login attempts", key, delay, failures);
+        } else {
+            debug!("Backing off {} for {}s after {} failed login attempts", key, delay, failures);
+        }
+    }
+}
+
+/// Resets the account's counter after a complete sign-in; the address keeps its history.
+pub async fn record_success(db: &Database, user: &ObjectId) {
+    if let Err(e) = db.clear_login_attempts(&account_key(Some(user), "")).await {
+        warn!("Could not reset failed logins for user {}: {}", user.to_hex(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_backoff_below_the_free_attempts() {
+        assert_eq!(backoff_secs(0, 5), None);
+        assert_eq!(backoff_secs(4, 5), None);
+    }
+
+    #[test]
+    fn backoff_doubles_then_caps() {
+        assert_eq!(backoff_secs(5, 5), Some(1));
+        assert_eq!(backoff_secs(6, 5), Some(2));
+        assert_eq!(backoff_secs(9, 5), Some(16));
+        assert_eq!(backoff_secs(20, 5), Some(MAX_LOCKOUT_SECS));
+        assert_eq!(backoff_secs(500, 5), Some(MAX_LOCKOUT_SECS));
+    }
+
+    #[test]
+    fn unknown_logins_are_case_insensitive() {
+        assert_eq!(account_key(None, " Alice "), account_key(None, "alice"));
+    }
+}
@@ -0,0 +1,135 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// Length of one TOTP time step in seconds (RFC 6238 default).
+const STEP_SECS: u64 = 30;
+/// Number of steps accepted on either side of the current one to tolerate clock drift.
+const SKEW_STEPS: i64 = 1;
+const DIGITS: u32 = 6;
+const RECOVERY_CODES: usize = 10;
+
+fn unix_now() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+/// Random 160-bit secret, base32 encoded as authenticator apps expect.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    let issuer = crate::frontend::url_encode(issuer);
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        issuer,
+        crate::frontend::url_encode(account),
+        secret,
+        issuer,
+        DIGITS,
+        STEP_SECS
+    )
+}
+
+/// Renders `uri` as an inline SVG QR code for the enrollment page.
+pub fn qr_svg(uri: &str) -> Option<String> {
+    let code = qrcode::QrCode::new(uri.as_bytes()).ok()?;
+    Some(
+        code.render::<qrcode::render::svg::Color>()
+            .min_dimensions(200, 200)
+            .dark_color(qrcode::render::svg::Color("#000000"))
+            .light_color(qrcode::render::svg::Color("#ffffff"))
+            .build(),
+    )
+}
+
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]]) & 0x7fff_ffff;
+    binary % 10u32.pow(DIGITS)
+}
+
+fn verify_at(secret: &str, code: &str, unix_secs: u64) -> bool {
+    let code = code.trim().replace(' ', "");
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+    let expected: u32 = match code.parse() {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+    let key = match BASE32_NOPAD.decode(secret.as_bytes()) {
+        Ok(k) => k,
+        Err(_) => return false,
+    };
+
+    let step = (unix_secs / STEP_SECS) as i64;
+    (-SKEW_STEPS..=SKEW_STEPS)
+        .map(|d| step + d)
+        .filter(|s| *s >= 0)
+        .any(|s| hotp(&key, s as u64) == expected)
+}
+
+/// Checks a 6-digit code from an authenticator app against `secret`.
+pub fn verify(secret: &str, code: &str) -> bool {
+    verify_at(secret, code, unix_now())
+}
+
+/// One-time recovery codes shown to the user once, formatted as `xxxxx-xxxxx`.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            rng.fill_bytes(&mut bytes);
+            let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("{}-{}", &hex[..5], &hex[5..])
+        })
+        .collect()
+}
+
+/// Recovery codes are stored hashed; they are random enough that a plain SHA-256 suffices.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized = code.trim().to_ascii_lowercase();
+    Sha256::digest(normalized.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B, SHA-1 secret "12345678901234567890", truncated to 6 digits.
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn matches_rfc6238_vectors() {
+        assert!(verify_at(RFC_SECRET, "287082", 59));
+        assert!(verify_at(RFC_SECRET, "081804", 1111111109));
+        assert!(verify_at(RFC_SECRET, "050471", 1111111111));
+        assert!(verify_at(RFC_SECRET, "005924", 1234567890));
+        assert!(!verify_at(RFC_SECRET, "000000", 59));
+        assert!(!verify_at(RFC_SECRET, "28708", 59));
+    }
+
+    #[test]
+    fn recovery_codes_hash_case_insensitively() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODES);
+        assert_eq!(hash_recovery_code(&codes[0]), hash_recovery_code(&codes[0].to_uppercase()));
+    }
+}